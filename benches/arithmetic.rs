@@ -0,0 +1,85 @@
+//! Benchmarks of the group arithmetic behind the SPAKE2+ handshake.
+//!
+//! `share` compares the ways to compute x·G + y·M: plain multiplications,
+//! a multiscalar multiplication, and the precomputed tables the protocols
+//! use. `handshake` reports complete handshakes per second.
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use group::{Group, ff::Field};
+use rand::rngs::OsRng;
+use rusty_pake::{
+    ciphersuite::{Base, Ciphersuite, P256, Ristretto255, Scalar},
+    spake2plus::{
+        client_cipher,
+        handshake::{ClientHandshake, ServerHandshake},
+        secret::SecretScalar,
+    },
+};
+use std::hint::black_box;
+
+const CONTEXT: &[u8] = b"rusty-pake bench";
+
+fn share<S: Ciphersuite>(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("share/{}", S::NAME));
+    let (x, y) = (
+        Scalar::<S>::random(&mut OsRng),
+        Scalar::<S>::random(&mut OsRng),
+    );
+    let (g, m) = (S::Group::generator(), S::m());
+    // Build the tables outside of the measurements
+    S::blind(&x, Base::M, &y);
+
+    group.bench_function("multiplications", |b| {
+        b.iter(|| black_box(g) * black_box(x) + black_box(m) * black_box(y))
+    });
+    group.bench_function("multiscalar", |b| {
+        b.iter(|| S::lincomb(&black_box(g), &black_box(x), &black_box(m), &black_box(y)))
+    });
+    group.bench_function("tables", |b| {
+        b.iter(|| S::blind(&black_box(x), Base::M, &black_box(y)))
+    });
+    group.finish();
+}
+
+fn handshake<S: Ciphersuite>(c: &mut Criterion) {
+    let phi0 = Scalar::<S>::random(&mut OsRng);
+    let phi1 = Scalar::<S>::random(&mut OsRng);
+    let c_point = client_cipher::<S>(&SecretScalar::new(phi1));
+
+    let mut group = c.benchmark_group("handshake");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function(S::NAME, |b| {
+        b.iter(|| {
+            let (client, message) = ClientHandshake::start(
+                &mut OsRng,
+                CONTEXT,
+                "client",
+                "server",
+                SecretScalar::<S>::new(phi0),
+                SecretScalar::new(phi1),
+            );
+            let (server, response) = ServerHandshake::respond(
+                &mut OsRng,
+                CONTEXT,
+                "client",
+                "server",
+                &SecretScalar::new(phi0),
+                c_point,
+                message,
+            )
+            .unwrap();
+            let keys = client.finish(response).unwrap();
+            server.finish(&keys.confirm_p).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    share::<Ristretto255>,
+    share::<P256>,
+    handshake::<Ristretto255>,
+    handshake::<P256>,
+);
+criterion_main!(benches);
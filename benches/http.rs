@@ -0,0 +1,134 @@
+//! Benchmarks of setup, exchange and verify round trips through the server's
+//! router, driven in-process without sockets so that the numbers show the
+//! cost of routing, JSON, decoding and the handshake rather than of the
+//! network.
+//!
+//! The client secrets are derived once with a cheap KDF, as the cost of
+//! password hashing is set by the KDF parameters and would dominate
+//! everything else.
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header::CONTENT_TYPE},
+};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use rand::rngs::OsRng;
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, P256, Ristretto255},
+    server::{self, Randomness},
+    shared::{
+        CONTEXT, ExchangeRequest, ExchangeResponseEncoded, SetupRequest, VerifyRequestEncoded,
+    },
+    spake2plus::{
+        client_cipher,
+        handshake::{ClientHandshake, ServerMessage},
+        kdf::PasswordKdf,
+        rfc9383::{SALT_LEN, client_secret},
+        secret::{Password, SecretScalar},
+    },
+};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+const SERVER_ID: &str = "server";
+const SALT: [u8; SALT_LEN] = [0; SALT_LEN];
+const KDF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+
+/// Posts `body` as JSON to `path` and returns the response status and body.
+async fn post(app: &Router, path: &str, body: &impl Serialize) -> (StatusCode, Vec<u8>) {
+    let request = Request::post(path)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(body).unwrap()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, body.to_vec())
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> T {
+    serde_json::from_slice(body).unwrap()
+}
+
+/// A client with precomputed secrets.
+struct Client<S: Ciphersuite> {
+    phi0: SecretScalar<S>,
+    phi1: SecretScalar<S>,
+}
+
+impl<S: Ciphersuite> Client<S> {
+    fn new() -> Self {
+        let password = Password::new("correct horse battery staple".into());
+        let (phi0, phi1) = client_secret::<S>(&password, "client", SERVER_ID, &SALT, &KDF).unwrap();
+        Self { phi0, phi1 }
+    }
+
+    fn secrets(&self) -> (SecretScalar<S>, SecretScalar<S>) {
+        (
+            SecretScalar::new(*self.phi0.expose_secret()),
+            SecretScalar::new(*self.phi1.expose_secret()),
+        )
+    }
+
+    async fn setup(&self, app: &Router, id: &str) {
+        let (phi0, phi1) = self.secrets();
+        let c = client_cipher::<S>(&phi1);
+        let request = SetupRequest::<S>::new(id.into(), phi0, c, [0; 16], PasswordKdf::default());
+        let (status, _) = post(app, "/setup", &request.encode()).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    async fn exchange_and_verify(&self, app: &Router, id: &str) {
+        let (phi0, phi1) = self.secrets();
+        let (handshake, message) =
+            ClientHandshake::start(&mut OsRng, CONTEXT, id, SERVER_ID, phi0, phi1);
+        let request = ExchangeRequest::<S>::new(id.into(), message.u);
+        let (status, body) = post(app, "/exchange", &request.encode()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let response = parse::<ExchangeResponseEncoded>(&body)
+            .decode::<S>()
+            .unwrap();
+        let keys = handshake
+            .finish(ServerMessage {
+                v: response.v,
+                confirm_v: response.confirm_v,
+            })
+            .unwrap();
+
+        let request = VerifyRequestEncoded::new(id.into(), hex::encode(&keys.confirm_p));
+        let (status, _) = post(app, "/verify", &request).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+fn round_trips<S: Ciphersuite>(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let app = server::router::<S>(SERVER_ID, CONTEXT, Randomness::Os);
+    let client = Client::<S>::new();
+    runtime.block_on(client.setup(&app, "client"));
+
+    let mut group = c.benchmark_group(format!("http/{}", S::NAME));
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("exchange+verify", |b| {
+        b.iter(|| runtime.block_on(client.exchange_and_verify(&app, "client")))
+    });
+    // Setup refuses known ids, so every iteration registers a new client
+    let mut clients = 0u64;
+    group.bench_function("setup+exchange+verify", |b| {
+        b.iter(|| {
+            clients += 1;
+            let id = format!("client-{clients}");
+            runtime.block_on(async {
+                client.setup(&app, &id).await;
+                client.exchange_and_verify(&app, &id).await;
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, round_trips::<Ristretto255>, round_trips::<P256>);
+criterion_main!(benches);
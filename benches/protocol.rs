@@ -0,0 +1,167 @@
+//! Benchmarks of the SPAKE2+ protocol functions and of the encoding of the
+//! messages that carry their results.
+//!
+//! `rfc9383::client_secret` is benchmarked with a cheap KDF. The password
+//! hashing is slow by design and its cost is set by the KDF parameters, so
+//! this measures the rest: the length prefixing and the reduction of the
+//! output to scalars.
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use rand::rngs::OsRng;
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, P256, Ristretto255},
+    shared::{
+        ExchangeRequest, ExchangeRequestEncoded, ExchangeResponse, ExchangeResponseEncoded,
+        SetupRequest, SetupRequestEncoded, VerifyRequestEncoded,
+    },
+    spake2plus::{
+        client_cipher, client_compute_key, client_initial,
+        kdf::PasswordKdf,
+        rfc9383::{SALT_LEN, client_secret},
+        secret::{Password, SecretScalar},
+        server_compute_key, server_initial,
+    },
+};
+use std::hint::black_box;
+
+const CONTEXT: &[u8] = b"rusty-pake bench";
+const IDC: &str = "client";
+const IDS: &str = "server";
+const SALT: [u8; SALT_LEN] = [7; SALT_LEN];
+const KDF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+
+fn secrets<S: Ciphersuite>() -> (SecretScalar<S>, SecretScalar<S>) {
+    let password = Password::new("correct horse battery staple".into());
+    client_secret::<S>(&password, IDC, IDS, &SALT, &KDF).unwrap()
+}
+
+fn protocol<S: Ciphersuite>(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("protocol/{}", S::NAME));
+    let (phi0, phi1) = secrets::<S>();
+    let c_point = client_cipher::<S>(&phi1);
+    let (u, alpha) = client_initial::<S>(&mut OsRng, &phi0);
+    let (v, beta) = server_initial::<S>(&mut OsRng, &phi0);
+
+    let password = Password::new("correct horse battery staple".into());
+    group.bench_function("client_secret", |b| {
+        b.iter(|| client_secret::<S>(black_box(&password), IDC, IDS, &SALT, &KDF).unwrap())
+    });
+    group.bench_function("client_initial", |b| {
+        b.iter(|| client_initial::<S>(&mut OsRng, black_box(&phi0)))
+    });
+    group.bench_function("server_initial", |b| {
+        b.iter(|| server_initial::<S>(&mut OsRng, black_box(&phi0)))
+    });
+    group.bench_function("client_compute_key", |b| {
+        b.iter(|| {
+            client_compute_key::<S>(
+                CONTEXT,
+                IDC,
+                IDS,
+                &phi0,
+                &phi1,
+                black_box(&alpha),
+                black_box(u),
+                black_box(v),
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("server_compute_key", |b| {
+        b.iter(|| {
+            server_compute_key::<S>(
+                CONTEXT,
+                IDC,
+                IDS,
+                &phi0,
+                c_point,
+                black_box(&beta),
+                black_box(u),
+                black_box(v),
+            )
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn encoding<S: Ciphersuite>(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("encoding/{}", S::NAME));
+    let (phi0, phi1) = secrets::<S>();
+    let c_point = client_cipher::<S>(&phi1);
+    let (u, _) = client_initial::<S>(&mut OsRng, &phi0);
+    let (v, _) = server_initial::<S>(&mut OsRng, &phi0);
+    let confirm_v = S::mac(b"key", b"transcript");
+    let kdf = PasswordKdf::default();
+
+    let setup = |phi0| SetupRequest::<S>::new(IDC.into(), phi0, c_point, [7; 16], kdf);
+    group.bench_function("setup/encode", |b| {
+        b.iter_batched(
+            || setup(SecretScalar::new(*phi0.expose_secret())),
+            |request| request.encode(),
+            BatchSize::SmallInput,
+        )
+    });
+    let encoded =
+        serde_json::to_string(&setup(SecretScalar::new(*phi0.expose_secret())).encode()).unwrap();
+    group.bench_function("setup/decode", |b| {
+        b.iter(|| {
+            serde_json::from_str::<SetupRequestEncoded>(black_box(&encoded))
+                .unwrap()
+                .decode::<S>()
+                .unwrap()
+        })
+    });
+
+    group.bench_function("exchange_request/encode", |b| {
+        b.iter(|| ExchangeRequest::<S>::new(IDC.into(), black_box(u)).encode())
+    });
+    let encoded =
+        serde_json::to_string(&ExchangeRequest::<S>::new(IDC.into(), u).encode()).unwrap();
+    group.bench_function("exchange_request/decode", |b| {
+        b.iter(|| {
+            serde_json::from_str::<ExchangeRequestEncoded>(black_box(&encoded))
+                .unwrap()
+                .decode::<S>()
+                .unwrap()
+        })
+    });
+
+    group.bench_function("exchange_response/encode", |b| {
+        b.iter(|| ExchangeResponse::<S>::new(black_box(v), confirm_v.clone()).encode())
+    });
+    let encoded =
+        serde_json::to_string(&ExchangeResponse::<S>::new(v, confirm_v.clone()).encode()).unwrap();
+    group.bench_function("exchange_response/decode", |b| {
+        b.iter(|| {
+            serde_json::from_str::<ExchangeResponseEncoded>(black_box(&encoded))
+                .unwrap()
+                .decode::<S>()
+                .unwrap()
+        })
+    });
+
+    let encoded = serde_json::to_string(&VerifyRequestEncoded::new(
+        IDC.into(),
+        hex::encode(&confirm_v),
+    ))
+    .unwrap();
+    group.bench_function("verify_request/decode", |b| {
+        b.iter(|| {
+            serde_json::from_str::<VerifyRequestEncoded>(black_box(&encoded))
+                .unwrap()
+                .decode::<S>()
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    protocol::<Ristretto255>,
+    protocol::<P256>,
+    encoding::<Ristretto255>,
+    encoding::<P256>,
+);
+criterion_main!(benches);
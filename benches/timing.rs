@@ -0,0 +1,371 @@
+//! A dudect-style check for timing leaks ("Dude, is my code constant
+//! time?", Reparaz, Balasch and Verbauwhede, 2017). Each target runs on
+//! inputs of two classes, a fixed one and random ones, interleaved at random
+//! and prepared outside the timed region. Welch's t-test then compares the
+//! execution times of the two classes, over all measurements and over those
+//! below a few percentiles, which cuts the noise of interrupts and
+//! scheduling. A |t| above 4.5 suggests that the time depends on the class,
+//! and so on the secret input, and one above 10 is all but certain.
+//!
+//! When there is a leak, t grows with the square root of the number of
+//! measurements, so a longer run finds smaller leaks. The early-exit
+//! comparison is there to show that the harness finds one at all.
+//!
+//! Configured with environment variables:
+//! - `MEASUREMENTS`: measurements per target, overriding their defaults
+//!
+//! Arguments other than flags select targets by substring, e.g.
+//! `cargo bench --bench timing -- verify`.
+
+use std::{env, hint::black_box, process::ExitCode, time::Instant};
+
+use group::Group;
+use rand::{Rng, RngCore, rngs::OsRng};
+use rusty_pake::{
+    ciphersuite::{
+        Ciphersuite, Output, P256, Ristretto255, constant_time_eq, random_nonzero_scalar,
+    },
+    shared::{CONTEXT, SetupRequest},
+    spake2plus::{
+        client_cipher,
+        handshake::{ClientHandshake, ServerHandshake},
+        kdf::PasswordKdf,
+        rfc9383::{SALT_LEN, client_secret},
+        secret::{Password, SecretScalar},
+        server_compute_key,
+    },
+};
+
+/// |t| above which a leak is likely.
+const POSSIBLE: f64 = 4.5;
+/// |t| above which a leak is all but certain.
+const DEFINITE: f64 = 10.0;
+
+/// Measurements per batch. The first batch only sets the percentiles.
+const BATCH: usize = 10_000;
+const PERCENTILES: [f64; 5] = [0.5, 0.75, 0.9, 0.95, 0.99];
+
+/// A cheap KDF for the client secrets, whose derivation is not measured.
+const KDF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+const SALT: [u8; SALT_LEN] = [0; SALT_LEN];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Fixed,
+    Random,
+}
+
+/// Welch's t-test, with the means and variances kept online.
+#[derive(Default)]
+struct TTest {
+    n: [f64; 2],
+    mean: [f64; 2],
+    m2: [f64; 2],
+}
+
+impl TTest {
+    fn push(&mut self, class: Class, x: f64) {
+        let i = class as usize;
+        self.n[i] += 1.0;
+        let delta = x - self.mean[i];
+        self.mean[i] += delta / self.n[i];
+        self.m2[i] += delta * (x - self.mean[i]);
+    }
+
+    fn t(&self) -> f64 {
+        if self.n.iter().any(|&n| n < 2.0) {
+            return 0.0;
+        }
+        let var = |i: usize| self.m2[i] / (self.n[i] - 1.0);
+        (self.mean[0] - self.mean[1]) / (var(0) / self.n[0] + var(1) / self.n[1]).sqrt()
+    }
+}
+
+/// Prepares an input of each class, then times `run` on each. Whatever `run`
+/// returns is dropped after the clock stops.
+fn measure<I, R>(
+    classes: &[Class],
+    mut prepare: impl FnMut(Class) -> I,
+    mut run: impl FnMut(I) -> R,
+) -> Vec<u64> {
+    let inputs: Vec<I> = classes.iter().map(|&class| prepare(class)).collect();
+    inputs
+        .into_iter()
+        .map(|input| {
+            let start = Instant::now();
+            let output = black_box(run(black_box(input)));
+            let elapsed = start.elapsed().as_nanos() as u64;
+            drop(output);
+            elapsed
+        })
+        .collect()
+}
+
+/// Times a batch of inputs of the given classes.
+type Measure = Box<dyn FnMut(&[Class]) -> Vec<u64>>;
+
+struct Target {
+    name: String,
+    measurements: usize,
+    /// Whether the target is a control that must leak.
+    leaks: bool,
+    measure: Measure,
+}
+
+impl Target {
+    fn new(
+        name: impl Into<String>,
+        measurements: usize,
+        measure: impl FnMut(&[Class]) -> Vec<u64> + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            measurements,
+            leaks: false,
+            measure: Box::new(measure),
+        }
+    }
+
+    /// Runs the test and returns the largest |t| over all croppings.
+    fn run(&mut self) -> f64 {
+        let classes = |rng: &mut OsRng| -> Vec<Class> {
+            (0..BATCH)
+                .map(|_| match rng.r#gen::<bool>() {
+                    true => Class::Fixed,
+                    false => Class::Random,
+                })
+                .collect()
+        };
+
+        let mut warmup = (self.measure)(&classes(&mut OsRng));
+        warmup.sort_unstable();
+        let thresholds: Vec<u64> = PERCENTILES
+            .iter()
+            .map(|p| warmup[(p * (warmup.len() - 1) as f64) as usize])
+            .collect();
+
+        let mut uncropped = TTest::default();
+        let mut cropped: Vec<TTest> = thresholds.iter().map(|_| TTest::default()).collect();
+        let mut measured = 0;
+        while measured < self.measurements {
+            let classes = classes(&mut OsRng);
+            let times = (self.measure)(&classes);
+            for (&class, &time) in classes.iter().zip(&times) {
+                uncropped.push(class, time as f64);
+                for (test, &threshold) in cropped.iter_mut().zip(&thresholds) {
+                    if time <= threshold {
+                        test.push(class, time as f64);
+                    }
+                }
+            }
+            measured += times.len();
+        }
+
+        std::iter::once(&uncropped)
+            .chain(&cropped)
+            .map(|test| test.t().abs())
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Reference comparison that returns at the first differing byte.
+fn early_exit_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    for (x, y) in a.iter().zip(b) {
+        if x != y {
+            return false;
+        }
+    }
+    true
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// The secret with its last byte flipped, which an early-exit comparison
+/// takes longest to reject, against random bytes, which it rejects at once.
+/// Both classes fail, so only the comparison can tell them apart.
+fn near_miss(secret: &[u8], class: Class) -> Vec<u8> {
+    match class {
+        Class::Fixed => {
+            let mut input = secret.to_vec();
+            *input.last_mut().unwrap() ^= 1;
+            input
+        }
+        Class::Random => random_bytes(secret.len()),
+    }
+}
+
+fn comparisons() -> Vec<Target> {
+    // repeated in each measurement, as one comparison is about as long as
+    // reading the clock
+    const REPEAT: usize = 64;
+    let secret = random_bytes(32);
+    let compare = move |eq: fn(&[u8], &[u8]) -> bool| {
+        let secret = secret.clone();
+        move |classes: &[Class]| {
+            measure(
+                classes,
+                |class| near_miss(&secret, class),
+                |input| (0..REPEAT).all(|_| !eq(black_box(&secret), black_box(&input))),
+            )
+        }
+    };
+    vec![
+        Target {
+            leaks: true,
+            ..Target::new("compare/early-exit", 1_000_000, compare(early_exit_eq))
+        },
+        Target::new(
+            "compare/constant-time",
+            1_000_000,
+            compare(constant_time_eq),
+        ),
+    ]
+}
+
+fn copy<S: Ciphersuite>(scalar: &SecretScalar<S>) -> SecretScalar<S> {
+    SecretScalar::new(*scalar.expose_secret())
+}
+
+fn random_scalar<S: Ciphersuite>() -> SecretScalar<S> {
+    SecretScalar::new(random_nonzero_scalar(&mut OsRng))
+}
+
+/// A fixed scalar of low weight, on which variable-time code is fastest,
+/// against random ones.
+fn scalar<S: Ciphersuite>(class: Class) -> SecretScalar<S> {
+    match class {
+        Class::Fixed => SecretScalar::new(1u64.into()),
+        Class::Random => random_scalar::<S>(),
+    }
+}
+
+fn suite_targets<S: Ciphersuite>() -> Vec<Target> {
+    let password = Password::new("correct horse battery staple".into());
+    let (phi0, phi1) = client_secret::<S>(&password, "client", "server", &SALT, &KDF).unwrap();
+    let c = client_cipher::<S>(&phi1);
+
+    // the server checking the client's confirmation
+    let verify = move |classes: &[Class]| {
+        measure(
+            classes,
+            |class| {
+                let (client, message) = ClientHandshake::start(
+                    &mut OsRng,
+                    CONTEXT,
+                    "client",
+                    "server",
+                    copy(&phi0),
+                    copy(&phi1),
+                );
+                let (server, response) = ServerHandshake::respond(
+                    &mut OsRng, CONTEXT, "client", "server", &phi0, c, message,
+                )
+                .unwrap();
+                let confirm_p = client.finish(response).unwrap().confirm_p.clone();
+                let input = Output::<S>::clone_from_slice(&near_miss(&confirm_p, class));
+                (server, input)
+            },
+            |(server, input)| server.finish(&input).is_err(),
+        )
+    };
+
+    // decoding a registration, with the secret phi0
+    let decode = move |classes: &[Class]| {
+        measure(
+            classes,
+            |class| {
+                let request = SetupRequest::<S>::new(
+                    "client".into(),
+                    scalar::<S>(class),
+                    c,
+                    [0; 16],
+                    PasswordKdf::default(),
+                );
+                request.encode()
+            },
+            |request| request.decode::<S>().is_ok(),
+        )
+    };
+
+    // the server deriving the key from the secret phi0 and beta
+    let u = S::Group::generator() * random_scalar::<S>().expose_secret();
+    let v = S::Group::generator() * random_scalar::<S>().expose_secret();
+    let compute_key = move |classes: &[Class]| {
+        measure(
+            classes,
+            |class| (scalar::<S>(class), scalar::<S>(class)),
+            |(phi0, beta)| {
+                server_compute_key::<S>(CONTEXT, "client", "server", &phi0, c, &beta, u, v)
+            },
+        )
+    };
+
+    let suite = S::NAME.split('-').next().unwrap();
+    vec![
+        Target::new(format!("verify/{suite}"), 20_000, verify),
+        Target::new(format!("decode/{suite}"), 200_000, decode),
+        Target::new(format!("compute_key/{suite}"), 20_000, compute_key),
+    ]
+}
+
+fn main() -> ExitCode {
+    let filters: Vec<String> = env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with('-'))
+        .collect();
+    let measurements: Option<usize> = env::var("MEASUREMENTS")
+        .ok()
+        .map(|value| value.parse().expect("MEASUREMENTS is a number"));
+
+    let targets = comparisons()
+        .into_iter()
+        .chain(suite_targets::<Ristretto255>())
+        .chain(suite_targets::<P256>())
+        .filter(|target| filters.is_empty() || filters.iter().any(|f| target.name.contains(f)));
+
+    println!(
+        "{:<26} {:>12} {:>8}  verdict",
+        "target", "measurements", "max |t|"
+    );
+    let mut unexpected = false;
+    for mut target in targets {
+        if let Some(measurements) = measurements {
+            target.measurements = measurements;
+        }
+        let t = target.run();
+        let verdict = match t {
+            t if t >= DEFINITE => "leaks",
+            t if t >= POSSIBLE => "may leak",
+            _ => "no leak found",
+        };
+        let expected = match target.leaks {
+            true => t >= DEFINITE,
+            false => t < DEFINITE,
+        };
+        unexpected |= !expected;
+        println!(
+            "{:<26} {:>12} {:>8.2}  {}{}",
+            target.name,
+            target.measurements,
+            t,
+            verdict,
+            match (target.leaks, expected) {
+                (true, true) => " (control)",
+                (true, false) => " (control, the harness missed it)",
+                (false, _) => "",
+            }
+        );
+    }
+
+    match unexpected {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusty_pake::{
+    ciphersuite::{P256, Ristretto255},
+    shared::ExchangeResponseEncoded,
+};
+use rusty_pake_fuzz::decode;
+
+fuzz_target!(|data: &[u8]| {
+    decode(data, |encoded: ExchangeResponseEncoded| {
+        let _ = encoded.decode::<Ristretto255>();
+    });
+    decode(data, |encoded: ExchangeResponseEncoded| {
+        let _ = encoded.decode::<P256>();
+    });
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusty_pake::shared::OpaqueRegisterStartRequestEncoded;
+use rusty_pake_fuzz::decode;
+
+fuzz_target!(|data: &[u8]| {
+    decode(data, |encoded: OpaqueRegisterStartRequestEncoded| {
+        let _ = encoded.decode();
+    });
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusty_pake_fuzz::Session;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(session) = Session::parse(data) {
+        session.run();
+    }
+});
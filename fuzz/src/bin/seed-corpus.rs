@@ -0,0 +1,193 @@
+//! Seeds the corpora from real client traffic. Runs the clients of every
+//! protocol against a server that records the requests and responses, and
+//! writes each body to the corpus of its decoder and each protocol run to
+//! the corpus of the `router` target.
+//!
+//! Run from the `fuzz` directory with `cargo run --bin seed-corpus`.
+
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    middleware::{Next, from_fn_with_state},
+    response::Response,
+};
+use rand::rngs::OsRng;
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, P256, Ristretto255},
+    client,
+    server::{self, Randomness},
+    shared::CONTEXT,
+    spake2plus::secret::Password,
+    srp::SrpGroup,
+};
+use rusty_pake_fuzz::{SERVER_ID, Session};
+use sha2::{Digest, Sha256};
+
+/// A request to the server and its response.
+struct Recorded {
+    route: String,
+    request: Vec<u8>,
+    response: Vec<u8>,
+}
+
+type Traffic = Arc<Mutex<Vec<Recorded>>>;
+
+async fn record(State(traffic): State<Traffic>, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let route = parts.uri.path().to_string();
+    let request = to_bytes(body, usize::MAX).await.unwrap();
+    let response = next
+        .run(Request::from_parts(parts, Body::from(request.clone())))
+        .await;
+
+    let (parts, body) = response.into_parts();
+    let response = to_bytes(body, usize::MAX).await.unwrap();
+    traffic.lock().unwrap().push(Recorded {
+        route,
+        request: request.to_vec(),
+        response: response.to_vec(),
+    });
+    Response::from_parts(parts, Body::from(response))
+}
+
+/// The decoder targets of the request and of the response body of a route.
+fn targets(route: &str) -> (Option<&'static str>, Option<&'static str>) {
+    match route {
+        "/setup" => (Some("decode_setup_request"), None),
+        "/prepare" => (None, Some("decode_prepare_response")),
+        "/exchange" => (
+            Some("decode_exchange_request"),
+            Some("decode_exchange_response"),
+        ),
+        "/verify" => (Some("decode_verify_request"), None),
+        "/cpace/setup" => (Some("decode_cpace_setup_request"), None),
+        "/cpace/exchange" => (
+            Some("decode_cpace_exchange_request"),
+            Some("decode_cpace_exchange_response"),
+        ),
+        "/cpace/verify" => (Some("decode_cpace_verify_request"), None),
+        "/srp/setup" => (Some("decode_srp_setup_request"), None),
+        "/srp/exchange" => (None, Some("decode_srp_exchange_response")),
+        "/srp/verify" => (
+            Some("decode_srp_verify_request"),
+            Some("decode_srp_verify_response"),
+        ),
+        "/opaque/register/start" => (
+            Some("decode_opaque_register_start_request"),
+            Some("decode_opaque_register_start_response"),
+        ),
+        "/opaque/register/finish" => (Some("decode_opaque_register_finish_request"), None),
+        "/opaque/login/start" => (
+            Some("decode_opaque_login_start_request"),
+            Some("decode_opaque_login_start_response"),
+        ),
+        "/opaque/login/finish" => (Some("decode_opaque_login_finish_request"), None),
+        _ => (None, None),
+    }
+}
+
+/// Writes `data` to the corpus of `target`, named by its hash like the
+/// inputs libFuzzer adds.
+fn write(target: &str, data: &[u8]) {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("corpus")
+        .join(target);
+    fs::create_dir_all(&directory).unwrap();
+    let name = hex::encode(&Sha256::digest(data)[..20]);
+    fs::write(directory.join(name), data).unwrap();
+}
+
+/// Runs one protocol's clients, with a successful run and failed ones.
+async fn run_clients<S: Ciphersuite>(url: &str, protocol: &str) -> Result<(), anyhow::Error> {
+    let password = &Password::from("correct horse battery staple");
+    let wrong = &Password::from("wrong");
+    let rng = &mut OsRng;
+    match protocol {
+        "spake2plus" => {
+            client::perform_setup::<S>(rng, url, SERVER_ID, "alice", password).await?;
+            let exchange =
+                client::perform_exchange::<S>(rng, url, SERVER_ID, "alice", password, CONTEXT)
+                    .await?;
+            client::perform_verify(url, "alice", &exchange.confirmation).await?;
+            let _ =
+                client::perform_exchange::<S>(rng, url, SERVER_ID, "alice", wrong, CONTEXT).await;
+            client::perform_verify(url, "alice", &exchange.confirmation).await?;
+        }
+        "cpace" => {
+            client::perform_cpace_setup(rng, url, SERVER_ID, "bob", password).await?;
+            let exchange =
+                client::perform_cpace_exchange(rng, url, SERVER_ID, "bob", password, CONTEXT)
+                    .await?;
+            client::perform_cpace_verify(url, "bob", &exchange.confirmation).await?;
+        }
+        "srp" => {
+            client::perform_srp_setup(rng, url, "carol", password, SrpGroup::G1024).await?;
+            client::perform_srp_exchange(rng, url, "carol", password).await?;
+            let _ = client::perform_srp_exchange(rng, url, "carol", wrong).await;
+        }
+        "opaque" => {
+            client::perform_opaque_registration(rng, url, SERVER_ID, "dave", password).await?;
+            client::perform_opaque_login(rng, url, SERVER_ID, "dave", password, CONTEXT).await?;
+            let _ = client::perform_opaque_login(rng, url, SERVER_ID, "dave", wrong, CONTEXT).await;
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+async fn seed<S: Ciphersuite>(p256: bool) -> Result<(), anyhow::Error> {
+    for protocol in ["spake2plus", "cpace", "srp", "opaque"] {
+        let traffic = Traffic::default();
+        let app = server::router::<S>(SERVER_ID, CONTEXT, Randomness::Os)
+            .layer(from_fn_with_state(traffic.clone(), record));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let server = tokio::spawn(async move { axum::serve(listener, app).await });
+
+        run_clients::<S>(&url, protocol).await?;
+        server.abort();
+
+        let traffic = traffic.lock().unwrap();
+        for recorded in traffic.iter() {
+            let (request, response) = targets(&recorded.route);
+            for (target, body) in [(request, &recorded.request), (response, &recorded.response)] {
+                if let Some(target) = target.filter(|_| !body.is_empty()) {
+                    write(target, body);
+                }
+            }
+        }
+        let session = Session {
+            p256,
+            long_ids: false,
+            requests: traffic
+                .iter()
+                .filter_map(|recorded| {
+                    let route = rusty_pake_fuzz::ROUTES
+                        .into_iter()
+                        .find(|&route| route == recorded.route)?;
+                    Some((route, recorded.request.as_slice()))
+                })
+                .collect(),
+        };
+        write("router", &session.encode());
+        println!(
+            "seeded {} with {} requests of {}",
+            S::NAME,
+            traffic.len(),
+            protocol
+        );
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    seed::<Ristretto255>(false).await?;
+    seed::<P256>(true).await
+}
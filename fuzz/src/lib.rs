@@ -0,0 +1,181 @@
+//! Helpers shared by the fuzz targets and the corpus seeder.
+
+use std::sync::LazyLock;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header::CONTENT_TYPE},
+};
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, P256, Ristretto255},
+    server::{self, Randomness},
+    shared::CONTEXT,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+/// The routes that take a request body, in the order their indices are
+/// encoded in the input of the `router` target.
+pub const ROUTES: [&str; 14] = [
+    "/setup",
+    "/prepare",
+    "/exchange",
+    "/verify",
+    "/cpace/setup",
+    "/cpace/exchange",
+    "/cpace/verify",
+    "/srp/setup",
+    "/srp/exchange",
+    "/srp/verify",
+    "/opaque/register/start",
+    "/opaque/register/finish",
+    "/opaque/login/start",
+    "/opaque/login/finish",
+];
+
+/// Longest sequence of requests run for one input, to keep inputs fast.
+const MAX_REQUESTS: usize = 16;
+
+pub const SERVER_ID: &str = "fuzz";
+
+/// Length the ids of a [`Session`] with `long_ids` are padded to, one more
+/// than OPAQUE can encode. Inputs cannot hold a body this long.
+pub const LONG_ID_LEN: usize = u16::MAX as usize + 1;
+
+/// Runs `decode` on `data` if it deserializes as the JSON of `T`. Decoders
+/// may reject anything, but must not panic.
+pub fn decode<T: DeserializeOwned>(data: &[u8], decode: impl FnOnce(T)) {
+    if let Ok(encoded) = serde_json::from_slice::<T>(data) {
+        decode(encoded);
+    }
+}
+
+/// A sequence of requests to a fresh server, encoded as a flags byte
+/// followed by requests, each a route index byte, a little-endian `u16` body
+/// length and the body. The lowest bit of the flags picks P256 over
+/// ristretto255, and the next one sets `long_ids`.
+pub struct Session<'a> {
+    pub p256: bool,
+    /// Pads the `id` of every body to [`LONG_ID_LEN`] bytes before sending
+    /// it.
+    pub long_ids: bool,
+    pub requests: Vec<(&'static str, &'a [u8])>,
+}
+
+impl<'a> Session<'a> {
+    /// Parses as many whole requests as `data` holds, up to a limit.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let (&flags, mut data) = data.split_first()?;
+        let mut requests = Vec::new();
+        while let [route, len_lo, len_hi, rest @ ..] = data {
+            if requests.len() == MAX_REQUESTS {
+                break;
+            }
+            let len = u16::from_le_bytes([*len_lo, *len_hi]) as usize;
+            let route = ROUTES[*route as usize % ROUTES.len()];
+            let (body, rest) = rest.split_at(len.min(rest.len()));
+            requests.push((route, body));
+            data = rest;
+        }
+        Some(Session {
+            p256: flags & 1 == 1,
+            long_ids: flags & 2 == 2,
+            requests,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![self.p256 as u8 | (self.long_ids as u8) << 1];
+        for (route, body) in &self.requests {
+            let index = ROUTES.iter().position(|r| r == route).expect("known route");
+            let len = u16::try_from(body.len()).expect("body fits in 64 KiB");
+            data.push(index as u8);
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(body);
+        }
+        data
+    }
+
+    /// Sends the requests to a fresh in-process server and fails on any 5xx.
+    /// Whatever a client sends, the server answers it with a 2xx or a 4xx,
+    /// and never panics or poisons a lock.
+    pub fn run(&self) {
+        match self.p256 {
+            true => self.run_suite::<P256>(),
+            false => self.run_suite::<Ristretto255>(),
+        }
+    }
+
+    fn run_suite<S: Ciphersuite>(&self) {
+        static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .expect("runtime builds")
+        });
+
+        let app = server::router::<S>(SERVER_ID, CONTEXT, Randomness::Os);
+        RUNTIME.block_on(async {
+            for (route, body) in &self.requests {
+                let body = match self.long_ids {
+                    true => lengthen_id(body),
+                    false => body.to_vec(),
+                };
+                let status = post(&app, route, body).await;
+                assert!(!status.is_server_error(), "{} returned {}", route, status);
+            }
+        });
+    }
+}
+
+/// Pads the `id` of a JSON object to [`LONG_ID_LEN`] bytes, leaving other
+/// bodies as they are.
+fn lengthen_id(body: &[u8]) -> Vec<u8> {
+    let Ok(Value::Object(mut fields)) = serde_json::from_slice(body) else {
+        return body.to_vec();
+    };
+    if let Some(Value::String(id)) = fields.get_mut("id") {
+        let padding = LONG_ID_LEN.saturating_sub(id.len());
+        id.extend(std::iter::repeat_n('x', padding));
+    }
+    serde_json::to_vec(&fields).expect("JSON values serialize")
+}
+
+/// Posts `body` as JSON to `route`, reads the whole response and returns its
+/// status.
+async fn post(app: &Router, route: &str, body: Vec<u8>) -> StatusCode {
+    let request = Request::post(route)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("request builds");
+    let response = app.clone().oneshot(request).await.expect("infallible");
+    let status = response.status();
+    to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body is in memory");
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Replays the seeded sessions of the `router` target, as they were
+    /// recorded and with ids too long for OPAQUE.
+    #[test]
+    fn seeded_sessions_run() {
+        let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/corpus/router");
+        for entry in fs::read_dir(corpus).expect("router corpus exists") {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            let mut session = Session::parse(&data).expect("seeds hold a flags byte");
+            for long_ids in [false, true] {
+                session.long_ids = long_ids;
+                session.run();
+            }
+        }
+    }
+}
@@ -1,5 +1,14 @@
-use rusty_pake::client;
-use std::io::{self, Write};
+use rand::rngs::OsRng;
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, P256, Ristretto255},
+    client,
+    shared::CONTEXT,
+    spake2plus::secret::Password,
+};
+use std::{
+    env,
+    io::{self, Write},
+};
 
 #[tokio::main]
 async fn main() {
@@ -14,8 +23,30 @@ async fn main() {
     };
     println!("Retrieved server id: {}", &server_id);
 
+    let suite = match client::get_server_suite(&server_ip).await {
+        Ok(suite) => suite,
+        Err(e) => {
+            eprintln!("Failed to get server suite: {:?}", e);
+            return;
+        }
+    };
+    println!("Retrieved server suite: {}", &suite);
+
+    // Must match the context the server was started with
+    let context = env::var("CONTEXT")
+        .map(String::into_bytes)
+        .unwrap_or(CONTEXT.to_vec());
+
+    match suite.as_str() {
+        Ristretto255::NAME => run::<Ristretto255>(&server_ip, &server_id, &context).await,
+        P256::NAME => run::<P256>(&server_ip, &server_id, &context).await,
+        _ => eprintln!("unsupported suite"),
+    }
+}
+
+async fn run<S: Ciphersuite>(server_ip: &str, server_id: &str, context: &[u8]) {
     let mut saved_id: Option<String> = None;
-    let mut saved_key: Option<String> = None;
+    let mut saved_confirmation: Option<String> = None;
 
     println!();
     loop {
@@ -25,9 +56,12 @@ async fn main() {
                 let client_id = prompt_saved("Enter client ID:", saved_id.as_deref())
                     .expect("need to provide client id!");
                 saved_id = Some(client_id.clone());
-                let password = prompt("Enter password:").expect("need to enter password!");
-                if let Err(e) =
-                    client::perform_setup(&server_ip, &server_id, &client_id, &password).await
+                let password =
+                    Password::new(prompt("Enter password:").expect("need to enter password!"));
+                if let Err(e) = client::perform_setup::<S>(
+                    &mut OsRng, server_ip, server_id, &client_id, &password,
+                )
+                .await
                 {
                     eprintln!("Error during setup: {}", e);
                 }
@@ -36,12 +70,16 @@ async fn main() {
                 let client_id = prompt_saved("Enter client ID:", saved_id.as_deref())
                     .expect("need to provide client id!");
                 saved_id = Some(client_id.clone());
-                let password = prompt("Enter password:").expect("need to enter password!");
+                let password =
+                    Password::new(prompt("Enter password:").expect("need to enter password!"));
 
-                match client::perform_exchange(&server_ip, &server_id, &client_id, &password).await
+                match client::perform_exchange::<S>(
+                    &mut OsRng, server_ip, server_id, &client_id, &password, context,
+                )
+                .await
                 {
-                    Ok(key) => {
-                        saved_key = Some(key);
+                    Ok(exchange) => {
+                        saved_confirmation = Some(exchange.confirmation);
                     }
                     Err(e) => {
                         eprintln!("Error during exchange: {}", e);
@@ -52,9 +90,10 @@ async fn main() {
                 let client_id = prompt_saved("Enter client ID:", saved_id.as_deref())
                     .expect("need to provide client id!");
                 saved_id = Some(client_id.clone());
-                let key =
-                    prompt_saved("Enter key", saved_key.as_deref()).expect("need to enter key!");
-                if let Err(e) = client::perform_verify(&server_ip, &client_id, &key).await {
+                let confirmation =
+                    prompt_saved("Enter confirmation", saved_confirmation.as_deref())
+                        .expect("need to enter confirmation!");
+                if let Err(e) = client::perform_verify(server_ip, &client_id, &confirmation).await {
                     eprintln!("Error during exchange: {}", e);
                 }
             }
@@ -1,20 +1,27 @@
-use rusty_pake::client;
+use rand::rngs::OsRng;
+use rusty_pake::{
+    ciphersuite::Ristretto255, client, shared::CONTEXT, spake2plus::secret::Password,
+};
 
 #[tokio::main]
 async fn main() {
     let ip = "http://localhost:3000";
     let client_id = "Alice";
-    let password = "ilovebob123";
+    let password = &Password::from("ilovebob123");
 
     let server_id = &client::get_server_id(ip).await.unwrap();
 
-    client::perform_setup(ip, server_id, client_id, password)
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, client_id, password)
         .await
         .unwrap();
 
-    let key = client::perform_exchange(ip, server_id, client_id, password)
+    let exchange = client::perform_exchange::<Ristretto255>(
+        &mut OsRng, ip, server_id, client_id, password, CONTEXT,
+    )
+    .await
+    .unwrap();
+
+    client::perform_verify(ip, client_id, &exchange.confirmation)
         .await
         .unwrap();
-
-    client::perform_verify(ip, client_id, &key).await.unwrap();
 }
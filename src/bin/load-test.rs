@@ -0,0 +1,505 @@
+//! Load generator for sizing servers. Registers `USERS` clients, then runs
+//! SPAKE2+ exchanges from `CONCURRENCY` workers for `DURATION` seconds and
+//! reports the throughput and latency percentiles of each endpoint, and its
+//! failures by status code.
+//!
+//! Each client hashes its password once during setup and reuses the secrets
+//! for every exchange, so that the generator measures the server rather than
+//! its own password hashing.
+
+use hdrhistogram::Histogram;
+use rand::{Rng, rngs::OsRng};
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, P256, Ristretto255},
+    client,
+    shared::{
+        CONTEXT, ExchangeRequest, ExchangeResponseEncoded, PrepareRequestEncoded, SetupRequest,
+        VerifyRequestEncoded,
+    },
+    spake2plus::{
+        client_cipher,
+        handshake::{ClientHandshake, ServerMessage},
+        kdf::PasswordKdf,
+        rfc9383,
+        secret::{Password, SecretScalar},
+    },
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+struct Config {
+    url: String,
+    users: usize,
+    concurrency: usize,
+    duration: Duration,
+    verify_ratio: f64,
+    kdf: PasswordKdf,
+    context: Vec<u8>,
+    json: bool,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        let users = var("USERS", 4);
+        let config = Config {
+            url: var("SERVER_URL", "http://localhost:3000".to_string()),
+            users,
+            concurrency: var("CONCURRENCY", users),
+            duration: Duration::from_secs(var("DURATION", 10)),
+            verify_ratio: var("VERIFY_RATIO", 1.0),
+            kdf: var("KDF", PasswordKdf::default()),
+            context: env::var("CONTEXT")
+                .map(String::into_bytes)
+                .unwrap_or(CONTEXT.to_vec()),
+            json: var("OUTPUT", "text".to_string()) == "json",
+        };
+        // Workers get disjoint users, as concurrent exchanges of one client
+        // would replace each other's pending handshake
+        assert!(
+            (1..=config.users).contains(&config.concurrency),
+            "CONCURRENCY must be between 1 and USERS"
+        );
+        assert!(
+            (0.0..=1.0).contains(&config.verify_ratio),
+            "VERIFY_RATIO must be between 0 and 1"
+        );
+        config
+    }
+}
+
+/// Reads and parses the environment variable `name`, or returns `default`
+/// if it is not set.
+fn var<T: FromStr>(name: &str, default: T) -> T
+where
+    T::Err: Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|error| panic!("invalid {}={}: {}", name, value, error)),
+        Err(_) => default,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Endpoint {
+    Prepare,
+    Exchange,
+    Verify,
+}
+
+impl Endpoint {
+    const ALL: [Endpoint; 3] = [Endpoint::Prepare, Endpoint::Exchange, Endpoint::Verify];
+
+    fn path(self) -> &'static str {
+        match self {
+            Endpoint::Prepare => "/prepare",
+            Endpoint::Exchange => "/exchange",
+            Endpoint::Verify => "/verify",
+        }
+    }
+}
+
+/// What happened to the requests to one endpoint. Latencies are only
+/// recorded for successful requests, in microseconds up to a minute.
+struct EndpointStats {
+    latencies: Histogram<u64>,
+    failures: BTreeMap<String, u64>,
+}
+
+impl EndpointStats {
+    fn new() -> Self {
+        Self {
+            latencies: Histogram::new_with_bounds(1, 60_000_000, 3).unwrap(),
+            failures: BTreeMap::new(),
+        }
+    }
+
+    fn succeed(&mut self, latency: Duration) {
+        self.latencies.saturating_record(latency.as_micros() as u64);
+    }
+
+    fn fail(&mut self, reason: impl ToString) {
+        *self.failures.entry(reason.to_string()).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: &EndpointStats) {
+        self.latencies.add(&other.latencies).unwrap();
+        for (reason, count) in &other.failures {
+            *self.failures.entry(reason.clone()).or_default() += count;
+        }
+    }
+}
+
+struct Stats(BTreeMap<Endpoint, EndpointStats>);
+
+impl Stats {
+    fn new() -> Self {
+        Self(
+            Endpoint::ALL
+                .into_iter()
+                .map(|endpoint| (endpoint, EndpointStats::new()))
+                .collect(),
+        )
+    }
+
+    fn endpoint(&mut self, endpoint: Endpoint) -> &mut EndpointStats {
+        self.0.get_mut(&endpoint).unwrap()
+    }
+}
+
+/// A registered client and the secrets derived from its password.
+struct User<S: Ciphersuite> {
+    id: String,
+    phi0: SecretScalar<S>,
+    phi1: SecretScalar<S>,
+}
+
+struct Worker<'a> {
+    http: reqwest::Client,
+    config: &'a Config,
+    server_id: &'a str,
+    stats: Stats,
+}
+
+impl Worker<'_> {
+    /// Posts `body` to `endpoint` and returns the response body and the
+    /// latency, or records the failure and returns `None`. The caller records
+    /// the success once it has checked the body.
+    async fn post(
+        &mut self,
+        endpoint: Endpoint,
+        body: &impl Serialize,
+    ) -> Option<(Vec<u8>, Duration)> {
+        let start = Instant::now();
+        let response = self
+            .http
+            .post(format!("{}{}", self.config.url, endpoint.path()))
+            .json(body)
+            .send()
+            .await;
+        let stats = self.stats.endpoint(endpoint);
+        let response = match response {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                stats.fail(response.status().as_u16());
+                return None;
+            }
+            Err(_) => {
+                stats.fail("transport");
+                return None;
+            }
+        };
+        match response.bytes().await {
+            Ok(body) => Some((body.to_vec(), start.elapsed())),
+            Err(_) => {
+                stats.fail("transport");
+                None
+            }
+        }
+    }
+
+    /// Runs one exchange for `user` and verifies it with probability
+    /// `verify_ratio`.
+    async fn exchange<S: Ciphersuite>(&mut self, user: &User<S>) {
+        let request = PrepareRequestEncoded::new(user.id.clone());
+        let Some((_, latency)) = self.post(Endpoint::Prepare, &request).await else {
+            return;
+        };
+        self.stats.endpoint(Endpoint::Prepare).succeed(latency);
+
+        let (handshake, message) = ClientHandshake::start(
+            &mut OsRng,
+            &self.config.context,
+            &user.id,
+            self.server_id,
+            SecretScalar::<S>::new(*user.phi0.expose_secret()),
+            SecretScalar::new(*user.phi1.expose_secret()),
+        );
+        let request = ExchangeRequest::<S>::new(user.id.clone(), message.u);
+        let Some((body, latency)) = self.post(Endpoint::Exchange, &request.encode()).await else {
+            return;
+        };
+        let keys = serde_json::from_slice::<ExchangeResponseEncoded>(&body)
+            .ok()
+            .and_then(|response| response.decode::<S>().ok())
+            .and_then(|response| {
+                handshake
+                    .finish(ServerMessage {
+                        v: response.v,
+                        confirm_v: response.confirm_v,
+                    })
+                    .ok()
+            });
+        let Some(keys) = keys else {
+            self.stats.endpoint(Endpoint::Exchange).fail("invalid");
+            return;
+        };
+        self.stats.endpoint(Endpoint::Exchange).succeed(latency);
+
+        if OsRng.gen_bool(self.config.verify_ratio) {
+            let request = VerifyRequestEncoded::new(user.id.clone(), hex::encode(&keys.confirm_p));
+            if let Some((_, latency)) = self.post(Endpoint::Verify, &request).await {
+                self.stats.endpoint(Endpoint::Verify).succeed(latency);
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    url: String,
+    suite: &'static str,
+    users: usize,
+    concurrency: usize,
+    duration_secs: f64,
+    verify_ratio: f64,
+    endpoints: Vec<EndpointReport>,
+}
+
+#[derive(Serialize)]
+struct EndpointReport {
+    endpoint: &'static str,
+    successes: u64,
+    /// Successful requests per second.
+    throughput: f64,
+    latency_ms: Latencies,
+    /// The number of failed requests by status code, `transport` for
+    /// connection errors, or `invalid` for responses that did not decode or
+    /// whose confirmation did not verify.
+    failures: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+struct Latencies {
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    p999: f64,
+    max: f64,
+}
+
+impl EndpointReport {
+    fn new(endpoint: Endpoint, stats: &EndpointStats, elapsed: Duration) -> Self {
+        let latencies = &stats.latencies;
+        let ms = |us: u64| us as f64 / 1000.0;
+        Self {
+            endpoint: endpoint.path(),
+            successes: latencies.len(),
+            throughput: latencies.len() as f64 / elapsed.as_secs_f64(),
+            latency_ms: Latencies {
+                p50: ms(latencies.value_at_quantile(0.5)),
+                p90: ms(latencies.value_at_quantile(0.9)),
+                p99: ms(latencies.value_at_quantile(0.99)),
+                p999: ms(latencies.value_at_quantile(0.999)),
+                max: ms(latencies.max()),
+            },
+            failures: stats.failures.clone(),
+        }
+    }
+}
+
+impl Report {
+    fn print(&self) {
+        println!(
+            "{} ({}): {} users, {} workers, {:.1}s, verify ratio {}",
+            self.url,
+            self.suite,
+            self.users,
+            self.concurrency,
+            self.duration_secs,
+            self.verify_ratio
+        );
+        println!(
+            "{:<10} {:>10} {:>10} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "endpoint", "ok", "ok/s", "p50 ms", "p90 ms", "p99 ms", "p99.9 ms", "max ms"
+        );
+        for endpoint in &self.endpoints {
+            let latency = &endpoint.latency_ms;
+            println!(
+                "{:<10} {:>10} {:>10.1} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+                endpoint.endpoint,
+                endpoint.successes,
+                endpoint.throughput,
+                latency.p50,
+                latency.p90,
+                latency.p99,
+                latency.p999,
+                latency.max
+            );
+        }
+
+        let failures: Vec<_> = self
+            .endpoints
+            .iter()
+            .flat_map(|endpoint| {
+                endpoint.failures.iter().map(move |(reason, count)| {
+                    format!("{} {}: {}", endpoint.endpoint, reason, count)
+                })
+            })
+            .collect();
+        match failures.is_empty() {
+            true => println!("no failures"),
+            false => println!("failures:\n  {}", failures.join("\n  ")),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let config = Config::from_env();
+
+    let server_id = match client::get_server_id(&config.url).await {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Failed to get server id: {:?}", e);
+            return;
+        }
+    };
+    let suite = match client::get_server_suite(&config.url).await {
+        Ok(suite) => suite,
+        Err(e) => {
+            eprintln!("Failed to get server suite: {:?}", e);
+            return;
+        }
+    };
+
+    let result = match suite.as_str() {
+        Ristretto255::NAME => run::<Ristretto255>(config, server_id).await,
+        P256::NAME => run::<P256>(config, server_id).await,
+        _ => Err(anyhow::anyhow!("unsupported suite {}", suite)),
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+    }
+}
+
+async fn run<S: Ciphersuite>(config: Config, server_id: String) -> Result<(), anyhow::Error> {
+    let config = Arc::new(config);
+    let server_id = Arc::new(server_id);
+
+    // Ids are unique per run, so that runs against the same server do not
+    // collide with the clients of earlier ones
+    let run_id: u32 = OsRng.r#gen();
+    if !config.json {
+        println!("registering {} users...", config.users);
+    }
+    let setups: Vec<_> = (0..config.users)
+        .map(|i| {
+            let (config, server_id) = (config.clone(), server_id.clone());
+            tokio::spawn(async move {
+                setup::<S>(&config, &server_id, format!("load-{:08x}-{}", run_id, i)).await
+            })
+        })
+        .collect();
+    let mut users = Vec::with_capacity(config.users);
+    for setup in setups {
+        users.push(setup.await??);
+    }
+
+    // Worker i takes users i, i + concurrency, ...
+    let mut partitions: Vec<Vec<User<S>>> = (0..config.concurrency).map(|_| Vec::new()).collect();
+    for (i, user) in users.into_iter().enumerate() {
+        partitions[i % config.concurrency].push(user);
+    }
+
+    if !config.json {
+        println!("running for {}s...", config.duration.as_secs());
+    }
+    let start = Instant::now();
+    let deadline = start + config.duration;
+    let workers: Vec<_> = partitions
+        .into_iter()
+        .map(|users| {
+            let (config, server_id) = (config.clone(), server_id.clone());
+            tokio::spawn(async move {
+                let mut worker = Worker {
+                    http: reqwest::Client::new(),
+                    config: &config,
+                    server_id: &server_id,
+                    stats: Stats::new(),
+                };
+                for user in users.iter().cycle() {
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                    worker.exchange(user).await;
+                }
+                worker.stats
+            })
+        })
+        .collect();
+
+    let mut stats = Stats::new();
+    for worker in workers {
+        for (endpoint, endpoint_stats) in worker.await?.0 {
+            stats.endpoint(endpoint).merge(&endpoint_stats);
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let report = Report {
+        url: config.url.clone(),
+        suite: S::NAME,
+        users: config.users,
+        concurrency: config.concurrency,
+        duration_secs: elapsed.as_secs_f64(),
+        verify_ratio: config.verify_ratio,
+        endpoints: stats
+            .0
+            .iter()
+            .map(|(&endpoint, stats)| EndpointReport::new(endpoint, stats, elapsed))
+            .collect(),
+    };
+    match config.json {
+        true => println!("{}", serde_json::to_string_pretty(&report)?),
+        false => report.print(),
+    }
+    Ok(())
+}
+
+/// Registers a user with a random password.
+async fn setup<S: Ciphersuite>(
+    config: &Config,
+    server_id: &str,
+    id: String,
+) -> Result<User<S>, anyhow::Error> {
+    let password = Password::new(hex::encode(OsRng.r#gen::<[u8; 16]>()));
+    let salt = rfc9383::generate_salt(&mut OsRng);
+
+    // Password hashing is slow on purpose, keep it off the runtime's workers
+    let (kdf, secrets_id, server) = (config.kdf, id.clone(), server_id.to_string());
+    let (phi0, phi1) = tokio::task::spawn_blocking(move || {
+        rfc9383::client_secret::<S>(&password, &secrets_id, &server, &salt, &kdf)
+    })
+    .await??;
+
+    let c = client_cipher::<S>(&phi1);
+    let request = SetupRequest::<S>::new(
+        id.clone(),
+        SecretScalar::new(*phi0.expose_secret()),
+        c,
+        salt,
+        kdf,
+    );
+    let response = reqwest::Client::new()
+        .post(format!("{}/setup", config.url))
+        .json(&request.encode())
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "setup of {} failed: server returned {}",
+            id,
+            response.status()
+        );
+    }
+    Ok(User { id, phi0, phi1 })
+}
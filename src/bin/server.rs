@@ -1,4 +1,8 @@
-use rusty_pake::server::run;
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, P256, Ristretto255},
+    server::{Randomness, run},
+    shared::CONTEXT,
+};
 use std::env;
 
 #[tokio::main]
@@ -8,12 +12,36 @@ async fn main() {
         .map(|p| p.parse::<u32>().unwrap())
         .unwrap_or(3000);
 
+    let suite = env::var("SUITE").unwrap_or("ristretto255".into());
+    let context = env::var("CONTEXT")
+        .map(String::into_bytes)
+        .unwrap_or(CONTEXT.to_vec());
+    let randomness = env::var("SEED")
+        .map(|seed| Randomness::Seeded(seed.parse::<u64>().unwrap()))
+        .unwrap_or_default();
+
     println!("starting server with id: {}", &id);
+    if let Randomness::Seeded(seed) = randomness {
+        println!(
+            "WARNING: using seeded randomness ({}), all secrets are predictable",
+            seed
+        );
+    }
 
     tracing_subscriber::fmt()
         .with_target(false)
         .compact()
         .init();
 
-    run(port, &id).await;
+    match suite.as_str() {
+        "ristretto255" => {
+            println!("using ciphersuite: {}", Ristretto255::NAME);
+            run::<Ristretto255>(port, &id, &context, randomness).await;
+        }
+        "p256" => {
+            println!("using ciphersuite: {}", P256::NAME);
+            run::<P256>(port, &id, &context, randomness).await;
+        }
+        _ => eprintln!("unknown suite {}, expected ristretto255 or p256", suite),
+    }
 }
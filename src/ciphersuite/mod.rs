@@ -0,0 +1,307 @@
+//! Ciphersuites for the PAKE protocols in this crate.
+//!
+//! A ciphersuite fixes the prime-order group, the hash function, the KDF and
+//! the MAC. The KDF and MAC default to HKDF and HMAC over the suite's hash.
+
+mod p256;
+mod precomputed;
+mod ristretto;
+
+pub use p256::P256;
+pub use ristretto::Ristretto255;
+pub(crate) use ristretto::hash_to_group;
+
+use group::{
+    Group,
+    ff::{Field, PrimeField},
+};
+use hkdf::SimpleHkdf;
+use hmac::{Mac, SimpleHmac, digest::core_api::BlockSizeUser};
+use rand_core::CryptoRngCore;
+use sha2::Digest;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+/// Scalar of the group of the ciphersuite `S`.
+pub type Scalar<S> = <<S as Ciphersuite>::Group as Group>::Scalar;
+
+/// Output of the hash function of the ciphersuite `S`, which is also the size
+/// of its keys and MACs.
+pub type Output<S> = sha2::digest::Output<<S as Ciphersuite>::Hash>;
+
+/// A fixed point of the protocols, which suites keep precomputed tables for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    /// The generator of the group.
+    G,
+    /// The M point of SPAKE2 and SPAKE2+.
+    M,
+    /// The N point of SPAKE2 and SPAKE2+.
+    N,
+}
+
+pub trait Ciphersuite: Send + Sync + 'static {
+    /// Name of the suite, as advertised by the server.
+    const NAME: &'static str;
+
+    /// A prime-order group. Its scalars must be zeroizable so secrets can be
+    /// wiped after use.
+    type Group: Group<Scalar: Zeroize>;
+
+    type Hash: Digest + BlockSizeUser + Clone;
+
+    /// The M point of SPAKE2+, used to blind the client's share.
+    fn m() -> Self::Group;
+
+    /// The N point of SPAKE2+, used to blind the server's share.
+    fn n() -> Self::Group;
+
+    fn base(base: Base) -> Self::Group {
+        match base {
+            Base::G => Self::Group::generator(),
+            Base::M => Self::m(),
+            Base::N => Self::n(),
+        }
+    }
+
+    /// Multiplies the fixed point `base` by `scalar` in constant time. Suites
+    /// override this with a precomputed table, built once.
+    fn mul_base(base: Base, scalar: &Scalar<Self>) -> Self::Group {
+        Self::base(base) * scalar
+    }
+
+    /// Computes x·p + y·q in constant time. Suites override this with a
+    /// multiscalar multiplication, which shares the doublings.
+    fn lincomb(
+        p: &Self::Group,
+        x: &Scalar<Self>,
+        q: &Self::Group,
+        y: &Scalar<Self>,
+    ) -> Self::Group {
+        *p * x + *q * y
+    }
+
+    /// Computes x·G + y·`base`, the blinded share of SPAKE2 and SPAKE2+.
+    fn blind(x: &Scalar<Self>, base: Base, y: &Scalar<Self>) -> Self::Group {
+        Self::mul_base(Base::G, x) + Self::mul_base(base, y)
+    }
+
+    /// Encodes a group element in the suite's canonical form.
+    fn encode_element(element: &Self::Group) -> Vec<u8>;
+
+    /// Decodes a group element, rejecting non-canonical encodings.
+    fn decode_element(bytes: &[u8]) -> Option<Self::Group>;
+
+    /// Reduces a byte string, interpreted as an integer in the suite's byte
+    /// order, modulo the group order.
+    fn reduce_scalar(bytes: &[u8]) -> Scalar<Self>;
+
+    fn encode_scalar(scalar: &Scalar<Self>) -> Vec<u8> {
+        scalar.to_repr().as_ref().to_vec()
+    }
+
+    /// Decodes a scalar, rejecting encodings that are not reduced.
+    fn decode_scalar(bytes: &[u8]) -> Option<Scalar<Self>> {
+        let mut repr = <Scalar<Self> as PrimeField>::Repr::default();
+        if bytes.len() != repr.as_ref().len() {
+            return None;
+        }
+        repr.as_mut().copy_from_slice(bytes);
+        Option::from(Scalar::<Self>::from_repr(repr))
+    }
+
+    fn hash(data: &[u8]) -> Output<Self> {
+        Self::Hash::digest(data)
+    }
+
+    /// Fills `output` with key material derived from `ikm`.
+    fn kdf(salt: Option<&[u8]>, ikm: &[u8], info: &[u8], output: &mut [u8]) {
+        SimpleHkdf::<Self::Hash>::new(salt, ikm)
+            .expand(info, output)
+            .expect("KDF output length is a small multiple of the hash length");
+    }
+
+    fn mac(key: &[u8], message: &[u8]) -> Output<Self> {
+        let mut mac =
+            SimpleHmac::<Self::Hash>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(message);
+        mac.finalize().into_bytes()
+    }
+}
+
+/// Reduces an integer given as bytes from most to least significant modulo the
+/// order of the scalar field `F`.
+fn reduce_be_bytes<'a, F: PrimeField>(bytes: impl Iterator<Item = &'a u8>) -> F {
+    let radix = F::from(256);
+    bytes.fold(F::ZERO, |acc, byte| acc * radix + F::from(u64::from(*byte)))
+}
+
+/// Draws a uniformly random nonzero scalar from `rng`, resampling on zero.
+pub fn random_nonzero_scalar<F: Field>(rng: &mut impl CryptoRngCore) -> F {
+    loop {
+        let scalar = F::random(&mut *rng);
+        if !bool::from(scalar.is_zero()) {
+            return scalar;
+        }
+    }
+}
+
+/// Decodes a MAC or key of the ciphersuite `S`, rejecting other lengths.
+pub fn decode_output<S: Ciphersuite>(bytes: &[u8]) -> Option<Output<S>> {
+    (bytes.len() == <S::Hash as Digest>::output_size())
+        .then(|| Output::<S>::clone_from_slice(bytes))
+}
+
+/// Compares MACs, confirmations and other values derived from secrets in
+/// time that depends only on their lengths, which are public.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Instantiates generic test functions `fn name<S: Ciphersuite>()` as tests
+/// for every ciphersuite.
+#[cfg(test)]
+macro_rules! suite_tests {
+    ($($test:ident),* $(,)?) => {
+        mod ristretto255 {
+            $(
+                #[test]
+                fn $test() {
+                    super::$test::<$crate::ciphersuite::Ristretto255>();
+                }
+            )*
+        }
+
+        mod p256 {
+            $(
+                #[test]
+                fn $test() {
+                    super::$test::<$crate::ciphersuite::P256>();
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use suite_tests;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use group::ff::Field;
+    use rand::rngs::OsRng;
+
+    fn encoding_round_trip<S: Ciphersuite>() {
+        let s = Scalar::<S>::random(&mut OsRng);
+        let p = S::Group::generator() * s;
+        assert_eq!(S::decode_element(&S::encode_element(&p)), Some(p));
+        let identity = S::Group::identity();
+        assert_eq!(
+            S::decode_element(&S::encode_element(&identity)),
+            Some(identity)
+        );
+        assert_eq!(S::decode_scalar(&S::encode_scalar(&s)), Some(s));
+        assert_eq!(S::decode_element(&S::encode_element(&p)[1..]), None);
+        assert_eq!(S::decode_scalar(&[0xff; 32]), None);
+    }
+
+    fn reduce_scalar_is_positional<S: Ciphersuite>() {
+        assert_eq!(S::reduce_scalar(&[]), Scalar::<S>::ZERO);
+        assert_eq!(S::reduce_scalar(&[0; 40]), Scalar::<S>::ZERO);
+        let mut one = [0u8; 40];
+        // Little-endian suites put the low byte first, big-endian last
+        if S::encode_scalar(&Scalar::<S>::ONE)[0] == 1 {
+            one[0] = 1;
+        } else {
+            one[39] = 1;
+        }
+        assert_eq!(S::reduce_scalar(&one), Scalar::<S>::ONE);
+    }
+
+    /// Yields `zeros` zero bytes, then ones.
+    struct ZerosFirst {
+        zeros: usize,
+    }
+
+    impl rand_core::RngCore for ZerosFirst {
+        fn next_u32(&mut self) -> u32 {
+            rand_core::impls::next_u32_via_fill(self)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            rand_core::impls::next_u64_via_fill(self)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for byte in dest {
+                *byte = if self.zeros > 0 { 0 } else { 1 };
+                self.zeros = self.zeros.saturating_sub(1);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl rand_core::CryptoRng for ZerosFirst {}
+
+    fn random_nonzero_scalar_resamples_zero<S: Ciphersuite>() {
+        // Enough zeros for a zero scalar from either wide or narrow sampling
+        let mut rng = ZerosFirst { zeros: 128 };
+        let scalar = random_nonzero_scalar::<Scalar<S>>(&mut rng);
+        assert!(!bool::from(scalar.is_zero()));
+        assert_eq!(rng.zeros, 0);
+    }
+
+    fn precomputed_arithmetic_matches_multiplication<S: Ciphersuite>() {
+        let (x, y) = (
+            Scalar::<S>::random(&mut OsRng),
+            Scalar::<S>::random(&mut OsRng),
+        );
+        for base in [Base::G, Base::M, Base::N] {
+            let point = S::base(base);
+            for scalar in [Scalar::<S>::ZERO, Scalar::<S>::ONE, -Scalar::<S>::ONE, x] {
+                assert_eq!(S::mul_base(base, &scalar), point * scalar);
+            }
+            assert_eq!(
+                S::blind(&x, base, &y),
+                S::Group::generator() * x + point * y
+            );
+        }
+        let p = S::Group::random(&mut OsRng);
+        assert_eq!(S::lincomb(&p, &x, &S::m(), &y), p * x + S::m() * y);
+        assert_eq!(S::lincomb(&p, &Scalar::<S>::ZERO, &p, &y), p * y);
+    }
+
+    suite_tests!(
+        encoding_round_trip,
+        precomputed_arithmetic_matches_multiplication,
+        reduce_scalar_is_positional,
+        random_nonzero_scalar_resamples_zero,
+    );
+
+    #[test]
+    fn decode_output_checks_length() {
+        assert!(decode_output::<P256>(&[0; 32]).is_some());
+        assert!(decode_output::<P256>(&[0; 31]).is_none());
+        assert!(decode_output::<Ristretto255>(&[0; 64]).is_some());
+        assert!(decode_output::<Ristretto255>(&[0; 32]).is_none());
+    }
+
+    #[test]
+    fn constant_time_eq_compares_every_byte() {
+        let mac = [0x5a; 32];
+        assert!(constant_time_eq(&mac, &mac.clone()));
+        assert!(constant_time_eq(&[], &[]));
+        for i in 0..mac.len() {
+            let mut other = mac;
+            other[i] ^= 0x80;
+            assert!(!constant_time_eq(&mac, &other), "byte {}", i);
+        }
+        assert!(!constant_time_eq(&mac, &mac[..31]));
+        assert!(!constant_time_eq(&mac[..31], &mac));
+    }
+}
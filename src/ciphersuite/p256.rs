@@ -0,0 +1,104 @@
+use std::sync::LazyLock;
+
+use group::{Group, ff::PrimeField};
+use p256::{
+    AffinePoint, EncodedPoint, ProjectivePoint,
+    elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint},
+};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::{
+    Base, Ciphersuite, Scalar,
+    precomputed::{self, BaseTable},
+    reduce_be_bytes,
+};
+
+/// The RFC 9383 P256-SHA256-HKDF-SHA256-HMAC-SHA256 ciphersuite. Scalars are
+/// encoded as 32 big-endian bytes and elements as uncompressed SEC1 points.
+pub struct P256;
+
+/// M and N from RFC 9383, as compressed SEC1 points.
+const M: &str = "02886e2f97ace46e55ba9dd7242579f2993b64e16ef3dcab95afd497333d8fa12f";
+const N: &str = "03d8bbd6c639c62937b04d997f38c3770719c629d7014d49a24b4f98baa1292b49";
+
+/// Byte length of a scalar.
+const SCALAR_LEN: usize = 32;
+
+static M_POINT: LazyLock<ProjectivePoint> =
+    LazyLock::new(|| decode_sec1(&hex::decode(M).unwrap()).expect("M is a valid point"));
+static N_POINT: LazyLock<ProjectivePoint> =
+    LazyLock::new(|| decode_sec1(&hex::decode(N).unwrap()).expect("N is a valid point"));
+static G_TABLE: LazyLock<BaseTable<ProjectivePoint>> =
+    LazyLock::new(|| BaseTable::new(ProjectivePoint::generator(), SCALAR_LEN));
+static M_TABLE: LazyLock<BaseTable<ProjectivePoint>> =
+    LazyLock::new(|| BaseTable::new(*M_POINT, SCALAR_LEN));
+static N_TABLE: LazyLock<BaseTable<ProjectivePoint>> =
+    LazyLock::new(|| BaseTable::new(*N_POINT, SCALAR_LEN));
+
+/// The little-endian encoding of a scalar, for the precomputed arithmetic.
+fn le_bytes(scalar: &Scalar<P256>) -> Zeroizing<[u8; SCALAR_LEN]> {
+    let mut bytes: Zeroizing<[u8; SCALAR_LEN]> = Zeroizing::new(scalar.to_repr().into());
+    bytes.reverse();
+    bytes
+}
+
+fn decode_sec1(bytes: &[u8]) -> Option<ProjectivePoint> {
+    let encoded = EncodedPoint::from_bytes(bytes).ok()?;
+    Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&encoded)).map(Into::into)
+}
+
+impl Ciphersuite for P256 {
+    const NAME: &'static str = "P256-SHA256-HKDF-SHA256-HMAC-SHA256";
+
+    type Group = ProjectivePoint;
+    type Hash = Sha256;
+
+    fn m() -> ProjectivePoint {
+        *M_POINT
+    }
+
+    fn n() -> ProjectivePoint {
+        *N_POINT
+    }
+
+    fn mul_base(base: Base, scalar: &Scalar<Self>) -> ProjectivePoint {
+        let table = match base {
+            Base::G => &G_TABLE,
+            Base::M => &M_TABLE,
+            Base::N => &N_TABLE,
+        };
+        table.mul(&*le_bytes(scalar))
+    }
+
+    fn lincomb(
+        p: &ProjectivePoint,
+        x: &Scalar<Self>,
+        q: &ProjectivePoint,
+        y: &Scalar<Self>,
+    ) -> ProjectivePoint {
+        precomputed::lincomb(*p, &*le_bytes(x), *q, &*le_bytes(y))
+    }
+
+    fn encode_element(element: &ProjectivePoint) -> Vec<u8> {
+        element
+            .to_affine()
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec()
+    }
+
+    fn decode_element(bytes: &[u8]) -> Option<ProjectivePoint> {
+        // Only the uncompressed form produced by `encode_element` is canonical,
+        // and the single zero byte SEC1 uses for the identity
+        match bytes {
+            [0x00] => Some(ProjectivePoint::IDENTITY),
+            [0x04, ..] => decode_sec1(bytes),
+            _ => None,
+        }
+    }
+
+    fn reduce_scalar(bytes: &[u8]) -> Scalar<Self> {
+        reduce_be_bytes(bytes.iter())
+    }
+}
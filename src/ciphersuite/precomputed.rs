@@ -0,0 +1,132 @@
+//! Constant-time fixed-base tables and two-point linear combinations, for
+//! groups whose crates do not provide them.
+//!
+//! Scalars are processed in 4-bit windows of their little-endian encoding.
+//! Table entries are selected by scanning the whole table with conditional
+//! assignments, so neither the memory accesses nor the group operations
+//! depend on the scalar.
+
+use group::Group;
+use subtle::{ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroizing;
+
+/// The multiples 0·P to 15·P of a point P.
+type Multiples<G> = [G; 16];
+
+/// Splits a little-endian encoding into 4-bit windows, least significant
+/// first.
+fn windows(le_bytes: &[u8]) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(
+        le_bytes
+            .iter()
+            .flat_map(|byte| [byte & 0xf, byte >> 4])
+            .collect(),
+    )
+}
+
+fn multiples<G: Group>(point: G) -> Multiples<G> {
+    let mut multiples = [G::identity(); 16];
+    for j in 1..16 {
+        multiples[j] = multiples[j - 1] + point;
+    }
+    multiples
+}
+
+/// Returns `multiples[index]` in constant time.
+fn select<G: Group + ConditionallySelectable>(multiples: &Multiples<G>, index: u8) -> G {
+    let mut point = G::identity();
+    for (j, multiple) in multiples.iter().enumerate() {
+        point.conditional_assign(multiple, index.ct_eq(&(j as u8)));
+    }
+    point
+}
+
+/// The multiples j·16^i·P of a fixed point P for every window i of a scalar,
+/// so that a multiplication by P takes one addition per window and no
+/// doublings.
+pub(crate) struct BaseTable<G> {
+    windows: Vec<Multiples<G>>,
+}
+
+impl<G: Group + ConditionallySelectable> BaseTable<G> {
+    /// Builds the table of `base` for scalars of `scalar_len` bytes.
+    pub(crate) fn new(base: G, scalar_len: usize) -> Self {
+        let mut windows = Vec::with_capacity(2 * scalar_len);
+        let mut base = base;
+        for _ in 0..2 * scalar_len {
+            let window = multiples(base);
+            base = window[15] + base;
+            windows.push(window);
+        }
+        Self { windows }
+    }
+
+    /// Multiplies the base by the scalar with little-endian encoding
+    /// `le_bytes`.
+    pub(crate) fn mul(&self, le_bytes: &[u8]) -> G {
+        windows(le_bytes)
+            .iter()
+            .zip(&self.windows)
+            .fold(G::identity(), |sum, (&index, window)| {
+                sum + select(window, index)
+            })
+    }
+}
+
+/// Computes x·p + y·q with Straus's method, sharing the doublings between
+/// both scalars, given as little-endian encodings of the same length.
+pub(crate) fn lincomb<G: Group + ConditionallySelectable>(
+    p: G,
+    x_le_bytes: &[u8],
+    q: G,
+    y_le_bytes: &[u8],
+) -> G {
+    let (p, q) = (multiples(p), multiples(q));
+    let (x, y) = (windows(x_le_bytes), windows(y_le_bytes));
+    x.iter()
+        .zip(y.iter())
+        .rev()
+        .fold(G::identity(), |sum, (&i, &j)| {
+            sum.double().double().double().double() + select(&p, i) + select(&q, j)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use group::ff::{Field, PrimeField};
+    use p256::{ProjectivePoint, Scalar};
+    use rand::rngs::OsRng;
+
+    fn le_bytes(scalar: &Scalar) -> Vec<u8> {
+        scalar.to_repr().iter().rev().copied().collect()
+    }
+
+    #[test]
+    fn table_matches_multiplication() {
+        let base = ProjectivePoint::random(&mut OsRng);
+        let table = BaseTable::new(base, 32);
+        for scalar in [
+            Scalar::ZERO,
+            Scalar::ONE,
+            -Scalar::ONE,
+            Scalar::random(&mut OsRng),
+        ] {
+            assert_eq!(table.mul(&le_bytes(&scalar)), base * scalar);
+        }
+    }
+
+    #[test]
+    fn lincomb_matches_multiplications() {
+        let (p, q) = (
+            ProjectivePoint::random(&mut OsRng),
+            ProjectivePoint::random(&mut OsRng),
+        );
+        let (x, y) = (Scalar::random(&mut OsRng), -Scalar::ONE);
+        assert_eq!(lincomb(p, &le_bytes(&x), q, &le_bytes(&y)), p * x + q * y);
+        assert_eq!(
+            lincomb(p, &le_bytes(&Scalar::ZERO), q, &le_bytes(&x)),
+            q * x
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::LazyLock;
+
+use curve25519_dalek::{
+    RistrettoPoint,
+    constants::RISTRETTO_BASEPOINT_TABLE,
+    ristretto::{CompressedRistretto, RistrettoBasepointTable},
+    traits::MultiscalarMul,
+};
+use elliptic_curve::hash2curve::{ExpandMsg, ExpandMsgXmd, Expander};
+use sha2::Sha512;
+
+use super::{Base, Ciphersuite, Scalar, reduce_be_bytes};
+
+/// Ristretto255 with SHA-512, HKDF-SHA512 and HMAC-SHA512, the hash of
+/// ristretto255 throughout RFC 9496 and of the hash-to-group below. Scalars and
+/// elements are encoded as 32 little-endian bytes.
+///
+/// The RFC does not define M and N for ristretto255, so they are derived as
+/// nothing-up-my-sleeve points: M = hash_to_group("M", DST) and
+/// N = hash_to_group("N", DST), where hash_to_group is the ristretto255
+/// hash-to-group of RFC 9496 (expand_message_xmd with SHA-512 from RFC 9380
+/// to 64 bytes, then the one-way map) and DST is
+/// `rusty-pake-SPAKE2+-V01-CS01-with-ristretto255_XMD:SHA-512_R255MAP_RO_`.
+pub struct Ristretto255;
+
+/// Domain separation tag for deriving M and N. The version is bumped if the
+/// points ever need to change.
+const NUMS_DST: &[u8] = b"rusty-pake-SPAKE2+-V01-CS01-with-ristretto255_XMD:SHA-512_R255MAP_RO_";
+
+static M: LazyLock<RistrettoPoint> = LazyLock::new(|| hash_to_group(b"M", NUMS_DST));
+static N: LazyLock<RistrettoPoint> = LazyLock::new(|| hash_to_group(b"N", NUMS_DST));
+static M_TABLE: LazyLock<RistrettoBasepointTable> =
+    LazyLock::new(|| RistrettoBasepointTable::create(&M));
+static N_TABLE: LazyLock<RistrettoBasepointTable> =
+    LazyLock::new(|| RistrettoBasepointTable::create(&N));
+
+/// The ristretto255 hash_to_group of RFC 9496.
+pub(crate) fn hash_to_group(msg: &[u8], dst: &[u8]) -> RistrettoPoint {
+    let mut uniform_bytes = [0u8; 64];
+    ExpandMsgXmd::<Sha512>::expand_message(&[msg], &[dst], uniform_bytes.len())
+        .expect("64 bytes is a valid expand_message_xmd output length")
+        .fill_bytes(&mut uniform_bytes);
+    RistrettoPoint::from_uniform_bytes(&uniform_bytes)
+}
+
+impl Ciphersuite for Ristretto255 {
+    const NAME: &'static str = "ristretto255-SHA512-HKDF-SHA512-HMAC-SHA512";
+
+    type Group = RistrettoPoint;
+    type Hash = Sha512;
+
+    fn m() -> RistrettoPoint {
+        *M
+    }
+
+    fn n() -> RistrettoPoint {
+        *N
+    }
+
+    fn mul_base(base: Base, scalar: &Scalar<Self>) -> RistrettoPoint {
+        let table = match base {
+            Base::G => RISTRETTO_BASEPOINT_TABLE,
+            Base::M => &*M_TABLE,
+            Base::N => &*N_TABLE,
+        };
+        table * scalar
+    }
+
+    fn lincomb(
+        p: &RistrettoPoint,
+        x: &Scalar<Self>,
+        q: &RistrettoPoint,
+        y: &Scalar<Self>,
+    ) -> RistrettoPoint {
+        RistrettoPoint::multiscalar_mul([x, y], [p, q])
+    }
+
+    fn encode_element(element: &RistrettoPoint) -> Vec<u8> {
+        element.compress().to_bytes().to_vec()
+    }
+
+    fn decode_element(bytes: &[u8]) -> Option<RistrettoPoint> {
+        CompressedRistretto::from_slice(bytes).ok()?.decompress()
+    }
+
+    fn reduce_scalar(bytes: &[u8]) -> Scalar<Self> {
+        reduce_be_bytes(bytes.iter().rev())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pinned so that other implementations can check their derivation
+    #[test]
+    fn m_n_encodings() {
+        let encode = |p: RistrettoPoint| hex::encode(Ristretto255::encode_element(&p));
+        assert_eq!(
+            encode(Ristretto255::m()),
+            "72d6c556b443e6bcfed1e8142fcfa2fd59bb03c2d781e1ee5795c0d4991a040d"
+        );
+        assert_eq!(
+            encode(Ristretto255::n()),
+            "cebb3b7a726908a7f49cec567cbb14b96cb73f35d278ac274af9d1fee519d856"
+        );
+    }
+
+    #[test]
+    fn hash_to_group_separates_domains() {
+        assert_eq!(hash_to_group(b"M", NUMS_DST), Ristretto255::m());
+        assert_ne!(hash_to_group(b"M", b"other-DST"), Ristretto255::m());
+    }
+}
@@ -1,4 +1,30 @@
-use crate::{shared, spake2plus};
+use rand_core::CryptoRngCore;
+use zeroize::Zeroizing;
+
+use crate::{
+    ciphersuite::Ciphersuite,
+    cpace::{self, CpaceHandshake, CpaceMessage},
+    opaque::{ClientLogin, ClientRegistration, LoginKeys, LoginResponse, RegistrationResponse},
+    shared,
+    spake2plus::{
+        self,
+        handshake::{ClientHandshake, ServerMessage},
+        kdf::PasswordKdf,
+        rfc9383::{self, SessionKeys},
+        secret::Password,
+    },
+    srp::{self, SrpClient, SrpGroup},
+};
+
+/// Result of a successful exchange.
+pub struct Exchange<S: Ciphersuite> {
+    /// The session keys, including the shared key and the keying material
+    /// exporter.
+    pub keys: SessionKeys<S>,
+    /// The client's key confirmation, hex encoded, sent to the server with
+    /// `perform_verify`.
+    pub confirmation: String,
+}
 
 pub async fn get_server_id(server_ip: &str) -> Result<String, anyhow::Error> {
     let client = reqwest::Client::new();
@@ -7,20 +33,30 @@ pub async fn get_server_id(server_ip: &str) -> Result<String, anyhow::Error> {
     Ok(id)
 }
 
-pub async fn perform_setup(
+pub async fn get_server_suite(server_ip: &str) -> Result<String, anyhow::Error> {
+    let client = reqwest::Client::new();
+    let response = client.get(format!("{}/suite", server_ip)).send().await?;
+    let suite = response.text().await?;
+    Ok(suite)
+}
+
+pub async fn perform_setup<S: Ciphersuite>(
+    rng: &mut impl CryptoRngCore,
     server_ip: &str,
     server_id: &str,
     client_id: &str,
-    password: &str,
+    password: &Password,
 ) -> Result<(), anyhow::Error> {
     println!("Starting PAKE setup process...");
 
     // Perform client setup
-    let (phi0, phi1) = spake2plus::client_secret(password, client_id, server_id);
-    let c = spake2plus::client_cipher(phi1);
+    let salt = rfc9383::generate_salt(rng);
+    let kdf = PasswordKdf::default();
+    let (phi0, phi1) = rfc9383::client_secret::<S>(password, client_id, server_id, &salt, &kdf)?;
+    let c = spake2plus::client_cipher::<S>(&phi1);
 
     // Create request
-    let request = shared::SetupRequest::new(client_id.to_string(), phi0, c);
+    let request = shared::SetupRequest::<S>::new(client_id.to_string(), phi0, c, salt, kdf);
 
     // Serialize to JSON
     let json = serde_json::to_string(&request.encode())?;
@@ -38,10 +74,8 @@ pub async fn perform_setup(
     // Handle response
     if res.status().is_success() {
         println!(
-            "Setup completed\nphi0={}\nphi1={}\nc={}\n",
-            hex::encode(phi0.as_bytes()),
-            hex::encode(phi1.as_bytes()),
-            hex::encode(c.compress().as_bytes()),
+            "Setup completed\nc={}\n",
+            hex::encode(S::encode_element(&c))
         );
         Ok(())
     } else {
@@ -49,20 +83,28 @@ pub async fn perform_setup(
     }
 }
 
-pub async fn perform_exchange(
+/// Performs an exchange with the server. `context` must match the context the
+/// server was started with, e.g. [`shared::CONTEXT`].
+pub async fn perform_exchange<S: Ciphersuite>(
+    rng: &mut impl CryptoRngCore,
     server_ip: &str,
     server_id: &str,
     idc: &str,
-    password: &str,
-) -> Result<String, anyhow::Error> {
+    password: &Password,
+    context: &[u8],
+) -> Result<Exchange<S>, anyhow::Error> {
+    let client = reqwest::Client::new();
+    let prepared = prepare(&client, server_ip, idc).await?;
+
     // client secrets & initial message
-    let (phi0, phi1) = spake2plus::client_secret(password, idc, server_id);
-    let (u, alpha) = spake2plus::client_initial(phi0);
+    let (phi0, phi1) =
+        rfc9383::client_secret::<S>(password, idc, server_id, &prepared.salt, &prepared.kdf)?;
+    let (handshake, message) = ClientHandshake::start(rng, context, idc, server_id, phi0, phi1);
+    let u = message.u;
 
     // POST /exchange with hex(u)
-    let request = shared::ExchangeRequest::new(idc.to_string(), u);
+    let request = shared::ExchangeRequest::<S>::new(idc.to_string(), u);
 
-    let client = reqwest::Client::new();
     let response = client
         .post(format!("{}/exchange", server_ip))
         .json(&request.encode())
@@ -73,23 +115,381 @@ pub async fn perform_exchange(
         anyhow::bail!("server returned {}", response.status());
     }
 
-    // parse response and compute k on client
+    // parse response and compute keys on client
     let response: shared::ExchangeResponseEncoded = response.json().await?;
+    let response = response.decode::<S>()?;
+
+    let keys = handshake
+        .finish(ServerMessage {
+            v: response.v,
+            confirm_v: response.confirm_v,
+        })
+        .map_err(|error| anyhow::anyhow!("handshake with server failed: {}", error))?;
+
+    let exchange = Exchange {
+        confirmation: hex::encode(&keys.confirm_p),
+        keys,
+    };
+    println!(
+        "Exchange completed\nu={}\nconfirmation={}\n",
+        hex::encode(S::encode_element(&u)),
+        exchange.confirmation,
+    );
+    Ok(exchange)
+}
+
+/// Result of a successful CPace exchange.
+pub struct CpaceExchange {
+    /// The intermediate session key ISK.
+    pub isk: Zeroizing<Vec<u8>>,
+    /// The client's key confirmation, hex encoded, sent to the server with
+    /// `perform_cpace_verify`.
+    pub confirmation: String,
+}
+
+/// Registers a CPace password-related string with the server, as clients
+/// that use CPace do instead of `perform_setup`.
+pub async fn perform_cpace_setup(
+    rng: &mut impl CryptoRngCore,
+    server_ip: &str,
+    server_id: &str,
+    idc: &str,
+    password: &Password,
+) -> Result<(), anyhow::Error> {
+    let salt = rfc9383::generate_salt(rng);
+    let kdf = PasswordKdf::default();
+    let prs = cpace::password_related_string(password, idc, server_id, &salt, &kdf)?;
+    let request = shared::CpaceSetupRequest::new(idc.to_string(), prs, salt, kdf);
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/cpace/setup", server_ip))
+        .json(&request.encode())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+    println!("CPace setup completed\n");
+    Ok(())
+}
+
+/// Performs a CPace exchange with the server, for a client registered with
+/// `perform_cpace_setup`.
+pub async fn perform_cpace_exchange(
+    rng: &mut impl CryptoRngCore,
+    server_ip: &str,
+    server_id: &str,
+    idc: &str,
+    password: &Password,
+    context: &[u8],
+) -> Result<CpaceExchange, anyhow::Error> {
+    let client = reqwest::Client::new();
+    let prepared = prepare(&client, server_ip, idc).await?;
+
+    let prs =
+        cpace::password_related_string(password, idc, server_id, &prepared.salt, &prepared.kdf)?;
+    let ci = cpace::channel_identifier(context, idc, server_id);
+    let mut sid = [0u8; cpace::SID_LEN];
+    rng.fill_bytes(&mut sid);
+    let (handshake, message) =
+        CpaceHandshake::start(rng, cpace::Role::Initiator, &prs, &ci, &sid, b"");
+
+    let request = shared::CpaceExchangeRequest::new(idc.to_string(), sid, message.y, message.ad);
+    let response = client
+        .post(format!("{}/cpace/exchange", server_ip))
+        .json(&request.encode())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+
+    let response: shared::CpaceExchangeResponseEncoded = response.json().await?;
     let response = response.decode()?;
 
-    let k_c = spake2plus::client_compute_key(idc, server_id, phi0, phi1, alpha, u, response.v);
-    let key = hex::encode(k_c);
+    let server_message = CpaceMessage {
+        y: response.y,
+        ad: response.ad,
+    };
+    let (isk, confirmation) = handshake
+        .finish(server_message)
+        .and_then(|(handshake, confirmation)| {
+            let isk = handshake.finish(&response.confirmation)?;
+            Ok((isk, confirmation))
+        })
+        .map_err(|error| anyhow::anyhow!("CPace handshake with server failed: {}", error))?;
+
+    let exchange = CpaceExchange {
+        isk,
+        confirmation: hex::encode(confirmation),
+    };
     println!(
-        "Exchange completed\nalpha={}\nu={}\nkey={}\n",
-        hex::encode(alpha.as_bytes()),
-        hex::encode(u.compress().as_bytes()),
-        key
+        "CPace exchange completed\nconfirmation={}\n",
+        exchange.confirmation
     );
+    Ok(exchange)
+}
+
+pub async fn perform_cpace_verify(
+    server_ip: &str,
+    idc: &str,
+    confirmation: &str,
+) -> Result<bool, anyhow::Error> {
+    let request = shared::CpaceVerifyRequestEncoded::new(idc.to_string(), confirmation.to_string());
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/cpace/verify", server_ip))
+        .json(&request)
+        .send()
+        .await?;
+
+    Ok(response.status().is_success())
+}
+
+/// Registers an SRP-6a verifier in `group` with the server, as legacy
+/// clients do instead of `perform_setup`.
+pub async fn perform_srp_setup(
+    rng: &mut impl CryptoRngCore,
+    server_ip: &str,
+    idc: &str,
+    password: &Password,
+    group: SrpGroup,
+) -> Result<(), anyhow::Error> {
+    let salt = srp::generate_salt(rng);
+    let verifier = srp::compute_verifier(group, idc, password, &salt);
+    let request = shared::SrpSetupRequest::new(idc.to_string(), group, salt.to_vec(), verifier);
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/srp/setup", server_ip))
+        .json(&request.encode())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+    println!("SRP setup completed\n");
+    Ok(())
+}
+
+/// Performs an SRP-6a exchange with the server, checking the server's proof.
+/// Returns the session key K.
+pub async fn perform_srp_exchange(
+    rng: &mut impl CryptoRngCore,
+    server_ip: &str,
+    idc: &str,
+    password: &Password,
+) -> Result<Zeroizing<Vec<u8>>, anyhow::Error> {
+    let client = reqwest::Client::new();
+    let request = shared::SrpExchangeRequestEncoded::new(idc.to_string());
+    let response = client
+        .post(format!("{}/srp/exchange", server_ip))
+        .json(&request)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+
+    let response: shared::SrpExchangeResponseEncoded = response.json().await?;
+    let response = response.decode()?;
+    let (handshake, message) = SrpClient::respond(
+        rng,
+        response.group,
+        idc,
+        password,
+        &response.salt,
+        &response.b,
+    )
+    .map_err(|error| anyhow::anyhow!("SRP handshake with server failed: {}", error))?;
+
+    let request = shared::SrpVerifyRequest::new(idc.to_string(), message.a, message.m1);
+    let response = client
+        .post(format!("{}/srp/verify", server_ip))
+        .json(&request.encode())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+
+    let response: shared::SrpVerifyResponseEncoded = response.json().await?;
+    let key = handshake
+        .finish(&response.decode()?.m2)
+        .map_err(|error| anyhow::anyhow!("SRP handshake with server failed: {}", error))?;
+    println!("SRP exchange completed\n");
     Ok(key)
 }
 
-pub async fn perform_verify(server_ip: &str, idc: &str, key: &str) -> Result<bool, anyhow::Error> {
-    let request = shared::VerifyRequestEncoded::new(idc.to_string(), key.to_string());
+/// Registers with the server through OPAQUE, as an alternative to
+/// `perform_setup`. The password is stretched with the default KDF, which the
+/// server stores with the record and hands back to `perform_opaque_login`.
+/// Returns the export key, which the server never learns.
+pub async fn perform_opaque_registration(
+    rng: &mut impl CryptoRngCore,
+    server_ip: &str,
+    server_id: &str,
+    idc: &str,
+    password: &Password,
+) -> Result<Zeroizing<Vec<u8>>, anyhow::Error> {
+    let (registration, request) = ClientRegistration::start(rng, password);
+
+    let client = reqwest::Client::new();
+    let request = shared::OpaqueRegisterStartRequest::new(idc.to_string(), request.blinded);
+    let response = client
+        .post(format!("{}/opaque/register/start", server_ip))
+        .json(&request.encode())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+
+    let response: shared::OpaqueRegisterStartResponseEncoded = response.json().await?;
+    let response = response.decode()?;
+    let kdf = PasswordKdf::default();
+    let (record, export_key) = registration.finish(
+        rng,
+        RegistrationResponse {
+            evaluated: response.evaluated,
+            server_public_key: response.server_public_key,
+        },
+        &kdf,
+        idc,
+        server_id,
+    )?;
+
+    let request = shared::OpaqueRegisterFinishRequest::new(
+        idc.to_string(),
+        record.client_public_key,
+        record.masking_key,
+        record.envelope,
+        kdf,
+    );
+    let response = client
+        .post(format!("{}/opaque/register/finish", server_ip))
+        .json(&request.encode())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+    println!("OPAQUE registration completed\n");
+    Ok(export_key)
+}
+
+/// Logs in to the server through OPAQUE, authenticating both sides. Fails if
+/// the password is wrong, the client is not registered, the server asks for
+/// a KDF above [`PasswordKdf::within_maximum`], or the server rejects the
+/// client's MAC.
+pub async fn perform_opaque_login(
+    rng: &mut impl CryptoRngCore,
+    server_ip: &str,
+    server_id: &str,
+    idc: &str,
+    password: &Password,
+    context: &[u8],
+) -> Result<LoginKeys, anyhow::Error> {
+    let (login, request) = ClientLogin::start(rng, password);
+
+    let client = reqwest::Client::new();
+    let request = shared::OpaqueLoginStartRequest::new(
+        idc.to_string(),
+        request.blinded,
+        request.client_nonce,
+        request.client_keyshare,
+    );
+    let response = client
+        .post(format!("{}/opaque/login/start", server_ip))
+        .json(&request.encode())
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+
+    let response: shared::OpaqueLoginStartResponseEncoded = response.json().await?;
+    let response = response.decode()?;
+    if !response.kdf.within_maximum() {
+        anyhow::bail!(
+            "server asked for a password KDF above the maximum cost: {}",
+            response.kdf
+        );
+    }
+    let server_message = LoginResponse {
+        evaluated: response.evaluated,
+        masking_nonce: response.masking_nonce,
+        masked_response: response.masked_response,
+        server_nonce: response.server_nonce,
+        server_keyshare: response.server_keyshare,
+        server_mac: response.server_mac,
+    };
+    let (keys, finalization) = login
+        .finish(server_message, &response.kdf, context, idc, server_id)
+        .map_err(|error| anyhow::anyhow!("OPAQUE login with server failed: {}", error))?;
+
+    let request = shared::OpaqueLoginFinishRequestEncoded::new(
+        idc.to_string(),
+        hex::encode(finalization.client_mac),
+    );
+    let response = client
+        .post(format!("{}/opaque/login/finish", server_ip))
+        .json(&request)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+    println!("OPAQUE login completed\n");
+    Ok(keys)
+}
+
+/// POST /prepare to get the salt and KDF parameters stored at setup, unless
+/// they cost more than [`PasswordKdf::within_maximum`] allows.
+async fn prepare(
+    client: &reqwest::Client,
+    server_ip: &str,
+    idc: &str,
+) -> Result<shared::PrepareResponse, anyhow::Error> {
+    let request = shared::PrepareRequestEncoded::new(idc.to_string());
+    let response = client
+        .post(format!("{}/prepare", server_ip))
+        .json(&request)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("server returned {}", response.status());
+    }
+
+    let response: shared::PrepareResponseEncoded = response.json().await?;
+    let response = response.decode()?;
+    if !response.kdf.within_maximum() {
+        anyhow::bail!(
+            "server asked for a password KDF above the maximum cost: {}",
+            response.kdf
+        );
+    }
+    Ok(response)
+}
+
+pub async fn perform_verify(
+    server_ip: &str,
+    idc: &str,
+    confirmation: &str,
+) -> Result<bool, anyhow::Error> {
+    let request = shared::VerifyRequestEncoded::new(idc.to_string(), confirmation.to_string());
 
     let client = reqwest::Client::new();
     let response = client
@@ -0,0 +1,477 @@
+//! CPace on ristretto255 with SHA-512, following the CFRG CPace draft
+//! (draft-irtf-cfrg-cpace).
+//!
+//! CPace is balanced like SPAKE2, but has no fixed M and N: the generator of
+//! each exchange is hashed from the password-related string PRS, the channel
+//! identifier CI and the session id sid, so nobody knows its discrete log.
+//! The draft only outputs the intermediate session key ISK; this module adds
+//! key confirmation on top, in the same style as SPAKE2+.
+//!
+//! Unlike SPAKE2+, both parties hold the same secret, so a server that stores
+//! a client's PRS can be impersonated to, and can impersonate the client,
+//! with a stolen copy. Clients register a PRS of their own for CPace, never
+//! one derived from their SPAKE2+ registration.
+
+use curve25519_dalek::RistrettoPoint;
+use group::Group;
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha512};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::{
+    ciphersuite::{Ciphersuite, Output, Ristretto255, constant_time_eq, random_nonzero_scalar},
+    spake2plus::{
+        kdf::{KdfError, PasswordKdf},
+        rfc9383::SALT_LEN,
+        secret::{Password, SecretScalar},
+    },
+};
+
+/// Domain separation identifier of the ristretto255 instantiation.
+const DSI: &[u8] = b"CPaceRistretto255";
+
+/// Input block size of SHA-512, which the generator string is padded to.
+const S_IN_BYTES: usize = 128;
+
+/// Length of a key confirmation MAC, an HMAC-SHA512 output.
+pub const CONFIRMATION_LEN: usize = 64;
+
+/// Length of the session ids the client picks for an exchange.
+pub const SID_LEN: usize = 16;
+
+/// Length of the password-related strings derived by
+/// [`password_related_string`].
+pub const PRS_LEN: usize = 64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CpaceError {
+    #[error("key confirmation failed")]
+    ConfirmationFailed,
+
+    #[error("peer share makes K the identity element")]
+    IdentityKeyMaterial,
+}
+
+/// Which side of the exchange a party takes, which selects how the
+/// transcript orders the two shares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Initiator,
+    Responder,
+    /// Neither party knows whether it initiated; the shares are ordered by
+    /// their encoding instead.
+    Symmetric,
+}
+
+/// Prepends the LEB128 encoding of the length of `data`.
+fn prepend_len(buf: &mut Vec<u8>, data: &[u8]) {
+    let mut len = data.len();
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            buf.push(byte);
+            break;
+        }
+        buf.push(byte | 0x80);
+    }
+    buf.extend_from_slice(data);
+}
+
+fn lv_cat(parts: &[&[u8]]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for part in parts {
+        prepend_len(&mut buf, part);
+    }
+    buf
+}
+
+/// The generator string, with zero padding so that PRS ends in the first
+/// hash block.
+fn generator_string(prs: &[u8], ci: &[u8], sid: &[u8]) -> Zeroizing<Vec<u8>> {
+    let prefix_len = |data: &[u8]| lv_cat(&[data]).len();
+    let len_zpad = S_IN_BYTES.saturating_sub(1 + prefix_len(prs) + prefix_len(DSI));
+    Zeroizing::new(lv_cat(&[DSI, prs, &vec![0; len_zpad], ci, sid]))
+}
+
+/// Hashes the generator string to the generator g of this exchange.
+fn calculate_generator(prs: &[u8], ci: &[u8], sid: &[u8]) -> RistrettoPoint {
+    let hash = Sha512::digest(generator_string(prs, ci, sid));
+    RistrettoPoint::from_uniform_bytes(&hash.into())
+}
+
+/// Orders two byte strings, the longer one first on a common prefix.
+fn lexicographically_larger(a: &[u8], b: &[u8]) -> bool {
+    for (x, y) in a.iter().zip(b) {
+        if x != y {
+            return x > y;
+        }
+    }
+    a.len() > b.len()
+}
+
+/// The channel identifier for an exchange between the client `idc` and the
+/// server `ids` under the application `context`.
+pub fn channel_identifier(context: &[u8], idc: &str, ids: &str) -> Vec<u8> {
+    lv_cat(&[context, idc.as_bytes(), ids.as_bytes()])
+}
+
+/// Derives the password-related string PRS of a client registered for CPace.
+///
+/// The length-prefixed encoding of DSI, the password and both identities is
+/// stretched with `kdf` and the user's `salt`, so that a stolen PRS still
+/// takes a dictionary attack to give up the password.
+pub fn password_related_string(
+    password: &Password,
+    idc: &str,
+    ids: &str,
+    salt: &[u8; SALT_LEN],
+    kdf: &PasswordKdf,
+) -> Result<Zeroizing<Vec<u8>>, KdfError> {
+    let input = Zeroizing::new(lv_cat(&[
+        DSI,
+        password.expose_secret().as_bytes(),
+        idc.as_bytes(),
+        ids.as_bytes(),
+    ]));
+    let mut prs = Zeroizing::new(vec![0; PRS_LEN]);
+    kdf.derive(&input, salt, &mut prs)?;
+    Ok(prs)
+}
+
+/// A party's share Y and associated data AD, sent to the peer.
+pub struct CpaceMessage {
+    pub y: RistrettoPoint,
+    pub ad: Vec<u8>,
+}
+
+impl CpaceMessage {
+    fn lv(&self) -> Vec<u8> {
+        lv_cat(&[&Ristretto255::encode_element(&self.y), &self.ad])
+    }
+}
+
+/// Entry point of a CPace exchange.
+pub struct CpaceHandshake;
+
+impl CpaceHandshake {
+    /// Computes this party's share from the password-related string `prs`,
+    /// the channel identifier `ci` and the session id `sid`, which must all
+    /// match on both sides. `ad` is sent along with the share. The ephemeral
+    /// scalar is drawn from `rng`.
+    pub fn start(
+        rng: &mut impl CryptoRngCore,
+        role: Role,
+        prs: &[u8],
+        ci: &[u8],
+        sid: &[u8],
+        ad: &[u8],
+    ) -> (CpaceAwaitingShare, CpaceMessage) {
+        let y = random_nonzero_scalar(rng);
+        Self::start_with_scalar(role, prs, ci, sid, ad, SecretScalar::new(y))
+    }
+
+    fn start_with_scalar(
+        role: Role,
+        prs: &[u8],
+        ci: &[u8],
+        sid: &[u8],
+        ad: &[u8],
+        y: SecretScalar<Ristretto255>,
+    ) -> (CpaceAwaitingShare, CpaceMessage) {
+        let g = calculate_generator(prs, ci, sid);
+        let message = CpaceMessage {
+            y: g * y.expose_secret(),
+            ad: ad.to_vec(),
+        };
+        let state = CpaceAwaitingShare {
+            role,
+            sid: sid.to_vec(),
+            y,
+            own: CpaceMessage {
+                y: message.y,
+                ad: message.ad.clone(),
+            },
+        };
+        (state, message)
+    }
+}
+
+/// A party that has sent its share and waits for the peer's.
+pub struct CpaceAwaitingShare {
+    role: Role,
+    sid: Vec<u8>,
+    y: SecretScalar<Ristretto255>,
+    own: CpaceMessage,
+}
+
+impl CpaceAwaitingShare {
+    /// Computes ISK from the peer's share. Returns the state waiting for the
+    /// peer's confirmation, and this party's confirmation to send.
+    pub fn finish(
+        self,
+        peer: CpaceMessage,
+    ) -> Result<(CpaceAwaitingConfirmation, [u8; CONFIRMATION_LEN]), CpaceError> {
+        let k = peer.y * self.y.expose_secret();
+        if bool::from(k.is_identity()) {
+            return Err(CpaceError::IdentityKeyMaterial);
+        }
+
+        let (own, theirs) = (self.own.lv(), peer.lv());
+        let (transcript, is_initiator) = match self.role {
+            Role::Initiator => ([own, theirs].concat(), true),
+            Role::Responder => ([theirs, own].concat(), false),
+            Role::Symmetric => {
+                let own_larger = lexicographically_larger(&own, &theirs);
+                let (first, second) = if own_larger {
+                    (own, theirs)
+                } else {
+                    (theirs, own)
+                };
+                ([b"oc".as_slice(), &first, &second].concat(), own_larger)
+            }
+        };
+
+        let dsi_isk = [DSI, b"_ISK"].concat();
+        let mut input = Zeroizing::new(lv_cat(&[&dsi_isk, &self.sid, &k.compress().0]));
+        input.extend_from_slice(&transcript);
+        let isk = Zeroizing::new(Sha512::digest(&input).to_vec());
+
+        // Key confirmation keys for the initiating (or larger) and the other
+        // party, MACing the transcript
+        let mut confirmation_keys = Zeroizing::new([0u8; 2 * CONFIRMATION_LEN]);
+        Ristretto255::kdf(
+            None,
+            &isk,
+            b"CPaceConfirmationKeys",
+            &mut *confirmation_keys,
+        );
+        let (kc_first, kc_second) = confirmation_keys.split_at(CONFIRMATION_LEN);
+        let (own_key, peer_key) = if is_initiator {
+            (kc_first, kc_second)
+        } else {
+            (kc_second, kc_first)
+        };
+
+        let confirmation = Ristretto255::mac(own_key, &transcript).into();
+        let state = CpaceAwaitingConfirmation {
+            isk,
+            expected: Ristretto255::mac(peer_key, &transcript),
+        };
+        Ok((state, confirmation))
+    }
+}
+
+/// A party that has sent its confirmation and waits for the peer's.
+pub struct CpaceAwaitingConfirmation {
+    isk: Zeroizing<Vec<u8>>,
+    expected: Output<Ristretto255>,
+}
+
+impl CpaceAwaitingConfirmation {
+    /// Checks the peer's confirmation and releases ISK.
+    pub fn finish(
+        self,
+        confirmation: &[u8; CONFIRMATION_LEN],
+    ) -> Result<Zeroizing<Vec<u8>>, CpaceError> {
+        if !constant_time_eq(&self.expected, confirmation) {
+            return Err(CpaceError::ConfirmationFailed);
+        }
+        Ok(self.isk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    const CI: &[u8] = b"\x0aAinitiator\x0aBresponder";
+    const SID: &[u8] = b"session-id";
+
+    #[test]
+    fn prepend_len_uses_leb128() {
+        let mut buf = Vec::new();
+        prepend_len(&mut buf, &[]);
+        prepend_len(&mut buf, &[7; 127]);
+        assert_eq!(&buf[..2], [0, 127]);
+
+        let mut buf = Vec::new();
+        prepend_len(&mut buf, &[7; 200]);
+        assert_eq!(&buf[..2], [0xc8, 0x01]);
+        assert_eq!(buf.len(), 202);
+    }
+
+    #[test]
+    fn generator_string_pads_prs_to_a_block() {
+        let gen_str = generator_string(b"Password", CI, SID);
+        // DSI and PRS with their lengths, then the zero padding with its
+        // length, fill exactly one block
+        assert_eq!(gen_str.len(), S_IN_BYTES + lv_cat(&[CI, SID]).len());
+        // Long PRS are not padded
+        let prs = [1; 200];
+        let gen_str = generator_string(&prs, CI, SID);
+        assert_eq!(gen_str.len(), lv_cat(&[DSI, &prs, &[], CI, SID]).len());
+    }
+
+    #[test]
+    fn generator_depends_on_all_inputs() {
+        let g = calculate_generator(b"Password", CI, SID);
+        assert_ne!(g, calculate_generator(b"password", CI, SID));
+        assert_ne!(g, calculate_generator(b"Password", b"other", SID));
+        assert_ne!(g, calculate_generator(b"Password", CI, b"other"));
+    }
+
+    #[test]
+    fn prs_depends_on_password_identities_and_salt() {
+        let kdf = PasswordKdf::Pbkdf2 { rounds: 10 };
+        let prs = |password: &str, idc, ids, salt: &[u8; SALT_LEN]| {
+            password_related_string(&Password::from(password), idc, ids, salt, &kdf).unwrap()
+        };
+        let expected = prs("Password", "initiator", "responder", &[1; SALT_LEN]);
+        assert_eq!(expected.len(), PRS_LEN);
+        assert_eq!(
+            expected,
+            prs("Password", "initiator", "responder", &[1; SALT_LEN])
+        );
+        assert_ne!(
+            expected,
+            prs("password", "initiator", "responder", &[1; SALT_LEN])
+        );
+        assert_ne!(
+            expected,
+            prs("Password", "other", "responder", &[1; SALT_LEN])
+        );
+        assert_ne!(
+            expected,
+            prs("Password", "initiator", "other", &[1; SALT_LEN])
+        );
+        assert_ne!(
+            expected,
+            prs("Password", "initiator", "responder", &[2; SALT_LEN])
+        );
+    }
+
+    #[test]
+    fn lexicographic_order() {
+        assert!(lexicographically_larger(b"b", b"a"));
+        assert!(lexicographically_larger(b"ab", b"a"));
+        assert!(!lexicographically_larger(b"a", b"ab"));
+        assert!(!lexicographically_larger(b"a", b"a"));
+    }
+
+    /// The CPace-RISTR255-SHA512 test vectors of the draft, with the
+    /// initiator/responder transcript.
+    #[test]
+    fn draft_test_vectors() {
+        let scalar = |hex: &str| {
+            let bytes = hex::decode(hex).unwrap().try_into().unwrap();
+            SecretScalar::new(curve25519_dalek::Scalar::from_canonical_bytes(bytes).unwrap())
+        };
+        let ci = hex::decode("0a41696e69746961746f720a42726573706f6e646572").unwrap();
+        let sid = hex::decode("7e4b4791d6a8ef019b936c79fb7f2c57").unwrap();
+        let ya = scalar("da3d23700a9e5699258aef94dc060dfda5ebb61f02a5ea77fad53f4ff0976d08");
+        let yb = scalar("d2316b454718c35362d83d69df6320f38578ed5984651435e2949762d900b80d");
+
+        assert_eq!(
+            hex::encode(Sha512::digest(generator_string(b"Password", &ci, &sid))),
+            "a5ce446f63a1ae6d1fee80fa67d0b4004a4b1283ec5549a462bf33a6c1ae06a0\
+             871f9bf48545f49b2a792eed255ac04f52758c9c60448306810b44e986e3dcbb"
+        );
+        assert_eq!(
+            hex::encode(Ristretto255::encode_element(&calculate_generator(
+                b"Password",
+                &ci,
+                &sid
+            ))),
+            "5e25411ca1ad7c9debfd0b33ad987a95cefef2d3f15dcc8bd26415a5dfe2e15a"
+        );
+
+        let (a, a_message) =
+            CpaceHandshake::start_with_scalar(Role::Initiator, b"Password", &ci, &sid, b"ADa", ya);
+        let (b, b_message) =
+            CpaceHandshake::start_with_scalar(Role::Responder, b"Password", &ci, &sid, b"ADb", yb);
+        assert_eq!(
+            hex::encode(Ristretto255::encode_element(&a_message.y)),
+            "383a85dd236978f17f8c8545b50dabc52a39fcdab2cf8bc531ce040ff77ca82d"
+        );
+        assert_eq!(
+            hex::encode(Ristretto255::encode_element(&b_message.y)),
+            "a6206309c0e8e5f579295e35997ac4300ab3fecec3c17f7b604f3e698fa1383c"
+        );
+
+        let isk_ir = "e91ccb2c0f5e0d0993a33956e3be59754f3f2b07db57631f5394452ea2e7b435\
+                      4674eb1f5686c078462bf83bec72e8743df440108e638f3526d9b90e85be096f";
+        let (a, a_confirmation) = a.finish(b_message).unwrap();
+        let (b, b_confirmation) = b.finish(a_message).unwrap();
+        assert_eq!(hex::encode(&*a.finish(&b_confirmation).unwrap()), isk_ir);
+        assert_eq!(hex::encode(&*b.finish(&a_confirmation).unwrap()), isk_ir);
+    }
+
+    /// Runs both sides to completion, returning each side's ISK or error.
+    #[allow(clippy::type_complexity)]
+    fn run(
+        roles: (Role, Role),
+        passwords: (&[u8], &[u8]),
+    ) -> (
+        Result<Zeroizing<Vec<u8>>, CpaceError>,
+        Result<Zeroizing<Vec<u8>>, CpaceError>,
+    ) {
+        let (first, first_message) =
+            CpaceHandshake::start(&mut OsRng, roles.0, passwords.0, CI, SID, b"ADa");
+        let (second, second_message) =
+            CpaceHandshake::start(&mut OsRng, roles.1, passwords.1, CI, SID, b"ADb");
+
+        let (first, first_confirmation) = first.finish(second_message).unwrap();
+        let (second, second_confirmation) = second.finish(first_message).unwrap();
+        (
+            first.finish(&second_confirmation),
+            second.finish(&first_confirmation),
+        )
+    }
+
+    #[test]
+    fn initiator_responder_agree() {
+        let (a, b) = run(
+            (Role::Initiator, Role::Responder),
+            (b"Password", b"Password"),
+        );
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 64);
+    }
+
+    #[test]
+    fn symmetric_mode_agrees() {
+        let (a, b) = run(
+            (Role::Symmetric, Role::Symmetric),
+            (b"Password", b"Password"),
+        );
+        assert_eq!(a.unwrap(), b.unwrap());
+    }
+
+    #[test]
+    fn wrong_password_fails_confirmation() {
+        let (a, b) = run(
+            (Role::Initiator, Role::Responder),
+            (b"Password", b"password"),
+        );
+        assert_eq!(a, Err(CpaceError::ConfirmationFailed));
+        assert_eq!(b, Err(CpaceError::ConfirmationFailed));
+    }
+
+    #[test]
+    fn rejects_identity_share() {
+        let (state, _) =
+            CpaceHandshake::start(&mut OsRng, Role::Initiator, b"Password", CI, SID, b"");
+        let identity = CpaceMessage {
+            y: RistrettoPoint::identity(),
+            ad: Vec::new(),
+        };
+        assert!(matches!(
+            state.finish(identity),
+            Err(CpaceError::IdentityKeyMaterial)
+        ));
+    }
+}
@@ -1,4 +1,9 @@
+pub mod ciphersuite;
 pub mod client;
+pub mod cpace;
+pub mod opaque;
+pub mod spake2;
+pub mod srp;
 pub mod spake2plus;
 pub mod server;
 pub mod shared;
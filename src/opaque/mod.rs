@@ -0,0 +1,823 @@
+//! OPAQUE with ristretto255-SHA512, HKDF-SHA512, HMAC-SHA512 and 3DH, following
+//! RFC 9807.
+//!
+//! OPAQUE is an augmented PAKE like SPAKE2+, but the server never sees
+//! anything derived from the password without its OPRF key: the client's
+//! password is blinded through an OPRF, and the result unlocks an envelope
+//! holding the client's long-term key. Precomputation attacks on a stolen
+//! registration record are therefore impossible, unlike with the phi0 and c
+//! of SPAKE2+.
+//!
+//! The client and server identities are always given explicitly, and the
+//! client identity doubles as the credential identifier.
+
+mod oprf;
+
+use curve25519_dalek::RistrettoPoint;
+use group::Group;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha512};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::{
+    ciphersuite::{Ciphersuite, Ristretto255, constant_time_eq},
+    spake2plus::{
+        kdf::{KdfError, PasswordKdf},
+        secret::{Password, SecretScalar},
+    },
+};
+use oprf::i2osp2;
+
+/// Length of the nonces.
+pub const NONCE_LEN: usize = 32;
+
+/// Length of hashes, MACs and KDF keys, those of SHA-512.
+pub const HASH_LEN: usize = 64;
+
+/// Length of an encoded ristretto255 element.
+const ELEMENT_LEN: usize = 32;
+
+/// Length of the seeds key pairs are derived from.
+const SEED_LEN: usize = 32;
+
+/// Longest client or server identity, whose length OPAQUE encodes in two
+/// bytes.
+pub const MAX_IDENTITY_LEN: usize = u16::MAX as usize;
+
+/// Length of an envelope: its nonce and authentication tag.
+pub const ENVELOPE_LEN: usize = NONCE_LEN + HASH_LEN;
+
+/// Length of the masked server public key and envelope.
+pub const MASKED_RESPONSE_LEN: usize = ELEMENT_LEN + ENVELOPE_LEN;
+
+/// Salt of the key stretching function. The OPRF output is already unique
+/// per user and server, so a fixed salt is enough.
+const STRETCH_SALT: [u8; 16] = [0; 16];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum OpaqueError {
+    #[error("key confirmation failed")]
+    ConfirmationFailed,
+
+    #[error("the envelope could not be recovered")]
+    EnvelopeRecoveryFailed,
+
+    #[error("an identity, context or password is longer than 65535 bytes")]
+    InputTooLong,
+
+    #[error(transparent)]
+    Kdf(#[from] KdfError),
+}
+
+fn expand(prk: &[u8], info: &[&[u8]], output: &mut [u8]) {
+    Hkdf::<Sha512>::from_prk(prk)
+        .expect("PRKs are the length of the hash")
+        .expand_multi_info(info, output)
+        .expect("KDF output length is a small multiple of the hash length");
+}
+
+fn mac(key: &[u8], message: &[&[u8]]) -> [u8; HASH_LEN] {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in message {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+fn random_bytes<const N: usize>(rng: &mut impl CryptoRngCore) -> [u8; N] {
+    let mut bytes = [0; N];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn encode(element: &RistrettoPoint) -> Vec<u8> {
+    Ristretto255::encode_element(element)
+}
+
+/// DeriveDiffieHellmanKeyPair, for the client's long-term key and the
+/// ephemeral key shares.
+fn derive_dh_key_pair(seed: &[u8]) -> (SecretScalar<Ristretto255>, RistrettoPoint) {
+    oprf::derive_key_pair(seed, b"OPAQUE-DeriveDiffieHellmanKeyPair")
+}
+
+/// `server_public_key || I2OSP(len(ids), 2) || ids || I2OSP(len(idc), 2) || idc`,
+/// the data the envelope authenticates.
+fn cleartext_credentials(
+    server_public_key: &RistrettoPoint,
+    idc: &str,
+    ids: &str,
+) -> Result<Vec<u8>, OpaqueError> {
+    let mut credentials = encode(server_public_key);
+    for identity in [ids, idc] {
+        credentials.extend_from_slice(&i2osp2(identity.len())?);
+        credentials.extend_from_slice(identity.as_bytes());
+    }
+    Ok(credentials)
+}
+
+/// Hashes the OPRF output, stretched with `ksf`, into the randomized
+/// password every envelope key is expanded from.
+fn randomized_password(
+    oprf_output: &[u8],
+    ksf: &PasswordKdf,
+) -> Result<Zeroizing<[u8; HASH_LEN]>, OpaqueError> {
+    let mut stretched = Zeroizing::new([0u8; HASH_LEN]);
+    ksf.derive(oprf_output, &STRETCH_SALT, &mut *stretched)?;
+    let (prk, _) = Hkdf::<Sha512>::extract(None, &[oprf_output, &*stretched].concat());
+    Ok(Zeroizing::new(prk.into()))
+}
+
+/// The keys expanded from the randomized password.
+struct EnvelopeKeys {
+    auth_key: Zeroizing<[u8; HASH_LEN]>,
+    export_key: Zeroizing<Vec<u8>>,
+    private_key: SecretScalar<Ristretto255>,
+    public_key: RistrettoPoint,
+}
+
+impl EnvelopeKeys {
+    fn derive(randomized_password: &[u8], nonce: &[u8; NONCE_LEN]) -> Self {
+        let mut auth_key = Zeroizing::new([0u8; HASH_LEN]);
+        expand(randomized_password, &[nonce, b"AuthKey"], &mut *auth_key);
+        let mut export_key = Zeroizing::new(vec![0u8; HASH_LEN]);
+        expand(randomized_password, &[nonce, b"ExportKey"], &mut export_key);
+        let mut seed = Zeroizing::new([0u8; SEED_LEN]);
+        expand(randomized_password, &[nonce, b"PrivateKey"], &mut *seed);
+        let (private_key, public_key) = derive_dh_key_pair(&*seed);
+        Self {
+            auth_key,
+            export_key,
+            private_key,
+            public_key,
+        }
+    }
+}
+
+fn masking_key(randomized_password: &[u8]) -> [u8; HASH_LEN] {
+    let mut masking_key = [0u8; HASH_LEN];
+    expand(randomized_password, &[b"MaskingKey"], &mut masking_key);
+    masking_key
+}
+
+/// XORs `data` with a pad expanded from the masking key and nonce, which
+/// both masks and unmasks the server's credential response.
+fn mask(
+    masking_key: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    data: &[u8; MASKED_RESPONSE_LEN],
+) -> [u8; MASKED_RESPONSE_LEN] {
+    let mut pad = [0u8; MASKED_RESPONSE_LEN];
+    expand(masking_key, &[nonce, b"CredentialResponsePad"], &mut pad);
+    for (pad, byte) in pad.iter_mut().zip(data) {
+        *pad ^= byte;
+    }
+    pad
+}
+
+/// The server's long-term state: its key pair, and the seed each
+/// credential's OPRF key is derived from.
+pub struct ServerSetup {
+    oprf_seed: Zeroizing<[u8; HASH_LEN]>,
+    private_key: SecretScalar<Ristretto255>,
+    public_key: RistrettoPoint,
+}
+
+impl ServerSetup {
+    /// Draws a fresh OPRF seed and server key pair from `rng`.
+    pub fn new(rng: &mut impl CryptoRngCore) -> Self {
+        let seed = Zeroizing::new(random_bytes::<SEED_LEN>(rng));
+        let (private_key, public_key) = derive_dh_key_pair(&*seed);
+        Self {
+            oprf_seed: Zeroizing::new(random_bytes(rng)),
+            private_key,
+            public_key,
+        }
+    }
+
+    pub fn public_key(&self) -> RistrettoPoint {
+        self.public_key
+    }
+
+    /// The OPRF key of `credential_identifier`. It is derived rather than
+    /// stored, so the server answers unknown identifiers consistently.
+    fn oprf_key(&self, credential_identifier: &str) -> SecretScalar<Ristretto255> {
+        let mut seed = Zeroizing::new([0u8; SEED_LEN]);
+        expand(
+            &*self.oprf_seed,
+            &[credential_identifier.as_bytes(), b"OprfKey"],
+            &mut *seed,
+        );
+        let (key, _) = oprf::derive_key_pair(&*seed, b"OPAQUE-DeriveKeyPair");
+        key
+    }
+
+    /// The record answered for an unknown `credential_identifier`. Like the
+    /// OPRF key it is derived from the seed, so repeated logins of the same
+    /// unknown client get the same record, as a registered one would.
+    fn fake_record(&self, credential_identifier: &str) -> RegistrationRecord {
+        let mut seed = Zeroizing::new([0u8; SEED_LEN]);
+        expand(
+            &*self.oprf_seed,
+            &[credential_identifier.as_bytes(), b"FakeClientKey"],
+            &mut *seed,
+        );
+        let (_, client_public_key) = derive_dh_key_pair(&*seed);
+        let mut masking_key = [0u8; HASH_LEN];
+        expand(
+            &*self.oprf_seed,
+            &[credential_identifier.as_bytes(), b"FakeMaskingKey"],
+            &mut masking_key,
+        );
+        RegistrationRecord {
+            client_public_key,
+            masking_key,
+            envelope: [0; ENVELOPE_LEN],
+        }
+    }
+}
+
+/// The client's blinded password, sent to the server to register.
+pub struct RegistrationRequest {
+    pub blinded: RistrettoPoint,
+}
+
+/// The server's OPRF evaluation and public key.
+pub struct RegistrationResponse {
+    pub evaluated: RistrettoPoint,
+    pub server_public_key: RistrettoPoint,
+}
+
+/// What the server stores for a registered client. None of it allows an
+/// offline attack on the password without the server's OPRF key.
+#[derive(Clone)]
+pub struct RegistrationRecord {
+    pub client_public_key: RistrettoPoint,
+    pub masking_key: [u8; HASH_LEN],
+    pub envelope: [u8; ENVELOPE_LEN],
+}
+
+/// Client side of a registration.
+pub struct ClientRegistration;
+
+impl ClientRegistration {
+    /// Blinds the password with a scalar from `rng` for the server's OPRF
+    /// evaluation.
+    pub fn start(
+        rng: &mut impl CryptoRngCore,
+        password: &Password,
+    ) -> (ClientAwaitingRegistration, RegistrationRequest) {
+        let password = Zeroizing::new(password.expose_secret().as_bytes().to_vec());
+        let (blind, blinded) = oprf::blind(rng, &password);
+        let state = ClientAwaitingRegistration { password, blind };
+        (state, RegistrationRequest { blinded })
+    }
+}
+
+/// A client that has sent its blinded password and waits for the
+/// evaluation.
+pub struct ClientAwaitingRegistration {
+    password: Zeroizing<Vec<u8>>,
+    blind: SecretScalar<Ristretto255>,
+}
+
+impl ClientAwaitingRegistration {
+    /// Seals a fresh client key pair into an envelope under the OPRF output
+    /// stretched with `ksf`, with a nonce drawn from `rng`. Returns the
+    /// record for the server, and the export key, which only the client can
+    /// recompute.
+    pub fn finish(
+        self,
+        rng: &mut impl CryptoRngCore,
+        response: RegistrationResponse,
+        ksf: &PasswordKdf,
+        idc: &str,
+        ids: &str,
+    ) -> Result<(RegistrationRecord, Zeroizing<Vec<u8>>), OpaqueError> {
+        let oprf_output = oprf::finalize(&self.password, &self.blind, response.evaluated)?;
+        let randomized_password = randomized_password(&*oprf_output, ksf)?;
+
+        let nonce = random_bytes::<NONCE_LEN>(rng);
+        let keys = EnvelopeKeys::derive(&*randomized_password, &nonce);
+        let credentials = cleartext_credentials(&response.server_public_key, idc, ids)?;
+        let auth_tag = mac(&*keys.auth_key, &[&nonce, &credentials]);
+
+        let mut envelope = [0u8; ENVELOPE_LEN];
+        envelope[..NONCE_LEN].copy_from_slice(&nonce);
+        envelope[NONCE_LEN..].copy_from_slice(&auth_tag);
+        let record = RegistrationRecord {
+            client_public_key: keys.public_key,
+            masking_key: masking_key(&*randomized_password),
+            envelope,
+        };
+        Ok((record, keys.export_key))
+    }
+}
+
+/// Server side of a registration.
+pub struct ServerRegistration;
+
+impl ServerRegistration {
+    /// Evaluates the client's blinded password under the OPRF key of `idc`.
+    /// The server keeps no state until the client uploads its record.
+    pub fn respond(
+        setup: &ServerSetup,
+        idc: &str,
+        request: RegistrationRequest,
+    ) -> RegistrationResponse {
+        RegistrationResponse {
+            evaluated: oprf::evaluate(&setup.oprf_key(idc), request.blinded),
+            server_public_key: setup.public_key,
+        }
+    }
+}
+
+/// KE1: the client's blinded password and key share.
+#[derive(Clone)]
+pub struct LoginRequest {
+    pub blinded: RistrettoPoint,
+    pub client_nonce: [u8; NONCE_LEN],
+    pub client_keyshare: RistrettoPoint,
+}
+
+impl LoginRequest {
+    fn serialize(&self) -> Vec<u8> {
+        [
+            encode(&self.blinded),
+            self.client_nonce.to_vec(),
+            encode(&self.client_keyshare),
+        ]
+        .concat()
+    }
+}
+
+/// KE2: the server's credential response, key share and MAC.
+pub struct LoginResponse {
+    pub evaluated: RistrettoPoint,
+    pub masking_nonce: [u8; NONCE_LEN],
+    pub masked_response: [u8; MASKED_RESPONSE_LEN],
+    pub server_nonce: [u8; NONCE_LEN],
+    pub server_keyshare: RistrettoPoint,
+    pub server_mac: [u8; HASH_LEN],
+}
+
+/// KE3: the client's MAC, confirming it recovered the envelope.
+pub struct LoginFinalization {
+    pub client_mac: [u8; HASH_LEN],
+}
+
+/// The keys of a completed login. The export key is the one of the
+/// registration, and is never known to the server.
+pub struct LoginKeys {
+    pub session_key: Zeroizing<Vec<u8>>,
+    pub export_key: Zeroizing<Vec<u8>>,
+}
+
+/// The 3DH keys of a login.
+struct AkeKeys {
+    session_key: Zeroizing<Vec<u8>>,
+    server_mac: [u8; HASH_LEN],
+    client_mac: [u8; HASH_LEN],
+}
+
+/// `"OPAQUE-" || label` expanded with the length prefixes of Expand-Label.
+fn derive_secret(secret: &[u8], label: &[u8], context: &[u8]) -> Zeroizing<[u8; HASH_LEN]> {
+    let label = [b"OPAQUE-".as_slice(), label].concat();
+    let mut output = Zeroizing::new([0u8; HASH_LEN]);
+    expand(
+        secret,
+        &[
+            &i2osp2(HASH_LEN).expect("the hash length fits in two bytes"),
+            &[label.len() as u8],
+            &label,
+            &[context.len() as u8],
+            context,
+        ],
+        &mut *output,
+    );
+    output
+}
+
+/// Derives the session key and both MACs from the three Diffie-Hellman
+/// results and the preamble of the exchange.
+fn derive_ake_keys(dh: [RistrettoPoint; 3], preamble: &[u8]) -> AkeKeys {
+    let ikm = Zeroizing::new(dh.iter().flat_map(encode).collect::<Vec<_>>());
+    let (prk, _) = Hkdf::<Sha512>::extract(None, &ikm);
+    let prk = Zeroizing::new(prk);
+    let preamble_hash = Sha512::digest(preamble);
+
+    let handshake_secret = derive_secret(&prk, b"HandshakeSecret", &preamble_hash);
+    let session_key = derive_secret(&prk, b"SessionKey", &preamble_hash);
+    let km2 = derive_secret(&*handshake_secret, b"ServerMAC", b"");
+    let km3 = derive_secret(&*handshake_secret, b"ClientMAC", b"");
+
+    let server_mac = mac(&*km2, &[&preamble_hash]);
+    let transcript_hash = Sha512::new()
+        .chain_update(preamble)
+        .chain_update(server_mac)
+        .finalize();
+    AkeKeys {
+        session_key: Zeroizing::new(session_key.to_vec()),
+        server_mac,
+        client_mac: mac(&*km3, &[&transcript_hash]),
+    }
+}
+
+/// The transcript prefix both sides MAC, binding the application `context`
+/// and both identities.
+fn preamble(
+    context: &[u8],
+    idc: &str,
+    ids: &str,
+    request: &LoginRequest,
+    response: &LoginResponse,
+) -> Result<Vec<u8>, OpaqueError> {
+    Ok([
+        b"OPAQUEv1-".as_slice(),
+        &i2osp2(context.len())?,
+        context,
+        &i2osp2(idc.len())?,
+        idc.as_bytes(),
+        &request.serialize(),
+        &i2osp2(ids.len())?,
+        ids.as_bytes(),
+        &encode(&response.evaluated),
+        &response.masking_nonce,
+        &response.masked_response,
+        &response.server_nonce,
+        &encode(&response.server_keyshare),
+    ]
+    .concat())
+}
+
+/// Client side of a login.
+pub struct ClientLogin;
+
+impl ClientLogin {
+    /// Blinds the password and picks the client's ephemeral key share and
+    /// nonce from `rng`.
+    pub fn start(
+        rng: &mut impl CryptoRngCore,
+        password: &Password,
+    ) -> (ClientAwaitingLoginResponse, LoginRequest) {
+        let password = Zeroizing::new(password.expose_secret().as_bytes().to_vec());
+        let (blind, blinded) = oprf::blind(rng, &password);
+        let seed = Zeroizing::new(random_bytes::<SEED_LEN>(rng));
+        let (keyshare_secret, client_keyshare) = derive_dh_key_pair(&*seed);
+        let request = LoginRequest {
+            blinded,
+            client_nonce: random_bytes(rng),
+            client_keyshare,
+        };
+        let state = ClientAwaitingLoginResponse {
+            password,
+            blind,
+            keyshare_secret,
+            request: request.clone(),
+        };
+        (state, request)
+    }
+}
+
+/// A client that has sent KE1 and waits for the server's KE2.
+pub struct ClientAwaitingLoginResponse {
+    password: Zeroizing<Vec<u8>>,
+    blind: SecretScalar<Ristretto255>,
+    keyshare_secret: SecretScalar<Ristretto255>,
+    request: LoginRequest,
+}
+
+impl ClientAwaitingLoginResponse {
+    /// Recovers the envelope with the password, authenticates the server
+    /// and derives the session key. On success the returned KE3 is to be
+    /// sent to the server.
+    pub fn finish(
+        self,
+        response: LoginResponse,
+        ksf: &PasswordKdf,
+        context: &[u8],
+        idc: &str,
+        ids: &str,
+    ) -> Result<(LoginKeys, LoginFinalization), OpaqueError> {
+        let oprf_output = oprf::finalize(&self.password, &self.blind, response.evaluated)?;
+        let randomized_password = randomized_password(&*oprf_output, ksf)?;
+
+        // A wrong password unmasks to garbage, which fails to decode or to
+        // authenticate
+        let unmasked = mask(
+            &masking_key(&*randomized_password),
+            &response.masking_nonce,
+            &response.masked_response,
+        );
+        let (server_public_key, envelope) = unmasked.split_at(ELEMENT_LEN);
+        let server_public_key = Ristretto255::decode_element(server_public_key)
+            .filter(|key| !bool::from(key.is_identity()))
+            .ok_or(OpaqueError::EnvelopeRecoveryFailed)?;
+        let (nonce, auth_tag) = envelope.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce.try_into().expect("envelope nonce length");
+
+        let keys = EnvelopeKeys::derive(&*randomized_password, &nonce);
+        let credentials = cleartext_credentials(&server_public_key, idc, ids)?;
+        if !constant_time_eq(&mac(&*keys.auth_key, &[&nonce, &credentials]), auth_tag) {
+            return Err(OpaqueError::EnvelopeRecoveryFailed);
+        }
+
+        let preamble = preamble(context, idc, ids, &self.request, &response)?;
+        let ake = derive_ake_keys(
+            [
+                response.server_keyshare * self.keyshare_secret.expose_secret(),
+                server_public_key * self.keyshare_secret.expose_secret(),
+                response.server_keyshare * keys.private_key.expose_secret(),
+            ],
+            &preamble,
+        );
+        if !constant_time_eq(&ake.server_mac, &response.server_mac) {
+            return Err(OpaqueError::ConfirmationFailed);
+        }
+
+        let login_keys = LoginKeys {
+            session_key: ake.session_key,
+            export_key: keys.export_key,
+        };
+        let finalization = LoginFinalization {
+            client_mac: ake.client_mac,
+        };
+        Ok((login_keys, finalization))
+    }
+}
+
+/// Server side of a login.
+pub struct ServerLogin;
+
+impl ServerLogin {
+    /// Answers KE1 with the masked envelope of `record` and the server's key
+    /// share and MAC, drawing the nonces and key share from `rng`. For an
+    /// unknown client `record` is `None`, and a fake record derived from the
+    /// setup and `idc` is used so the response does not reveal that the
+    /// client is not registered; the login then fails at the client. Fails
+    /// if the context or an identity is longer than 65535 bytes.
+    pub fn respond(
+        rng: &mut impl CryptoRngCore,
+        setup: &ServerSetup,
+        record: Option<&RegistrationRecord>,
+        context: &[u8],
+        idc: &str,
+        ids: &str,
+        request: LoginRequest,
+    ) -> Result<(ServerAwaitingLoginFinalization, LoginResponse), OpaqueError> {
+        let fake_record;
+        let record = match record {
+            Some(record) => record,
+            None => {
+                fake_record = setup.fake_record(idc);
+                &fake_record
+            }
+        };
+
+        let mut credentials = [0u8; MASKED_RESPONSE_LEN];
+        credentials[..ELEMENT_LEN].copy_from_slice(&encode(&setup.public_key));
+        credentials[ELEMENT_LEN..].copy_from_slice(&record.envelope);
+        let masking_nonce = random_bytes(rng);
+
+        let seed = Zeroizing::new(random_bytes::<SEED_LEN>(rng));
+        let (keyshare_secret, server_keyshare) = derive_dh_key_pair(&*seed);
+        let mut response = LoginResponse {
+            evaluated: oprf::evaluate(&setup.oprf_key(idc), request.blinded),
+            masking_nonce,
+            masked_response: mask(&record.masking_key, &masking_nonce, &credentials),
+            server_nonce: random_bytes(rng),
+            server_keyshare,
+            server_mac: [0; HASH_LEN],
+        };
+
+        let preamble = preamble(context, idc, ids, &request, &response)?;
+        let ake = derive_ake_keys(
+            [
+                request.client_keyshare * keyshare_secret.expose_secret(),
+                request.client_keyshare * setup.private_key.expose_secret(),
+                record.client_public_key * keyshare_secret.expose_secret(),
+            ],
+            &preamble,
+        );
+        response.server_mac = ake.server_mac;
+
+        let state = ServerAwaitingLoginFinalization {
+            session_key: ake.session_key,
+            expected_client_mac: ake.client_mac,
+        };
+        Ok((state, response))
+    }
+}
+
+/// A server that has sent KE2 and waits for the client's KE3.
+pub struct ServerAwaitingLoginFinalization {
+    session_key: Zeroizing<Vec<u8>>,
+    expected_client_mac: [u8; HASH_LEN],
+}
+
+impl ServerAwaitingLoginFinalization {
+    /// Checks the client's MAC and releases the session key.
+    pub fn finish(
+        self,
+        finalization: &LoginFinalization,
+    ) -> Result<Zeroizing<Vec<u8>>, OpaqueError> {
+        if !constant_time_eq(&self.expected_client_mac, &finalization.client_mac) {
+            return Err(OpaqueError::ConfirmationFailed);
+        }
+        Ok(self.session_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    const CONTEXT: &[u8] = b"rusty-pake test";
+    const KSF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+
+    fn register(setup: &ServerSetup, password: &str) -> (RegistrationRecord, Zeroizing<Vec<u8>>) {
+        let (client, request) = ClientRegistration::start(&mut OsRng, &Password::from(password));
+        let response = ServerRegistration::respond(setup, "client", request);
+        client
+            .finish(&mut OsRng, response, &KSF, "client", "server")
+            .unwrap()
+    }
+
+    /// Runs a login, returning the client's result and the server state.
+    fn login(
+        setup: &ServerSetup,
+        record: Option<&RegistrationRecord>,
+        password: &str,
+        server_context: &[u8],
+    ) -> (
+        Result<(LoginKeys, LoginFinalization), OpaqueError>,
+        ServerAwaitingLoginFinalization,
+    ) {
+        let (client, request) = ClientLogin::start(&mut OsRng, &Password::from(password));
+        let (server, response) = ServerLogin::respond(
+            &mut OsRng,
+            setup,
+            record,
+            server_context,
+            "client",
+            "server",
+            request,
+        )
+        .unwrap();
+        let result = client.finish(response, &KSF, CONTEXT, "client", "server");
+        (result, server)
+    }
+
+    #[test]
+    fn login_agrees() {
+        let setup = ServerSetup::new(&mut OsRng);
+        let (record, export_key) = register(&setup, "password123");
+
+        let (result, server) = login(&setup, Some(&record), "password123", CONTEXT);
+        let (keys, finalization) = result.unwrap();
+        let session_key = server.finish(&finalization).unwrap();
+
+        assert_eq!(keys.session_key, session_key);
+        assert_eq!(keys.export_key, export_key);
+    }
+
+    #[test]
+    fn logins_have_fresh_session_keys() {
+        let setup = ServerSetup::new(&mut OsRng);
+        let (record, _) = register(&setup, "password123");
+
+        let (first, _) = login(&setup, Some(&record), "password123", CONTEXT);
+        let (second, _) = login(&setup, Some(&record), "password123", CONTEXT);
+        assert_ne!(first.unwrap().0.session_key, second.unwrap().0.session_key);
+    }
+
+    #[test]
+    fn wrong_password_fails_envelope_recovery() {
+        let setup = ServerSetup::new(&mut OsRng);
+        let (record, _) = register(&setup, "password123");
+
+        let (result, _) = login(&setup, Some(&record), "wrongpassword", CONTEXT);
+        assert_eq!(result.err(), Some(OpaqueError::EnvelopeRecoveryFailed));
+    }
+
+    #[test]
+    fn unknown_client_fails_envelope_recovery() {
+        let setup = ServerSetup::new(&mut OsRng);
+
+        let (result, _) = login(&setup, None, "password123", CONTEXT);
+        assert_eq!(result.err(), Some(OpaqueError::EnvelopeRecoveryFailed));
+    }
+
+    #[test]
+    fn unknown_client_gets_the_same_fake_record() {
+        let setup = ServerSetup::new(&mut OsRng);
+        let record = setup.fake_record("client");
+        let mut credentials = encode(&setup.public_key);
+        credentials.extend_from_slice(&record.envelope);
+
+        // both responses unmask under the same masking key
+        for _ in 0..2 {
+            let (_, request) = ClientLogin::start(&mut OsRng, &Password::from("password123"));
+            let (_, response) = ServerLogin::respond(
+                &mut OsRng, &setup, None, CONTEXT, "client", "server", request,
+            )
+            .unwrap();
+            let unmasked = mask(
+                &record.masking_key,
+                &response.masking_nonce,
+                &response.masked_response,
+            );
+            assert_eq!(unmasked.as_slice(), credentials);
+        }
+
+        let again = setup.fake_record("client");
+        assert_eq!(again.client_public_key, record.client_public_key);
+        assert_eq!(again.masking_key, record.masking_key);
+        for other in [
+            setup.fake_record("other"),
+            ServerSetup::new(&mut OsRng).fake_record("client"),
+        ] {
+            assert_ne!(other.client_public_key, record.client_public_key);
+            assert_ne!(other.masking_key, record.masking_key);
+        }
+    }
+
+    #[test]
+    fn other_server_key_fails_envelope_recovery() {
+        let setup = ServerSetup::new(&mut OsRng);
+        let (record, _) = register(&setup, "password123");
+        let impostor = ServerSetup::new(&mut OsRng);
+
+        let (result, _) = login(&impostor, Some(&record), "password123", CONTEXT);
+        assert_eq!(result.err(), Some(OpaqueError::EnvelopeRecoveryFailed));
+    }
+
+    #[test]
+    fn mismatched_context_fails_confirmation() {
+        let setup = ServerSetup::new(&mut OsRng);
+        let (record, _) = register(&setup, "password123");
+
+        let (result, _) = login(&setup, Some(&record), "password123", b"other context");
+        assert_eq!(result.err(), Some(OpaqueError::ConfirmationFailed));
+    }
+
+    #[test]
+    fn server_rejects_wrong_client_mac() {
+        let setup = ServerSetup::new(&mut OsRng);
+        let (record, _) = register(&setup, "password123");
+
+        let (result, server) = login(&setup, Some(&record), "password123", CONTEXT);
+        let (_, mut finalization) = result.unwrap();
+        finalization.client_mac[0] ^= 1;
+        assert_eq!(
+            server.finish(&finalization).err(),
+            Some(OpaqueError::ConfirmationFailed)
+        );
+    }
+
+    #[test]
+    fn long_identities_are_rejected() {
+        let setup = ServerSetup::new(&mut OsRng);
+        let long = "a".repeat(MAX_IDENTITY_LEN + 1);
+        let respond = |idc: &str, ids: &str| {
+            let (_, request) = ClientLogin::start(&mut OsRng, &Password::from("password123"));
+            ServerLogin::respond(&mut OsRng, &setup, None, CONTEXT, idc, ids, request).map(|_| ())
+        };
+
+        assert_eq!(respond(&long[1..], "server"), Ok(()));
+        assert_eq!(respond(&long, "server"), Err(OpaqueError::InputTooLong));
+        assert_eq!(respond("client", &long), Err(OpaqueError::InputTooLong));
+
+        let (record, _) = register(&setup, "password123");
+        let (client, request) = ClientLogin::start(&mut OsRng, &Password::from("password123"));
+        let (_, response) = ServerLogin::respond(
+            &mut OsRng,
+            &setup,
+            Some(&record),
+            CONTEXT,
+            "client",
+            "server",
+            request,
+        )
+        .unwrap();
+        assert_eq!(
+            client
+                .finish(response, &KSF, CONTEXT, &long, "server")
+                .err(),
+            Some(OpaqueError::InputTooLong)
+        );
+    }
+
+    #[test]
+    fn masking_is_an_involution() {
+        let key = [1u8; HASH_LEN];
+        let nonce = [2u8; NONCE_LEN];
+        let data = [3u8; MASKED_RESPONSE_LEN];
+        let masked = mask(&key, &nonce, &data);
+        assert_ne!(masked, data);
+        assert_eq!(mask(&key, &nonce, &masked), data);
+    }
+}
@@ -0,0 +1,166 @@
+//! The OPRF of RFC 9497 in base mode with ristretto255-SHA512: blinding,
+//! evaluation and finalization, plus the deterministic key derivation that
+//! OPAQUE also uses for its Diffie-Hellman key pairs.
+
+use curve25519_dalek::{RistrettoPoint, Scalar};
+use elliptic_curve::hash2curve::{ExpandMsg, ExpandMsgXmd, Expander};
+use group::{Group, ff::Field};
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha512};
+use zeroize::Zeroizing;
+
+use super::OpaqueError;
+use crate::{
+    ciphersuite::{Ciphersuite, Ristretto255, hash_to_group, random_nonzero_scalar},
+    spake2plus::secret::SecretScalar,
+};
+
+/// `"OPRFV1-" || I2OSP(mode, 1) || "-" || identifier` for the base mode.
+const CONTEXT_STRING: &[u8] = b"OPRFV1-\x00-ristretto255-SHA512";
+
+/// Length of an OPRF output, that of SHA-512.
+pub const OUTPUT_LEN: usize = 64;
+
+/// Encodes a length as the two big-endian bytes of I2OSP(len, 2), failing
+/// for inputs of 64 KiB or more.
+pub(super) fn i2osp2(len: usize) -> Result<[u8; 2], OpaqueError> {
+    u16::try_from(len)
+        .map(u16::to_be_bytes)
+        .map_err(|_| OpaqueError::InputTooLong)
+}
+
+/// The ristretto255 HashToScalar: 64 bytes of expand_message_xmd with
+/// SHA-512, reduced modulo the group order.
+fn hash_to_scalar(msg: &[&[u8]], dst: &[u8]) -> Scalar {
+    let mut uniform_bytes = Zeroizing::new([0u8; 64]);
+    ExpandMsgXmd::<Sha512>::expand_message(msg, &[dst], uniform_bytes.len())
+        .expect("64 bytes is a valid expand_message_xmd output length")
+        .fill_bytes(&mut *uniform_bytes);
+    Scalar::from_bytes_mod_order_wide(&uniform_bytes)
+}
+
+/// DeriveKeyPair: deterministically derives a non-zero private key from
+/// `seed` and `info`.
+pub(super) fn derive_key_pair(
+    seed: &[u8],
+    info: &[u8],
+) -> (SecretScalar<Ristretto255>, RistrettoPoint) {
+    let dst = [b"DeriveKeyPair".as_slice(), CONTEXT_STRING].concat();
+    for counter in 0..=u8::MAX {
+        let info_len = i2osp2(info.len()).expect("key pair infos are short constants");
+        let sk = hash_to_scalar(&[seed, &info_len, info, &[counter]], &dst);
+        if !bool::from(sk.is_zero()) {
+            let pk = RistrettoPoint::generator() * sk;
+            return (SecretScalar::new(sk), pk);
+        }
+    }
+    panic!("DeriveKeyPair should find a non-zero key!");
+}
+
+fn hash_input(input: &[u8]) -> RistrettoPoint {
+    hash_to_group(
+        input,
+        &[b"HashToGroup-".as_slice(), CONTEXT_STRING].concat(),
+    )
+}
+
+/// Blinds `input` with a fresh scalar from `rng`, returning the scalar and
+/// the blinded element for the server.
+pub(super) fn blind(
+    rng: &mut impl CryptoRngCore,
+    input: &[u8],
+) -> (SecretScalar<Ristretto255>, RistrettoPoint) {
+    blind_with_scalar(input, SecretScalar::new(random_nonzero_scalar(rng)))
+}
+
+fn blind_with_scalar(
+    input: &[u8],
+    r: SecretScalar<Ristretto255>,
+) -> (SecretScalar<Ristretto255>, RistrettoPoint) {
+    let blinded = hash_input(input) * r.expose_secret();
+    (r, blinded)
+}
+
+/// BlindEvaluate: the server's step, applying its key to a blinded element.
+pub(super) fn evaluate(
+    key: &SecretScalar<Ristretto255>,
+    blinded: RistrettoPoint,
+) -> RistrettoPoint {
+    blinded * key.expose_secret()
+}
+
+/// Unblinds the server's evaluation and hashes it with `input` into the
+/// OPRF output.
+pub(super) fn finalize(
+    input: &[u8],
+    r: &SecretScalar<Ristretto255>,
+    evaluated: RistrettoPoint,
+) -> Result<Zeroizing<[u8; OUTPUT_LEN]>, OpaqueError> {
+    let unblinded = evaluated * r.expose_secret().invert();
+    let unblinded = Zeroizing::new(Ristretto255::encode_element(&unblinded));
+    let output = Sha512::new()
+        .chain_update(i2osp2(input.len())?)
+        .chain_update(input)
+        .chain_update(i2osp2(unblinded.len())?)
+        .chain_update(&*unblinded)
+        .chain_update(b"Finalize")
+        .finalize();
+    Ok(Zeroizing::new(output.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    // RFC 9497 appendix A.1.1, the OPRF outputs of ristretto255-SHA512. The
+    // output does not depend on the blind, so the vectors' blinds are not
+    // needed to reproduce it.
+    const SEED: [u8; 32] = [0xa3; 32];
+    const KEY_INFO: &[u8] = b"test key";
+
+    fn evaluate_input(input: &[u8]) -> String {
+        let (key, _) = derive_key_pair(&SEED, KEY_INFO);
+        let (r, blinded) = blind(&mut OsRng, input);
+        hex::encode(*finalize(input, &r, evaluate(&key, blinded)).unwrap())
+    }
+
+    #[test]
+    fn derive_key_pair_test_vector() {
+        let (key, public_key) = derive_key_pair(&SEED, KEY_INFO);
+        assert_eq!(
+            hex::encode(key.expose_secret().to_bytes()),
+            "5ebcea5ee37023ccb9fc2d2019f9d7737be85591ae8652ffa9ef0f4d37063b0e"
+        );
+        assert_eq!(
+            public_key,
+            RistrettoPoint::generator() * key.expose_secret()
+        );
+    }
+
+    #[test]
+    fn rfc9497_test_vectors() {
+        assert_eq!(
+            evaluate_input(&[0x00]),
+            "527759c3d9366f277d8c6020418d96bb393ba2afb20ff90df23fb7708264e2f3\
+             ab9135e3bd69955851de4b1f9fe8a0973396719b7912ba9ee8aa7d0b5e24bcf6"
+        );
+        assert_eq!(
+            evaluate_input(&[0x5a; 17]),
+            "f4a74c9c592497375e796aa837e907b1a045d34306a749db9f34221f7e750cb4\
+             f2a6413a6bf6fa5e19ba6348eb673934a722a7ede2e7621306d18951e7cf2c73"
+        );
+    }
+
+    #[test]
+    fn output_depends_on_key() {
+        let input = b"password";
+        let (r, blinded) = blind(&mut OsRng, input);
+        let (key, _) = derive_key_pair(&SEED, KEY_INFO);
+        let (other_key, _) = derive_key_pair(&SEED, b"other key");
+        assert_ne!(
+            finalize(input, &r, evaluate(&key, blinded)).unwrap(),
+            finalize(input, &r, evaluate(&other_key, blinded)).unwrap()
+        );
+    }
+}
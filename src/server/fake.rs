@@ -0,0 +1,141 @@
+//! Stand-ins for the registrations of ids that are not registered.
+//!
+//! The server answers such ids as it answers registered ones, with a salt,
+//! KDF and exchange derived from a fake registration, so that the exchange
+//! fails only at confirmation, like one with a wrong password, and nobody can
+//! tell which ids are registered. Every value is derived from a seed of the
+//! server and the id, so repeated requests for the same id get the same
+//! answer, as they would for a registered one.
+
+use hkdf::Hkdf;
+use rand_core::CryptoRngCore;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::{
+    ciphersuite::{Base, Ciphersuite},
+    cpace::PRS_LEN,
+    spake2plus::{
+        rfc9383::{SALT_LEN, secret_seed_len},
+        secret::{Password, SecretScalar},
+    },
+    srp::{self, SrpGroup},
+};
+
+/// Length of the seed every fake registration is derived from.
+const SEED_LEN: usize = 32;
+
+pub(crate) struct FakeRegistrations {
+    seed: Zeroizing<[u8; SEED_LEN]>,
+}
+
+impl FakeRegistrations {
+    /// Draws a fresh seed from `rng`.
+    pub(crate) fn new(rng: &mut impl CryptoRngCore) -> Self {
+        let mut seed = Zeroizing::new([0u8; SEED_LEN]);
+        rng.fill_bytes(&mut *seed);
+        Self { seed }
+    }
+
+    /// Fills `output` with HKDF-Expand, that is HMAC-SHA256 keyed with the
+    /// seed, over the length-prefixed `id` and a `label` per value.
+    fn derive(&self, id: &str, label: &[u8], output: &mut [u8]) {
+        Hkdf::<Sha256>::from_prk(&*self.seed)
+            .expect("the seed is as long as the hash")
+            .expand_multi_info(
+                &[&(id.len() as u64).to_le_bytes(), id.as_bytes(), label],
+                output,
+            )
+            .expect("fake values are a few hash lengths long");
+    }
+
+    /// The salt `/prepare` answers for `id`, with the default KDF.
+    pub(crate) fn salt(&self, id: &str) -> [u8; SALT_LEN] {
+        let mut salt = [0u8; SALT_LEN];
+        self.derive(id, b"Salt", &mut salt);
+        salt
+    }
+
+    /// A SPAKE2+ phi0 and c for `id`.
+    pub(crate) fn spake2plus<S: Ciphersuite>(&self, id: &str) -> (SecretScalar<S>, S::Group) {
+        let mut phi0 = Zeroizing::new(vec![0u8; secret_seed_len::<S>()]);
+        self.derive(id, b"Spake2PlusPhi0", &mut phi0);
+        let mut phi1 = Zeroizing::new(vec![0u8; secret_seed_len::<S>()]);
+        self.derive(id, b"Spake2PlusPhi1", &mut phi1);
+        let phi1 = SecretScalar::<S>::new(S::reduce_scalar(&phi1));
+        (
+            SecretScalar::new(S::reduce_scalar(&phi0)),
+            S::mul_base(Base::G, phi1.expose_secret()),
+        )
+    }
+
+    /// A CPace password-related string for `id`.
+    pub(crate) fn cpace_prs(&self, id: &str) -> Zeroizing<Vec<u8>> {
+        let mut prs = Zeroizing::new(vec![0u8; PRS_LEN]);
+        self.derive(id, b"CpacePrs", &mut prs);
+        prs
+    }
+
+    /// An SRP-6a salt for `id`.
+    pub(crate) fn srp_salt(&self, id: &str) -> Vec<u8> {
+        let mut salt = vec![0u8; srp::SALT_LEN];
+        self.derive(id, b"SrpSalt", &mut salt);
+        salt
+    }
+
+    /// An SRP-6a verifier for `id` in `group`, of a password derived like
+    /// the other values.
+    pub(crate) fn srp_verifier(&self, group: SrpGroup, id: &str) -> Zeroizing<Vec<u8>> {
+        let mut password = Zeroizing::new([0u8; 32]);
+        self.derive(id, b"SrpPassword", &mut *password);
+        let password = Password::new(hex::encode(*password));
+        srp::compute_verifier(group, id, &password, &self.srp_salt(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphersuite::Ristretto255;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn fake_registrations_are_stable_per_id() {
+        let fakes = FakeRegistrations::new(&mut OsRng);
+        let others = FakeRegistrations::new(&mut OsRng);
+
+        assert_eq!(fakes.salt("alice"), fakes.salt("alice"));
+        assert_ne!(fakes.salt("alice"), fakes.salt("bob"));
+        assert_ne!(fakes.salt("alice"), others.salt("alice"));
+
+        let (phi0, c) = fakes.spake2plus::<Ristretto255>("alice");
+        let (again, c_again) = fakes.spake2plus::<Ristretto255>("alice");
+        assert_eq!(phi0.expose_secret(), again.expose_secret());
+        assert_eq!(c, c_again);
+        assert_ne!(c, fakes.spake2plus::<Ristretto255>("bob").1);
+
+        assert_eq!(fakes.cpace_prs("alice"), fakes.cpace_prs("alice"));
+        assert_ne!(fakes.cpace_prs("alice"), fakes.cpace_prs("bob"));
+        assert_eq!(fakes.srp_salt("alice"), fakes.srp_salt("alice"));
+        assert_ne!(fakes.srp_salt("alice"), fakes.srp_salt("bob"));
+    }
+
+    #[test]
+    fn ids_cannot_run_into_labels() {
+        let fakes = FakeRegistrations::new(&mut OsRng);
+        let mut first = [0u8; SALT_LEN];
+        fakes.derive("ab", b"c", &mut first);
+        let mut second = [0u8; SALT_LEN];
+        fakes.derive("a", b"bc", &mut second);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn fake_srp_verifier_is_valid() {
+        let fakes = FakeRegistrations::new(&mut OsRng);
+        let group = SrpGroup::G1024;
+        let verifier = fakes.srp_verifier(group, "alice");
+        assert_eq!(group.check_verifier(&verifier), Ok(()));
+        assert_eq!(verifier, fakes.srp_verifier(group, "alice"));
+    }
+}
@@ -0,0 +1,1041 @@
+mod fake;
+mod store;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use rand::{CryptoRng, RngCore, SeedableRng, rngs::OsRng};
+use rand_chacha::ChaCha20Rng;
+use tower_http::trace::TraceLayer;
+use tracing::{error, info};
+use zeroize::Zeroizing;
+
+use crate::{
+    ciphersuite::{Ciphersuite, Ristretto255},
+    cpace::{self, CpaceAwaitingConfirmation, CpaceHandshake, CpaceMessage},
+    opaque::{
+        LoginFinalization, LoginRequest, RegistrationRecord, RegistrationRequest,
+        ServerAwaitingLoginFinalization, ServerLogin, ServerRegistration, ServerSetup,
+    },
+    shared::{
+        CpaceExchangeRequestEncoded, CpaceExchangeResponse, CpaceExchangeResponseEncoded,
+        CpaceSetupRequestEncoded, CpaceVerifyRequestEncoded, ExchangeRequestEncoded,
+        ExchangeResponse, ExchangeResponseEncoded, OpaqueLoginFinishRequestEncoded,
+        OpaqueLoginStartRequestEncoded, OpaqueLoginStartResponse, OpaqueLoginStartResponseEncoded,
+        OpaqueRegisterFinishRequestEncoded, OpaqueRegisterStartRequestEncoded,
+        OpaqueRegisterStartResponse, OpaqueRegisterStartResponseEncoded, PrepareRequestEncoded,
+        PrepareResponse, PrepareResponseEncoded, SetupRequestEncoded, SrpExchangeRequestEncoded,
+        SrpExchangeResponse, SrpExchangeResponseEncoded, SrpSetupRequestEncoded,
+        SrpVerifyRequestEncoded, SrpVerifyResponse, SrpVerifyResponseEncoded, VerifyRequestEncoded,
+    },
+    spake2plus::{
+        handshake::{ClientMessage, ServerAwaitingConfirmation, ServerHandshake},
+        kdf::PasswordKdf,
+        rfc9383::SALT_LEN,
+        secret::SecretScalar,
+    },
+    srp::{SrpClientMessage, SrpGroup, SrpServer, SrpServerAwaitingClient},
+};
+use fake::FakeRegistrations;
+use store::{PendingCache, SessionStore, StoreError};
+
+/// Most handshakes of unknown clients kept pending at once, per protocol.
+const UNKNOWN_PENDING: usize = 1024;
+
+struct AppState<S: Ciphersuite> {
+    id: String,
+    context: Vec<u8>,
+    sessions: Arc<SessionStore<Registration<S>, Pending<S>>>,
+    opaque_setup: Arc<ServerSetup>,
+    opaque_accounts: Arc<Mutex<HashMap<String, OpaqueAccount>>>,
+    fakes: Arc<FakeRegistrations>,
+    unknown_handshakes: Arc<PendingCache<ServerAwaitingConfirmation<S>>>,
+    unknown_cpace: Arc<PendingCache<CpaceAwaitingConfirmation>>,
+    unknown_srp: Arc<PendingCache<SrpServerAwaitingClient>>,
+    fake_srp_verifiers: Arc<PendingCache<Zeroizing<Vec<u8>>>>,
+    unknown_logins: Arc<PendingCache<ServerAwaitingLoginFinalization>>,
+    rng: Arc<Mutex<ServerRng>>,
+}
+
+// Derived Clone would require S: Clone
+impl<S: Ciphersuite> Clone for AppState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            context: self.context.clone(),
+            sessions: self.sessions.clone(),
+            opaque_setup: self.opaque_setup.clone(),
+            opaque_accounts: self.opaque_accounts.clone(),
+            fakes: self.fakes.clone(),
+            unknown_handshakes: self.unknown_handshakes.clone(),
+            unknown_cpace: self.unknown_cpace.clone(),
+            unknown_srp: self.unknown_srp.clone(),
+            fake_srp_verifiers: self.fake_srp_verifiers.clone(),
+            unknown_logins: self.unknown_logins.clone(),
+            rng: self.rng.clone(),
+        }
+    }
+}
+
+/// The handshakes a client has started and not yet confirmed, at most one
+/// per protocol. Those of ids not registered with the protocol are answered
+/// from a fake registration and kept pending in bounded caches instead, so
+/// they fail at confirmation without creating a session.
+struct Pending<S: Ciphersuite> {
+    handshake: Option<ServerAwaitingConfirmation<S>>,
+    cpace: Option<CpaceAwaitingConfirmation>,
+    srp: Option<SrpServerAwaitingClient>,
+}
+
+// Derived Default would require S: Default
+impl<S: Ciphersuite> Default for Pending<S> {
+    fn default() -> Self {
+        Self {
+            handshake: None,
+            cpace: None,
+            srp: None,
+        }
+    }
+}
+
+/// What a client registered with. CPace and legacy SRP clients share the
+/// session store with SPAKE2+ clients, but each can only use the routes of
+/// its protocol.
+enum Registration<S: Ciphersuite> {
+    Spake2Plus {
+        phi0: SecretScalar<S>,
+        c: S::Group,
+        salt: [u8; SALT_LEN],
+        kdf: PasswordKdf,
+    },
+    Cpace {
+        prs: Zeroizing<Vec<u8>>,
+        salt: [u8; SALT_LEN],
+        kdf: PasswordKdf,
+    },
+    Srp {
+        group: SrpGroup,
+        salt: Vec<u8>,
+        verifier: Zeroizing<Vec<u8>>,
+    },
+}
+
+/// A registered OPAQUE client, kept apart from the SPAKE2+ sessions. Logins
+/// of unknown clients are answered with a fake record and kept pending in a
+/// bounded cache instead, so they can be failed like any other without
+/// creating an account.
+struct OpaqueAccount {
+    record: RegistrationRecord,
+    kdf: PasswordKdf,
+    login: Option<ServerAwaitingLoginFinalization>,
+}
+
+/// Where the server draws its ephemeral scalars, nonces and keys from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Randomness {
+    /// The operating system's generator.
+    #[default]
+    Os,
+    /// ChaCha20 seeded with the given value, so that runs with the same
+    /// requests are reproducible, for demos, teaching and deterministic
+    /// tests. Anyone who knows the seed can recompute every secret, so
+    /// release builds refuse to start with it.
+    Seeded(u64),
+}
+
+/// The generator behind [`Randomness`].
+enum ServerRng {
+    Os(OsRng),
+    Seeded(Box<ChaCha20Rng>),
+}
+
+impl ServerRng {
+    fn new(randomness: Randomness) -> Self {
+        match randomness {
+            Randomness::Os => ServerRng::Os(OsRng),
+            Randomness::Seeded(seed) => {
+                ServerRng::Seeded(Box::new(ChaCha20Rng::seed_from_u64(seed)))
+            }
+        }
+    }
+}
+
+impl RngCore for ServerRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            ServerRng::Os(rng) => rng.next_u32(),
+            ServerRng::Seeded(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            ServerRng::Os(rng) => rng.next_u64(),
+            ServerRng::Seeded(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            ServerRng::Os(rng) => rng.fill_bytes(dest),
+            ServerRng::Seeded(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            ServerRng::Os(rng) => rng.try_fill_bytes(dest),
+            ServerRng::Seeded(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+impl CryptoRng for ServerRng {}
+
+/// Runs the server. Every exchange binds `context` into its keys, so clients
+/// must be configured with the same context, e.g. [`CONTEXT`].
+///
+/// Panics if `randomness` is seeded in a release build.
+///
+/// [`CONTEXT`]: crate::shared::CONTEXT
+pub async fn run<S: Ciphersuite>(port: u32, id: &str, context: &[u8], randomness: Randomness) {
+    let app = router::<S>(id, context, randomness);
+
+    let address = format!("0.0.0.0:{}", port);
+    println!("listening on http://{}", address);
+    let listener = tokio::net::TcpListener::bind(address).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// Builds the server's routes with fresh state, to be served by [`run`] or
+/// driven in-process, e.g. by benchmarks.
+///
+/// Panics if `randomness` is seeded in a release build.
+pub fn router<S: Ciphersuite>(id: &str, context: &[u8], randomness: Randomness) -> Router {
+    if matches!(randomness, Randomness::Seeded(_)) && !cfg!(debug_assertions) {
+        panic!("seeded randomness is insecure, refusing to start a release build with it");
+    }
+    let mut rng = ServerRng::new(randomness);
+
+    let appstate = AppState::<S> {
+        id: id.to_string(),
+        context: context.to_vec(),
+        sessions: Arc::new(SessionStore::default()),
+        opaque_setup: Arc::new(ServerSetup::new(&mut rng)),
+        opaque_accounts: Arc::new(Mutex::new(HashMap::new())),
+        fakes: Arc::new(FakeRegistrations::new(&mut rng)),
+        unknown_handshakes: Arc::new(PendingCache::new(UNKNOWN_PENDING)),
+        unknown_cpace: Arc::new(PendingCache::new(UNKNOWN_PENDING)),
+        unknown_srp: Arc::new(PendingCache::new(UNKNOWN_PENDING)),
+        fake_srp_verifiers: Arc::new(PendingCache::new(UNKNOWN_PENDING)),
+        unknown_logins: Arc::new(PendingCache::new(UNKNOWN_PENDING)),
+        rng: Arc::new(Mutex::new(rng)),
+    };
+
+    Router::new()
+        .route("/id", get(handle_id::<S>))
+        .route("/suite", get(handle_suite::<S>))
+        .route("/setup", post(handle_setup::<S>))
+        .route("/prepare", post(handle_prepare::<S>))
+        .route("/exchange", post(handle_exchange::<S>))
+        .route("/verify", post(handle_verify::<S>))
+        .route("/cpace/setup", post(handle_cpace_setup::<S>))
+        .route("/cpace/exchange", post(handle_cpace_exchange::<S>))
+        .route("/cpace/verify", post(handle_cpace_verify::<S>))
+        .route("/srp/setup", post(handle_srp_setup::<S>))
+        .route("/srp/exchange", post(handle_srp_exchange::<S>))
+        .route("/srp/verify", post(handle_srp_verify::<S>))
+        .route(
+            "/opaque/register/start",
+            post(handle_opaque_register_start::<S>),
+        )
+        .route(
+            "/opaque/register/finish",
+            post(handle_opaque_register_finish::<S>),
+        )
+        .route("/opaque/login/start", post(handle_opaque_login_start::<S>))
+        .route(
+            "/opaque/login/finish",
+            post(handle_opaque_login_finish::<S>),
+        )
+        .with_state(appstate)
+        .layer(TraceLayer::new_for_http())
+}
+
+async fn handle_id<S: Ciphersuite>(State(state): State<AppState<S>>) -> impl IntoResponse {
+    info!("/id");
+    state.id
+}
+
+async fn handle_suite<S: Ciphersuite>() -> impl IntoResponse {
+    info!("/suite");
+    S::NAME
+}
+
+async fn handle_setup<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<SetupRequestEncoded>,
+) -> Result<(), StatusCode> {
+    let request = match request.decode::<S>() {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/setup failed to decode request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let id = request.id;
+    let c = hex::encode(S::encode_element(&request.c));
+    let kdf = request.kdf;
+    if !kdf.meets_minimum() {
+        error!(%id, %kdf, "/setup password KDF is below the minimum cost");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let registration = Registration::Spake2Plus {
+        phi0: request.phi0,
+        c: request.c,
+        salt: request.salt,
+        kdf: request.kdf,
+    };
+    match state.sessions.register(id.clone(), registration) {
+        Ok(()) => {}
+        Err(StoreError::AlreadyRegistered) => {
+            error!(%id, "/setup client id is already setup");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(error) => {
+            error!(%error, "/setup failed to store registration");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    info!(%id, %c, %kdf, "/setup completed");
+    Ok(())
+}
+
+async fn handle_prepare<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<PrepareRequestEncoded>,
+) -> Result<Json<PrepareResponseEncoded>, StatusCode> {
+    let registration = match state.sessions.registration(&request.id) {
+        Ok(r) => Some(r),
+        Err(StoreError::NotFound) => None,
+        Err(error) => {
+            error!(%error, "/prepare failed to look up session");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    // ids without a salt get a fake one, so the response does not tell
+    // whether the client is registered. They get the default KDF, though, so
+    // a client registered with another one can be told apart from a missing
+    // one
+    let (salt, kdf) = match registration.as_deref() {
+        Some(
+            Registration::Spake2Plus { salt, kdf, .. } | Registration::Cpace { salt, kdf, .. },
+        ) => (*salt, *kdf),
+        _ => {
+            info!(id = %request.id, "/prepare client is not registered with SPAKE2+ or CPace");
+            (state.fakes.salt(&request.id), PasswordKdf::default())
+        }
+    };
+
+    info!(id = %request.id, kdf = %kdf, "/prepare completed");
+    Ok(Json(PrepareResponse::new(salt, kdf).encode()))
+}
+
+async fn handle_exchange<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<ExchangeRequestEncoded>,
+) -> Result<Json<ExchangeResponseEncoded>, StatusCode> {
+    let registration = match state.sessions.registration(&request.id) {
+        Ok(r) => Some(r),
+        Err(StoreError::NotFound) => None,
+        Err(error) => {
+            error!(%error, "/exchange failed to look up session");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    // other ids get a handshake with a fake registration, which fails at
+    // /verify like one with a wrong password
+    let fake;
+    let (phi0, c, registered) = match registration.as_deref() {
+        Some(Registration::Spake2Plus { phi0, c, .. }) => (phi0, *c, true),
+        _ => {
+            info!(id = %request.id, "/exchange client is not registered with SPAKE2+");
+            fake = state.fakes.spake2plus::<S>(&request.id);
+            (&fake.0, fake.1, false)
+        }
+    };
+
+    let request = match request.decode::<S>() {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/exchange failed to decode request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    // server step, deriving the keys
+    let u = request.u;
+    let mut rng = match state.rng.lock() {
+        Ok(r) => r,
+        _ => {
+            error!("/exchange failed to lock rng");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let (handshake, response) = match ServerHandshake::respond(
+        &mut *rng,
+        &state.context,
+        &request.id,
+        &state.id,
+        phi0,
+        c,
+        ClientMessage { u },
+    ) {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, id = %request.id, "/exchange handshake aborted");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    drop(rng);
+    info!(
+        id = %request.id,
+        u = %hex::encode(S::encode_element(&u)),
+        v = %hex::encode(S::encode_element(&response.v)),
+        "/exchange completed"
+    );
+
+    // store the handshake in client session until the client confirms,
+    // replacing any earlier one
+    let stored = match registered {
+        true => state.sessions.pending(&request.id, |pending| {
+            pending.handshake = Some(handshake);
+        }),
+        false => state
+            .unknown_handshakes
+            .insert(request.id.clone(), handshake),
+    };
+    if let Err(error) = stored {
+        error!(%error, "/exchange failed to store handshake");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(Json(
+        ExchangeResponse::<S>::new(response.v, response.confirm_v).encode(),
+    ))
+}
+
+async fn handle_verify<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<VerifyRequestEncoded>,
+) -> Result<(), StatusCode> {
+    // a handshake can only be verified once, a failed attempt discards it
+    let handshake = match state
+        .sessions
+        .pending(&request.idc, |pending| pending.handshake.take())
+    {
+        Ok(Some(h)) => Ok(Some(h)),
+        Ok(None) | Err(StoreError::NotFound) => state.unknown_handshakes.take(&request.idc),
+        Err(error) => Err(error),
+    };
+    let handshake = match handshake {
+        Ok(Some(h)) => h,
+        Ok(None) => {
+            info!(id = %request.idc, "/verify no handshake pending for client");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(error) => {
+            error!(%error, "/verify failed to take handshake");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let request = request.decode::<S>().map_err(|error| {
+        info!(%error, "/verify failed to decode request");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    if handshake.finish(&request.confirm_p).is_err() {
+        info!(id = %request.idc, "/verify verification failed!");
+
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    info!(id = %request.idc, "/verify verification succeeded");
+    Ok(())
+}
+
+/// CPace alternative to `/setup`, registering the client's password-related
+/// string under the same session store.
+async fn handle_cpace_setup<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<CpaceSetupRequestEncoded>,
+) -> Result<(), StatusCode> {
+    let request = match request.decode() {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/cpace/setup failed to decode request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let id = request.id;
+    let kdf = request.kdf;
+    if !kdf.meets_minimum() {
+        error!(%id, %kdf, "/cpace/setup password KDF is below the minimum cost");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let registration = Registration::Cpace {
+        prs: request.prs,
+        salt: request.salt,
+        kdf: request.kdf,
+    };
+    match state.sessions.register(id.clone(), registration) {
+        Ok(()) => {}
+        Err(StoreError::AlreadyRegistered) => {
+            error!(%id, "/cpace/setup client id is already setup");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(error) => {
+            error!(%error, "/cpace/setup failed to store registration");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    info!(%id, %kdf, "/cpace/setup completed");
+    Ok(())
+}
+
+/// CPace alternative to `/exchange`, for clients registered with
+/// `/cpace/setup`.
+async fn handle_cpace_exchange<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<CpaceExchangeRequestEncoded>,
+) -> Result<Json<CpaceExchangeResponseEncoded>, StatusCode> {
+    let registration = match state.sessions.registration(&request.id) {
+        Ok(r) => Some(r),
+        Err(StoreError::NotFound) => None,
+        Err(error) => {
+            error!(%error, "/cpace/exchange failed to look up session");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    // other ids get a handshake with a fake registration, which fails at
+    // /cpace/verify like one with a wrong password
+    let fake;
+    let (prs, registered) = match registration.as_deref() {
+        Some(Registration::Cpace { prs, .. }) => (prs, true),
+        _ => {
+            info!(id = %request.id, "/cpace/exchange client is not registered with CPace");
+            fake = state.fakes.cpace_prs(&request.id);
+            (&fake, false)
+        }
+    };
+
+    let request = match request.decode() {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/cpace/exchange failed to decode request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let ci = cpace::channel_identifier(&state.context, &request.id, &state.id);
+    let mut rng = match state.rng.lock() {
+        Ok(r) => r,
+        _ => {
+            error!("/cpace/exchange failed to lock rng");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let (handshake, message) = CpaceHandshake::start(
+        &mut *rng,
+        cpace::Role::Responder,
+        prs,
+        &ci,
+        &request.sid,
+        b"",
+    );
+    drop(rng);
+    let client_message = CpaceMessage {
+        y: request.y,
+        ad: request.ad,
+    };
+    let (handshake, confirmation) = match handshake.finish(client_message) {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, id = %request.id, "/cpace/exchange handshake aborted");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    info!(id = %request.id, "/cpace/exchange completed");
+
+    // store the handshake in client session until the client confirms
+    let stored = match registered {
+        true => state.sessions.pending(&request.id, |pending| {
+            pending.cpace = Some(handshake);
+        }),
+        false => state.unknown_cpace.insert(request.id.clone(), handshake),
+    };
+    if let Err(error) = stored {
+        error!(%error, "/cpace/exchange failed to store handshake");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(Json(
+        CpaceExchangeResponse::new(message.y, message.ad, confirmation).encode(),
+    ))
+}
+
+async fn handle_cpace_verify<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<CpaceVerifyRequestEncoded>,
+) -> Result<(), StatusCode> {
+    let handshake = match state
+        .sessions
+        .pending(&request.idc, |pending| pending.cpace.take())
+    {
+        Ok(Some(h)) => Ok(Some(h)),
+        Ok(None) | Err(StoreError::NotFound) => state.unknown_cpace.take(&request.idc),
+        Err(error) => Err(error),
+    };
+    let handshake = match handshake {
+        Ok(Some(h)) => h,
+        Ok(None) => {
+            info!(id = %request.idc, "/cpace/verify no handshake pending for client");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(error) => {
+            error!(%error, "/cpace/verify failed to take handshake");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let request = request.decode().map_err(|error| {
+        info!(%error, "/cpace/verify failed to decode request");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    if handshake.finish(&request.confirmation).is_err() {
+        info!(id = %request.idc, "/cpace/verify verification failed!");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    info!(id = %request.idc, "/cpace/verify verification succeeded");
+    Ok(())
+}
+
+/// SRP-6a alternative to `/setup` for legacy clients, registering a verifier
+/// under the same session store.
+async fn handle_srp_setup<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<SrpSetupRequestEncoded>,
+) -> Result<(), StatusCode> {
+    let request = match request.decode() {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/srp/setup failed to decode request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let id = request.id;
+    let group = request.group;
+    let registration = Registration::Srp {
+        group: request.group,
+        salt: request.salt,
+        verifier: request.verifier,
+    };
+    match state.sessions.register(id.clone(), registration) {
+        Ok(()) => {}
+        Err(StoreError::AlreadyRegistered) => {
+            error!(%id, "/srp/setup client id is already setup");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(error) => {
+            error!(%error, "/srp/setup failed to store registration");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    info!(%id, %group, "/srp/setup completed");
+    Ok(())
+}
+
+/// Starts an SRP-6a exchange, answering with the group, the salt and B.
+async fn handle_srp_exchange<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<SrpExchangeRequestEncoded>,
+) -> Result<Json<SrpExchangeResponseEncoded>, StatusCode> {
+    let registration = match state.sessions.registration(&request.id) {
+        Ok(r) => Some(r),
+        Err(StoreError::NotFound) => None,
+        Err(error) => {
+            error!(%error, "/srp/exchange failed to look up session");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    // other ids get the salt and B of a fake verifier in the default group,
+    // and fail at /srp/verify like a wrong password. Only registrations in
+    // the default group are hidden that way, the others answer with their
+    // own group
+    let fake;
+    let (group, salt, verifier, registered) = match registration.as_deref() {
+        Some(Registration::Srp {
+            group,
+            salt,
+            verifier,
+        }) => (*group, salt, verifier, true),
+        _ => {
+            info!(id = %request.id, "/srp/exchange client is not registered with SRP");
+            let group = SrpGroup::default();
+            // the verifier costs a modular exponentiation, so it is kept for
+            // the next exchanges of the id
+            let verifier = match state.fake_srp_verifiers.get(&request.id) {
+                Ok(Some(verifier)) => verifier,
+                Ok(None) => {
+                    let verifier = state.fakes.srp_verifier(group, &request.id);
+                    let stored = state
+                        .fake_srp_verifiers
+                        .insert(request.id.clone(), verifier.clone());
+                    if let Err(error) = stored {
+                        error!(%error, "/srp/exchange failed to store fake verifier");
+                        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                    }
+                    verifier
+                }
+                Err(error) => {
+                    error!(%error, "/srp/exchange failed to look up fake verifier");
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            };
+            fake = (state.fakes.srp_salt(&request.id), verifier);
+            (group, &fake.0, &fake.1, false)
+        }
+    };
+
+    let mut rng = match state.rng.lock() {
+        Ok(r) => r,
+        _ => {
+            error!("/srp/exchange failed to lock rng");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let (handshake, b) = match SrpServer::start(&mut *rng, group, verifier) {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, id = %request.id, "/srp/exchange stored verifier is invalid");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    drop(rng);
+    info!(id = %request.id, group = %group, "/srp/exchange completed");
+
+    let response = SrpExchangeResponse::new(group, salt.clone(), b);
+    // store the handshake in client session until the client proves itself
+    let stored = match registered {
+        true => state.sessions.pending(&request.id, |pending| {
+            pending.srp = Some(handshake);
+        }),
+        false => state.unknown_srp.insert(request.id.clone(), handshake),
+    };
+    if let Err(error) = stored {
+        error!(%error, "/srp/exchange failed to store handshake");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(Json(response.encode()))
+}
+
+/// Checks the client's A and M1, answering with M2.
+async fn handle_srp_verify<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<SrpVerifyRequestEncoded>,
+) -> Result<Json<SrpVerifyResponseEncoded>, StatusCode> {
+    // a handshake can only be verified once, a failed attempt discards it
+    let (handshake, registered) = match state
+        .sessions
+        .pending(&request.id, |pending| pending.srp.take())
+    {
+        Ok(Some(h)) => (Ok(Some(h)), true),
+        Ok(None) | Err(StoreError::NotFound) => (state.unknown_srp.take(&request.id), false),
+        Err(error) => (Err(error), true),
+    };
+    let handshake = match handshake {
+        Ok(Some(h)) => h,
+        Ok(None) => {
+            info!(id = %request.id, "/srp/verify no handshake pending for client");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(error) => {
+            error!(%error, "/srp/verify failed to take handshake");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    // registrations never change, so the one the handshake started with is
+    // still there, and the fake salt is derived again
+    let salt = match registered {
+        true => match state.sessions.registration(&request.id).as_deref() {
+            Ok(Registration::Srp { salt, .. }) => salt.clone(),
+            Ok(_) => {
+                info!(id = %request.id, "/srp/verify client is not registered with SRP");
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            Err(error) => {
+                error!(%error, "/srp/verify failed to look up session");
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+        false => state.fakes.srp_salt(&request.id),
+    };
+    let request = request.decode().map_err(|error| {
+        info!(%error, "/srp/verify failed to decode request");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    let message = SrpClientMessage {
+        a: request.a,
+        m1: request.m1,
+    };
+    let m2 = match handshake.finish(&request.id, &salt, &message) {
+        Ok((_, m2)) => m2,
+        Err(error) => {
+            info!(%error, id = %request.id, "/srp/verify verification failed!");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    };
+    info!(id = %request.id, "/srp/verify verification succeeded");
+    Ok(Json(SrpVerifyResponse::new(m2).encode()))
+}
+
+/// OPAQUE alternative to `/setup`. Evaluates the client's blinded password;
+/// nothing is stored until `/opaque/register/finish`.
+async fn handle_opaque_register_start<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<OpaqueRegisterStartRequestEncoded>,
+) -> Result<Json<OpaqueRegisterStartResponseEncoded>, StatusCode> {
+    let request = match request.decode() {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/opaque/register/start failed to decode request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let registered = match state.opaque_accounts.lock() {
+        Ok(accounts) => accounts.contains_key(&request.id),
+        _ => {
+            error!("/opaque/register/start failed to lock accounts");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if registered {
+        error!(id = %request.id, "/opaque/register/start client id is already registered");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let response = ServerRegistration::respond(
+        &state.opaque_setup,
+        &request.id,
+        RegistrationRequest {
+            blinded: request.blinded,
+        },
+    );
+    info!(id = %request.id, "/opaque/register/start completed");
+
+    Ok(Json(
+        OpaqueRegisterStartResponse::new(response.evaluated, response.server_public_key).encode(),
+    ))
+}
+
+async fn handle_opaque_register_finish<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<OpaqueRegisterFinishRequestEncoded>,
+) -> Result<(), StatusCode> {
+    let request = match request.decode() {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/opaque/register/finish failed to decode request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let kdf = request.kdf;
+    if !kdf.meets_minimum() {
+        error!(id = %request.id, %kdf, "/opaque/register/finish password KDF is below the minimum cost");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut accounts = match state.opaque_accounts.lock() {
+        Ok(a) => a,
+        _ => {
+            error!("/opaque/register/finish failed to lock accounts");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if accounts.contains_key(&request.id) {
+        error!(id = %request.id, "/opaque/register/finish client id is already registered");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    info!(
+        id = %request.id,
+        client_public_key = %hex::encode(Ristretto255::encode_element(&request.client_public_key)),
+        %kdf,
+        "/opaque/register/finish completed"
+    );
+    let record = RegistrationRecord {
+        client_public_key: request.client_public_key,
+        masking_key: request.masking_key,
+        envelope: request.envelope,
+    };
+    accounts.insert(
+        request.id,
+        OpaqueAccount {
+            record,
+            kdf,
+            login: None,
+        },
+    );
+    Ok(())
+}
+
+async fn handle_opaque_login_start<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<OpaqueLoginStartRequestEncoded>,
+) -> Result<Json<OpaqueLoginStartResponseEncoded>, StatusCode> {
+    let request = match request.decode() {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/opaque/login/start failed to decode request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    // unknown clients get a response from a fake record and the default
+    // KDF, which the client cannot tell apart from a wrong password. A client
+    // registered with another KDF can be told apart from a missing one
+    let (record, kdf) = match state.opaque_accounts.lock() {
+        Ok(accounts) => match accounts.get(&request.id) {
+            Some(account) => (Some(account.record.clone()), account.kdf),
+            None => (None, PasswordKdf::default()),
+        },
+        _ => {
+            error!("/opaque/login/start failed to lock accounts");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let mut rng = match state.rng.lock() {
+        Ok(r) => r,
+        _ => {
+            error!("/opaque/login/start failed to lock rng");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let respond = ServerLogin::respond(
+        &mut *rng,
+        &state.opaque_setup,
+        record.as_ref(),
+        &state.context,
+        &request.id,
+        &state.id,
+        LoginRequest {
+            blinded: request.blinded,
+            client_nonce: request.client_nonce,
+            client_keyshare: request.client_keyshare,
+        },
+    );
+    drop(rng);
+    let (login, response) = match respond {
+        Ok(r) => r,
+        Err(error) => {
+            error!(%error, "/opaque/login/start failed to respond");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    info!(id = %request.id, "/opaque/login/start completed");
+
+    // store the login until the client finishes it
+    let stored = match record {
+        Some(_) => match state.opaque_accounts.lock() {
+            Ok(mut accounts) => {
+                if let Some(account) = accounts.get_mut(&request.id) {
+                    account.login = Some(login);
+                }
+                Ok(())
+            }
+            _ => {
+                error!("/opaque/login/start failed to lock accounts");
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+        None => state.unknown_logins.insert(request.id.clone(), login),
+    };
+    if let Err(error) = stored {
+        error!(%error, "/opaque/login/start failed to store login");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(Json(
+        OpaqueLoginStartResponse::new(
+            response.evaluated,
+            response.masking_nonce,
+            response.masked_response,
+            response.server_nonce,
+            response.server_keyshare,
+            response.server_mac,
+            kdf,
+        )
+        .encode(),
+    ))
+}
+
+async fn handle_opaque_login_finish<S: Ciphersuite>(
+    State(state): State<AppState<S>>,
+    Json(request): Json<OpaqueLoginFinishRequestEncoded>,
+) -> Result<(), StatusCode> {
+    let mut accounts = match state.opaque_accounts.lock() {
+        Ok(a) => a,
+        _ => {
+            error!("/opaque/login/finish failed to lock accounts");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let login = match accounts.get_mut(&request.id) {
+        Some(account) => account.login.take(),
+        None => match state.unknown_logins.take(&request.id) {
+            Ok(login) => login,
+            Err(error) => {
+                error!(%error, "/opaque/login/finish failed to take login");
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+    };
+    let login = login.ok_or_else(|| {
+        info!(id = %request.id, "/opaque/login/finish no login pending for client");
+        StatusCode::BAD_REQUEST
+    })?;
+    let request = request.decode().map_err(|error| {
+        info!(%error, "/opaque/login/finish failed to decode request");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    let finalization = LoginFinalization {
+        client_mac: request.client_mac,
+    };
+    if login.finish(&finalization).is_err() {
+        info!(id = %request.id, "/opaque/login/finish verification failed!");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    info!(id = %request.id, "/opaque/login/finish verification succeeded");
+    Ok(())
+}
@@ -0,0 +1,349 @@
+//! The server's session store, shared by the handlers of every request, and
+//! a bounded cache for the state of ids that are not registered.
+//!
+//! Registrations never change once stored, so handlers get them as an `Arc`
+//! and do their decoding, cryptography and logging without holding the lock,
+//! which is only taken to look up, insert, store or take a value.
+//!
+//! Built with `--cfg rusty_pake_loom`, the store uses loom's primitives so
+//! that its tests check every interleaving of the racing threads, and loom
+//! fails them on a deadlock. The cfg is not plain `loom`, which would also
+//! switch tokio to its own loom build:
+//! ```shell
+//! RUSTFLAGS="--cfg rusty_pake_loom" cargo test --release --lib server::store
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+
+#[cfg(rusty_pake_loom)]
+use loom::sync::{Arc, Mutex};
+#[cfg(not(rusty_pake_loom))]
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub(crate) enum StoreError {
+    #[error("client id is already registered")]
+    AlreadyRegistered,
+
+    #[error("client session not found")]
+    NotFound,
+
+    #[error("session store lock is poisoned")]
+    Poisoned,
+}
+
+struct Session<R, P> {
+    registration: Arc<R>,
+    pending: P,
+}
+
+/// Clients by id, each with its registration `R` and the state `P` of the
+/// handshakes it has started but not yet confirmed.
+pub(crate) struct SessionStore<R, P> {
+    sessions: Mutex<HashMap<String, Session<R, P>>>,
+}
+
+impl<R, P: Default> Default for SessionStore<R, P> {
+    fn default() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<R, P: Default> SessionStore<R, P> {
+    /// Registers a client with nothing pending, unless its id is taken.
+    pub(crate) fn register(&self, id: String, registration: R) -> Result<(), StoreError> {
+        let mut sessions = self.sessions.lock().map_err(|_| StoreError::Poisoned)?;
+        if sessions.contains_key(&id) {
+            return Err(StoreError::AlreadyRegistered);
+        }
+        sessions.insert(
+            id,
+            Session {
+                registration: Arc::new(registration),
+                pending: P::default(),
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn registration(&self, id: &str) -> Result<Arc<R>, StoreError> {
+        let sessions = self.sessions.lock().map_err(|_| StoreError::Poisoned)?;
+        sessions
+            .get(id)
+            .map(|session| session.registration.clone())
+            .ok_or(StoreError::NotFound)
+    }
+
+    /// Runs `f` on the pending state of a client under the lock, to store or
+    /// take a handshake. `f` should do nothing else.
+    pub(crate) fn pending<T>(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut P) -> T,
+    ) -> Result<T, StoreError> {
+        let mut sessions = self.sessions.lock().map_err(|_| StoreError::Poisoned)?;
+        let session = sessions.get_mut(id).ok_or(StoreError::NotFound)?;
+        Ok(f(&mut session.pending))
+    }
+}
+
+struct CacheEntries<P> {
+    /// Ids from the oldest to the latest.
+    order: VecDeque<String>,
+    pending: HashMap<String, P>,
+}
+
+/// State `P` of ids that are not registered, such as the logins answered
+/// with a fake record. Anyone can start one, so only the latest `capacity`
+/// ids are kept and the oldest is evicted to make room.
+pub(crate) struct PendingCache<P> {
+    capacity: usize,
+    entries: Mutex<CacheEntries<P>>,
+}
+
+impl<P> PendingCache<P> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(CacheEntries {
+                order: VecDeque::new(),
+                pending: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Stores `pending` for `id`, replacing any earlier one.
+    pub(crate) fn insert(&self, id: String, pending: P) -> Result<(), StoreError> {
+        let mut entries = self.entries.lock().map_err(|_| StoreError::Poisoned)?;
+        if entries.pending.insert(id.clone(), pending).is_none() {
+            entries.order.push_back(id);
+        }
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.pending.remove(&oldest);
+            }
+        }
+        Ok(())
+    }
+
+    /// A copy of the value stored for `id`, which stays stored.
+    pub(crate) fn get(&self, id: &str) -> Result<Option<P>, StoreError>
+    where
+        P: Clone,
+    {
+        let entries = self.entries.lock().map_err(|_| StoreError::Poisoned)?;
+        Ok(entries.pending.get(id).cloned())
+    }
+
+    pub(crate) fn take(&self, id: &str) -> Result<Option<P>, StoreError> {
+        let mut entries = self.entries.lock().map_err(|_| StoreError::Poisoned)?;
+        let pending = entries.pending.remove(id);
+        if pending.is_some() {
+            entries.order.retain(|other| other != id);
+        }
+        Ok(pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(rusty_pake_loom)]
+    use loom::{model, thread};
+    #[cfg(not(rusty_pake_loom))]
+    use std::thread;
+
+    /// Runs `f` once, or in every interleaving when built for loom.
+    #[cfg(not(rusty_pake_loom))]
+    fn model(f: impl Fn()) {
+        f()
+    }
+
+    /// Registrations are numbers, and so are the handshakes, of which a
+    /// client has at most one pending.
+    type Store = SessionStore<u32, Option<u32>>;
+
+    fn store() -> Arc<Store> {
+        Arc::new(Store::default())
+    }
+
+    /// The handlers of `/exchange` and `/verify`, without the cryptography.
+    fn exchange(store: &Store, id: &str, handshake: u32) -> Result<(), StoreError> {
+        store.registration(id)?;
+        store.pending(id, |pending| *pending = Some(handshake))
+    }
+
+    fn verify(store: &Store, id: &str) -> Result<Option<u32>, StoreError> {
+        store.pending(id, Option::take)
+    }
+
+    #[test]
+    fn simultaneous_setups_register_one() {
+        model(|| {
+            let store = store();
+            let results = [1, 2]
+                .map(|registration| {
+                    let store = store.clone();
+                    thread::spawn(move || store.register("alice".into(), registration))
+                })
+                .map(|thread| thread.join().unwrap());
+
+            let winners: Vec<_> = [1, 2]
+                .into_iter()
+                .zip(&results)
+                .filter(|(_, result)| result.is_ok())
+                .map(|(registration, _)| registration)
+                .collect();
+            assert_eq!(winners.len(), 1, "{:?}", results);
+            assert!(results.contains(&Err(StoreError::AlreadyRegistered)));
+            assert_eq!(*store.registration("alice").unwrap(), winners[0]);
+        });
+    }
+
+    #[test]
+    fn simultaneous_setups_of_different_ids_are_kept() {
+        model(|| {
+            let store = store();
+            let other = {
+                let store = store.clone();
+                thread::spawn(move || store.register("bob".into(), 2))
+            };
+            store.register("alice".into(), 1).unwrap();
+            other.join().unwrap().unwrap();
+
+            assert_eq!(*store.registration("alice").unwrap(), 1);
+            assert_eq!(*store.registration("bob").unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn exchange_racing_setup_stores_its_handshake() {
+        model(|| {
+            let store = store();
+            let setup = {
+                let store = store.clone();
+                thread::spawn(move || store.register("alice".into(), 1))
+            };
+            let exchanged = exchange(&store, "alice", 7);
+            setup.join().unwrap().unwrap();
+
+            // the exchange either came too early or its handshake is pending
+            match exchanged {
+                Ok(()) => assert_eq!(verify(&store, "alice"), Ok(Some(7))),
+                Err(error) => {
+                    assert_eq!(error, StoreError::NotFound);
+                    assert_eq!(verify(&store, "alice"), Ok(None));
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn exchange_racing_verify_loses_no_handshake() {
+        model(|| {
+            let store = store();
+            store.register("alice".into(), 1).unwrap();
+            let exchanging = {
+                let store = store.clone();
+                thread::spawn(move || exchange(&store, "alice", 7))
+            };
+            let verified = verify(&store, "alice").unwrap();
+            exchanging.join().unwrap().unwrap();
+
+            // the verify took the handshake, or it is still pending
+            let pending = verify(&store, "alice").unwrap();
+            assert_eq!(verified.xor(pending), Some(7));
+        });
+    }
+
+    #[test]
+    fn racing_exchanges_and_verify_take_each_handshake_at_most_once() {
+        model(|| {
+            let store = store();
+            store.register("alice".into(), 1).unwrap();
+            let exchanging = [7, 8].map(|handshake| {
+                let store = store.clone();
+                thread::spawn(move || exchange(&store, "alice", handshake))
+            });
+            let verified = verify(&store, "alice").unwrap();
+            for thread in exchanging {
+                thread.join().unwrap().unwrap();
+            }
+
+            // a later exchange replaces an earlier one, but the last one is
+            // either verified or still pending
+            let pending = verify(&store, "alice").unwrap();
+            assert!(pending.is_some() || verified.is_some());
+            assert_ne!(pending, verified);
+            for handshake in [pending, verified].into_iter().flatten() {
+                assert!([7, 8].contains(&handshake));
+            }
+        });
+    }
+
+    #[test]
+    fn cache_evicts_the_oldest_id() {
+        model(|| {
+            let cache = PendingCache::new(2);
+            cache.insert("alice".into(), 1).unwrap();
+            cache.insert("bob".into(), 2).unwrap();
+            // replacing a pending value keeps its place
+            cache.insert("alice".into(), 3).unwrap();
+            cache.insert("carol".into(), 4).unwrap();
+
+            assert_eq!(cache.take("alice"), Ok(None));
+            assert_eq!(cache.take("bob"), Ok(Some(2)));
+            assert_eq!(cache.take("bob"), Ok(None));
+            assert_eq!(cache.take("carol"), Ok(Some(4)));
+        });
+    }
+
+    #[test]
+    fn taken_ids_make_room() {
+        model(|| {
+            let cache = PendingCache::new(2);
+            cache.insert("alice".into(), 1).unwrap();
+            cache.insert("bob".into(), 2).unwrap();
+            assert_eq!(cache.take("alice"), Ok(Some(1)));
+            cache.insert("carol".into(), 3).unwrap();
+
+            assert_eq!(cache.take("bob"), Ok(Some(2)));
+            assert_eq!(cache.take("carol"), Ok(Some(3)));
+        });
+    }
+
+    #[test]
+    fn get_keeps_the_value() {
+        model(|| {
+            let cache = PendingCache::new(1);
+            assert_eq!(cache.get("alice"), Ok(None));
+            cache.insert("alice".into(), 1).unwrap();
+
+            assert_eq!(cache.get("alice"), Ok(Some(1)));
+            assert_eq!(cache.take("alice"), Ok(Some(1)));
+            assert_eq!(cache.get("alice"), Ok(None));
+        });
+    }
+
+    #[test]
+    fn racing_inserts_stay_within_capacity() {
+        model(|| {
+            let cache = Arc::new(PendingCache::new(1));
+            let other = {
+                let cache = cache.clone();
+                thread::spawn(move || cache.insert("bob".into(), 2))
+            };
+            cache.insert("alice".into(), 1).unwrap();
+            other.join().unwrap().unwrap();
+
+            // whichever came last is kept
+            let kept = [cache.take("alice").unwrap(), cache.take("bob").unwrap()];
+            assert_eq!(kept.iter().flatten().count(), 1, "{:?}", kept);
+        });
+    }
+}
@@ -1,18 +1,99 @@
-use curve25519_dalek::{RistrettoPoint, Scalar, ristretto::CompressedRistretto};
+use curve25519_dalek::RistrettoPoint;
+use group::Group;
 use hex::FromHexError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    ciphersuite::{self, Ciphersuite, Output, Ristretto255},
+    cpace::{CONFIRMATION_LEN, PRS_LEN, SID_LEN},
+    opaque,
+    spake2plus::{
+        kdf::{KdfError, PasswordKdf},
+        rfc9383::SALT_LEN,
+        secret::SecretScalar,
+    },
+    srp::{self, SrpError, SrpGroup},
+};
+
+/// Default context string bound into the SPAKE2+ transcript by both client and
+/// server.
+pub const CONTEXT: &[u8] = b"rusty-pake SPAKE2+";
 
 #[derive(Debug, Error)]
 pub enum DecodeError {
     #[error("invalid hex encoding: {0}")]
     InvalidHex(#[from] FromHexError),
 
+    #[error("invalid hex encoding of a secret")]
+    InvalidSecretHex,
+
     #[error("invalid byte length: {0}")]
     InvalidLength(String),
 
-    #[error("invalid Ristretto point")]
+    #[error("invalid group element")]
     InvalidPoint,
+
+    #[error("group element is the identity")]
+    IdentityElement,
+
+    #[error("invalid scalar")]
+    InvalidScalar,
+
+    #[error(transparent)]
+    InvalidKdf(#[from] KdfError),
+
+    #[error(transparent)]
+    InvalidSrpGroup(#[from] SrpError),
+}
+
+/// Decodes a group element received from the peer. All supported groups have
+/// prime order, so the identity is the only low-order element to reject.
+fn decode_element<S: Ciphersuite>(element: &str) -> Result<S::Group, DecodeError> {
+    let element = S::decode_element(&hex::decode(element)?).ok_or(DecodeError::InvalidPoint)?;
+    if bool::from(element.is_identity()) {
+        return Err(DecodeError::IdentityElement);
+    }
+    Ok(element)
+}
+
+/// Decodes the hex encoding of a secret in constant time. `hex::decode`
+/// branches on the range of every digit, and so leaks timing.
+fn decode_secret_hex(secret: &str) -> Result<Zeroizing<Vec<u8>>, DecodeError> {
+    let mut bytes = Zeroizing::new(vec![0; secret.len() / 2]);
+    base16ct::mixed::decode(secret, &mut bytes).map_err(|_| DecodeError::InvalidSecretHex)?;
+    Ok(bytes)
+}
+
+fn decode_secret_scalar<S: Ciphersuite>(scalar: &str) -> Result<SecretScalar<S>, DecodeError> {
+    S::decode_scalar(&decode_secret_hex(scalar)?)
+        .map(SecretScalar::new)
+        .ok_or(DecodeError::InvalidScalar)
+}
+
+fn decode_output<S: Ciphersuite>(name: &str, output: &str) -> Result<Output<S>, DecodeError> {
+    ciphersuite::decode_output::<S>(&hex::decode(output)?)
+        .ok_or_else(|| DecodeError::InvalidLength(name.into()))
+}
+
+fn decode_array<const N: usize>(name: &str, bytes: &str) -> Result<[u8; N], DecodeError> {
+    hex::decode(bytes)?
+        .try_into()
+        .map_err(|_| DecodeError::InvalidLength(name.into()))
+}
+
+/// Checks the length of an OPAQUE client id, which the protocol encodes in
+/// two bytes.
+fn decode_opaque_id(id: String) -> Result<String, DecodeError> {
+    if id.len() > opaque::MAX_IDENTITY_LEN {
+        return Err(DecodeError::InvalidLength("id".into()));
+    }
+    Ok(id)
+}
+
+fn decode_salt(salt: &str) -> Result<[u8; SALT_LEN], DecodeError> {
+    decode_array("salt", salt)
 }
 
 #[derive(Serialize, Deserialize)]
@@ -20,50 +101,105 @@ pub struct SetupRequestEncoded {
     pub id: String,
     pub phi0: String,
     pub c: String,
+    pub salt: String,
+    pub kdf: String,
 }
 
-pub struct SetupRequest {
+pub struct SetupRequest<S: Ciphersuite> {
     pub id: String,
-    pub phi0: Scalar,
-    pub c: RistrettoPoint,
+    pub phi0: SecretScalar<S>,
+    pub c: S::Group,
+    pub salt: [u8; SALT_LEN],
+    pub kdf: PasswordKdf,
 }
 
 impl SetupRequestEncoded {
-    pub fn decode(self) -> Result<SetupRequest, DecodeError> {
-        let phi0_bytes = hex::decode(&self.phi0)?;
-        let c_bytes = hex::decode(&self.c)?;
-
-        let phi0_bytes: [u8; 32] = phi0_bytes
-            .try_into()
-            .map_err(|_| DecodeError::InvalidLength("phi0".into()))?;
-
-        let phi0 = Scalar::from_bytes_mod_order(phi0_bytes);
-        let c = match CompressedRistretto::from_slice(&c_bytes) {
-            Ok(compressed) => match compressed.decompress() {
-                Some(c) => c,
-                None => return Err(DecodeError::InvalidPoint),
-            },
-            _ => return Err(DecodeError::InvalidLength("c".into())),
-        };
+    pub fn decode<S: Ciphersuite>(mut self) -> Result<SetupRequest<S>, DecodeError> {
+        let phi0 = decode_secret_scalar::<S>(&self.phi0);
+        self.phi0.zeroize();
+        let phi0 = phi0?;
+        let c = decode_element::<S>(&self.c)?;
+        let salt = decode_salt(&self.salt)?;
+        let kdf = self.kdf.parse()?;
 
         Ok(SetupRequest {
             id: self.id,
             phi0,
             c,
+            salt,
+            kdf,
         })
     }
 }
 
-impl SetupRequest {
-    pub fn new(id: String, phi0: Scalar, c: RistrettoPoint) -> Self {
-        Self { id, phi0, c }
+impl<S: Ciphersuite> SetupRequest<S> {
+    pub fn new(
+        id: String,
+        phi0: SecretScalar<S>,
+        c: S::Group,
+        salt: [u8; SALT_LEN],
+        kdf: PasswordKdf,
+    ) -> Self {
+        Self {
+            id,
+            phi0,
+            c,
+            salt,
+            kdf,
+        }
     }
 
     pub fn encode(self) -> SetupRequestEncoded {
         SetupRequestEncoded {
             id: self.id,
-            phi0: hex::encode(self.phi0.to_bytes()),
-            c: hex::encode(self.c.compress().to_bytes()),
+            phi0: hex::encode(Zeroizing::new(S::encode_scalar(self.phi0.expose_secret()))),
+            c: hex::encode(S::encode_element(&self.c)),
+            salt: hex::encode(self.salt),
+            kdf: self.kdf.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PrepareRequestEncoded {
+    pub id: String,
+}
+
+impl PrepareRequestEncoded {
+    pub fn new(id: String) -> Self {
+        Self { id }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PrepareResponseEncoded {
+    pub salt: String,
+    pub kdf: String,
+}
+
+pub struct PrepareResponse {
+    pub salt: [u8; SALT_LEN],
+    pub kdf: PasswordKdf,
+}
+
+impl PrepareResponseEncoded {
+    pub fn decode(self) -> Result<PrepareResponse, DecodeError> {
+        Ok(PrepareResponse {
+            salt: decode_salt(&self.salt)?,
+            kdf: self.kdf.parse()?,
+        })
+    }
+}
+
+impl PrepareResponse {
+    pub fn new(salt: [u8; SALT_LEN], kdf: PasswordKdf) -> Self {
+        Self { salt, kdf }
+    }
+
+    pub fn encode(self) -> PrepareResponseEncoded {
+        PrepareResponseEncoded {
+            salt: hex::encode(self.salt),
+            kdf: self.kdf.to_string(),
         }
     }
 }
@@ -74,36 +210,27 @@ pub struct ExchangeRequestEncoded {
     pub u: String,
 }
 
-pub struct ExchangeRequest {
+pub struct ExchangeRequest<S: Ciphersuite> {
     pub id: String,
-    pub u: RistrettoPoint,
+    pub u: S::Group,
 }
 
 impl ExchangeRequestEncoded {
-    pub fn decode(self) -> Result<ExchangeRequest, DecodeError> {
-        let u_bytes = hex::decode(&self.u)?;
-
-        let u = match CompressedRistretto::from_slice(&u_bytes) {
-            Ok(c) => match c.decompress() {
-                Some(u) => u,
-                None => return Err(DecodeError::InvalidPoint),
-            },
-            Err(_) => return Err(DecodeError::InvalidLength("u".into())),
-        };
-
+    pub fn decode<S: Ciphersuite>(self) -> Result<ExchangeRequest<S>, DecodeError> {
+        let u = decode_element::<S>(&self.u)?;
         Ok(ExchangeRequest { id: self.id, u })
     }
 }
 
-impl ExchangeRequest {
-    pub fn new(id: String, u: RistrettoPoint) -> Self {
+impl<S: Ciphersuite> ExchangeRequest<S> {
+    pub fn new(id: String, u: S::Group) -> Self {
         Self { id, u }
     }
 
     pub fn encode(self) -> ExchangeRequestEncoded {
         ExchangeRequestEncoded {
             id: self.id,
-            u: hex::encode(self.u.compress().to_bytes()),
+            u: hex::encode(S::encode_element(&self.u)),
         }
     }
 }
@@ -111,36 +238,31 @@ impl ExchangeRequest {
 #[derive(Serialize, Deserialize)]
 pub struct ExchangeResponseEncoded {
     pub v: String,
+    pub confirm_v: String,
 }
 
-pub struct ExchangeResponse {
-    pub v: RistrettoPoint,
+pub struct ExchangeResponse<S: Ciphersuite> {
+    pub v: S::Group,
+    pub confirm_v: Output<S>,
 }
 
 impl ExchangeResponseEncoded {
-    pub fn decode(self) -> Result<ExchangeResponse, DecodeError> {
-        let v_bytes = hex::decode(&self.v)?;
-
-        let v = match CompressedRistretto::from_slice(&v_bytes) {
-            Ok(c) => match c.decompress() {
-                Some(u) => u,
-                None => return Err(DecodeError::InvalidPoint),
-            },
-            Err(_) => return Err(DecodeError::InvalidLength("u".into())),
-        };
-
-        Ok(ExchangeResponse { v })
+    pub fn decode<S: Ciphersuite>(self) -> Result<ExchangeResponse<S>, DecodeError> {
+        let v = decode_element::<S>(&self.v)?;
+        let confirm_v = decode_output::<S>("confirm_v", &self.confirm_v)?;
+        Ok(ExchangeResponse { v, confirm_v })
     }
 }
 
-impl ExchangeResponse {
-    pub fn new(v: RistrettoPoint) -> Self {
-        Self { v }
+impl<S: Ciphersuite> ExchangeResponse<S> {
+    pub fn new(v: S::Group, confirm_v: Output<S>) -> Self {
+        Self { v, confirm_v }
     }
 
     pub fn encode(self) -> ExchangeResponseEncoded {
         ExchangeResponseEncoded {
-            v: hex::encode(self.v.compress().to_bytes()),
+            v: hex::encode(S::encode_element(&self.v)),
+            confirm_v: hex::encode(self.confirm_v),
         }
     }
 }
@@ -148,25 +270,1053 @@ impl ExchangeResponse {
 #[derive(Serialize, Deserialize)]
 pub struct VerifyRequestEncoded {
     pub idc: String,
-    pub key: String,
+    pub confirm_p: String,
 }
 
-pub struct VerifyRequest {
+pub struct VerifyRequest<S: Ciphersuite> {
     pub idc: String,
-    pub key: [u8; 32],
+    pub confirm_p: Output<S>,
 }
 
 impl VerifyRequestEncoded {
-    // Assumes key is a valid hex string representing [u8; 32]
-    pub fn new(idc: String, key: String) -> Self {
-        Self { idc, key }
+    // Assumes confirm_p is a valid hex string representing a MAC of the suite
+    pub fn new(idc: String, confirm_p: String) -> Self {
+        Self { idc, confirm_p }
+    }
+
+    pub fn decode<S: Ciphersuite>(self) -> Result<VerifyRequest<S>, DecodeError> {
+        let confirm_p = decode_output::<S>("confirm_p", &self.confirm_p)?;
+        Ok(VerifyRequest {
+            idc: self.idc,
+            confirm_p,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CpaceSetupRequestEncoded {
+    pub id: String,
+    pub prs: String,
+    pub salt: String,
+    pub kdf: String,
+}
+
+pub struct CpaceSetupRequest {
+    pub id: String,
+    pub prs: Zeroizing<Vec<u8>>,
+    pub salt: [u8; SALT_LEN],
+    pub kdf: PasswordKdf,
+}
+
+impl CpaceSetupRequestEncoded {
+    pub fn decode(mut self) -> Result<CpaceSetupRequest, DecodeError> {
+        let prs = decode_secret_hex(&self.prs);
+        self.prs.zeroize();
+        let prs = prs?;
+        if prs.len() != PRS_LEN {
+            return Err(DecodeError::InvalidLength("prs".into()));
+        }
+        Ok(CpaceSetupRequest {
+            id: self.id,
+            prs,
+            salt: decode_salt(&self.salt)?,
+            kdf: self.kdf.parse()?,
+        })
+    }
+}
+
+impl CpaceSetupRequest {
+    pub fn new(
+        id: String,
+        prs: Zeroizing<Vec<u8>>,
+        salt: [u8; SALT_LEN],
+        kdf: PasswordKdf,
+    ) -> Self {
+        Self { id, prs, salt, kdf }
+    }
+
+    pub fn encode(self) -> CpaceSetupRequestEncoded {
+        CpaceSetupRequestEncoded {
+            id: self.id,
+            prs: hex::encode(&*self.prs),
+            salt: hex::encode(self.salt),
+            kdf: self.kdf.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CpaceExchangeRequestEncoded {
+    pub id: String,
+    pub sid: String,
+    pub y: String,
+    pub ad: String,
+}
+
+pub struct CpaceExchangeRequest {
+    pub id: String,
+    pub sid: [u8; SID_LEN],
+    pub y: RistrettoPoint,
+    pub ad: Vec<u8>,
+}
+
+impl CpaceExchangeRequestEncoded {
+    pub fn decode(self) -> Result<CpaceExchangeRequest, DecodeError> {
+        Ok(CpaceExchangeRequest {
+            id: self.id,
+            sid: decode_array("sid", &self.sid)?,
+            y: decode_element::<Ristretto255>(&self.y)?,
+            ad: hex::decode(&self.ad)?,
+        })
+    }
+}
+
+impl CpaceExchangeRequest {
+    pub fn new(id: String, sid: [u8; SID_LEN], y: RistrettoPoint, ad: Vec<u8>) -> Self {
+        Self { id, sid, y, ad }
     }
 
-    pub fn decode(self) -> Result<VerifyRequest, DecodeError> {
-        let key = hex::decode(self.key)?;
-        let key = key
-            .try_into()
-            .map_err(|_| DecodeError::InvalidLength("key".into()))?;
-        Ok(VerifyRequest { idc: self.idc, key })
+    pub fn encode(self) -> CpaceExchangeRequestEncoded {
+        CpaceExchangeRequestEncoded {
+            id: self.id,
+            sid: hex::encode(self.sid),
+            y: hex::encode(Ristretto255::encode_element(&self.y)),
+            ad: hex::encode(self.ad),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CpaceExchangeResponseEncoded {
+    pub y: String,
+    pub ad: String,
+    pub confirmation: String,
+}
+
+pub struct CpaceExchangeResponse {
+    pub y: RistrettoPoint,
+    pub ad: Vec<u8>,
+    pub confirmation: [u8; CONFIRMATION_LEN],
+}
+
+impl CpaceExchangeResponseEncoded {
+    pub fn decode(self) -> Result<CpaceExchangeResponse, DecodeError> {
+        Ok(CpaceExchangeResponse {
+            y: decode_element::<Ristretto255>(&self.y)?,
+            ad: hex::decode(&self.ad)?,
+            confirmation: decode_array("confirmation", &self.confirmation)?,
+        })
+    }
+}
+
+impl CpaceExchangeResponse {
+    pub fn new(y: RistrettoPoint, ad: Vec<u8>, confirmation: [u8; CONFIRMATION_LEN]) -> Self {
+        Self {
+            y,
+            ad,
+            confirmation,
+        }
+    }
+
+    pub fn encode(self) -> CpaceExchangeResponseEncoded {
+        CpaceExchangeResponseEncoded {
+            y: hex::encode(Ristretto255::encode_element(&self.y)),
+            ad: hex::encode(self.ad),
+            confirmation: hex::encode(self.confirmation),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CpaceVerifyRequestEncoded {
+    pub idc: String,
+    pub confirmation: String,
+}
+
+pub struct CpaceVerifyRequest {
+    pub idc: String,
+    pub confirmation: [u8; CONFIRMATION_LEN],
+}
+
+impl CpaceVerifyRequestEncoded {
+    pub fn new(idc: String, confirmation: String) -> Self {
+        Self { idc, confirmation }
+    }
+
+    pub fn decode(self) -> Result<CpaceVerifyRequest, DecodeError> {
+        Ok(CpaceVerifyRequest {
+            confirmation: decode_array("confirmation", &self.confirmation)?,
+            idc: self.idc,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpaqueRegisterStartRequestEncoded {
+    pub id: String,
+    pub blinded: String,
+}
+
+pub struct OpaqueRegisterStartRequest {
+    pub id: String,
+    pub blinded: RistrettoPoint,
+}
+
+impl OpaqueRegisterStartRequestEncoded {
+    pub fn decode(self) -> Result<OpaqueRegisterStartRequest, DecodeError> {
+        Ok(OpaqueRegisterStartRequest {
+            blinded: decode_element::<Ristretto255>(&self.blinded)?,
+            id: decode_opaque_id(self.id)?,
+        })
+    }
+}
+
+impl OpaqueRegisterStartRequest {
+    pub fn new(id: String, blinded: RistrettoPoint) -> Self {
+        Self { id, blinded }
+    }
+
+    pub fn encode(self) -> OpaqueRegisterStartRequestEncoded {
+        OpaqueRegisterStartRequestEncoded {
+            id: self.id,
+            blinded: hex::encode(Ristretto255::encode_element(&self.blinded)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpaqueRegisterStartResponseEncoded {
+    pub evaluated: String,
+    pub server_public_key: String,
+}
+
+pub struct OpaqueRegisterStartResponse {
+    pub evaluated: RistrettoPoint,
+    pub server_public_key: RistrettoPoint,
+}
+
+impl OpaqueRegisterStartResponseEncoded {
+    pub fn decode(self) -> Result<OpaqueRegisterStartResponse, DecodeError> {
+        Ok(OpaqueRegisterStartResponse {
+            evaluated: decode_element::<Ristretto255>(&self.evaluated)?,
+            server_public_key: decode_element::<Ristretto255>(&self.server_public_key)?,
+        })
+    }
+}
+
+impl OpaqueRegisterStartResponse {
+    pub fn new(evaluated: RistrettoPoint, server_public_key: RistrettoPoint) -> Self {
+        Self {
+            evaluated,
+            server_public_key,
+        }
+    }
+
+    pub fn encode(self) -> OpaqueRegisterStartResponseEncoded {
+        OpaqueRegisterStartResponseEncoded {
+            evaluated: hex::encode(Ristretto255::encode_element(&self.evaluated)),
+            server_public_key: hex::encode(Ristretto255::encode_element(&self.server_public_key)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpaqueRegisterFinishRequestEncoded {
+    pub id: String,
+    pub client_public_key: String,
+    pub masking_key: String,
+    pub envelope: String,
+    pub kdf: String,
+}
+
+pub struct OpaqueRegisterFinishRequest {
+    pub id: String,
+    pub client_public_key: RistrettoPoint,
+    pub masking_key: [u8; opaque::HASH_LEN],
+    pub envelope: [u8; opaque::ENVELOPE_LEN],
+    pub kdf: PasswordKdf,
+}
+
+impl OpaqueRegisterFinishRequestEncoded {
+    pub fn decode(self) -> Result<OpaqueRegisterFinishRequest, DecodeError> {
+        Ok(OpaqueRegisterFinishRequest {
+            client_public_key: decode_element::<Ristretto255>(&self.client_public_key)?,
+            masking_key: decode_array("masking_key", &self.masking_key)?,
+            envelope: decode_array("envelope", &self.envelope)?,
+            kdf: self.kdf.parse()?,
+            id: decode_opaque_id(self.id)?,
+        })
+    }
+}
+
+impl OpaqueRegisterFinishRequest {
+    pub fn new(
+        id: String,
+        client_public_key: RistrettoPoint,
+        masking_key: [u8; opaque::HASH_LEN],
+        envelope: [u8; opaque::ENVELOPE_LEN],
+        kdf: PasswordKdf,
+    ) -> Self {
+        Self {
+            id,
+            client_public_key,
+            masking_key,
+            envelope,
+            kdf,
+        }
+    }
+
+    pub fn encode(self) -> OpaqueRegisterFinishRequestEncoded {
+        OpaqueRegisterFinishRequestEncoded {
+            id: self.id,
+            client_public_key: hex::encode(Ristretto255::encode_element(&self.client_public_key)),
+            masking_key: hex::encode(self.masking_key),
+            envelope: hex::encode(self.envelope),
+            kdf: self.kdf.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpaqueLoginStartRequestEncoded {
+    pub id: String,
+    pub blinded: String,
+    pub client_nonce: String,
+    pub client_keyshare: String,
+}
+
+pub struct OpaqueLoginStartRequest {
+    pub id: String,
+    pub blinded: RistrettoPoint,
+    pub client_nonce: [u8; opaque::NONCE_LEN],
+    pub client_keyshare: RistrettoPoint,
+}
+
+impl OpaqueLoginStartRequestEncoded {
+    pub fn decode(self) -> Result<OpaqueLoginStartRequest, DecodeError> {
+        Ok(OpaqueLoginStartRequest {
+            blinded: decode_element::<Ristretto255>(&self.blinded)?,
+            client_nonce: decode_array("client_nonce", &self.client_nonce)?,
+            client_keyshare: decode_element::<Ristretto255>(&self.client_keyshare)?,
+            id: decode_opaque_id(self.id)?,
+        })
+    }
+}
+
+impl OpaqueLoginStartRequest {
+    pub fn new(
+        id: String,
+        blinded: RistrettoPoint,
+        client_nonce: [u8; opaque::NONCE_LEN],
+        client_keyshare: RistrettoPoint,
+    ) -> Self {
+        Self {
+            id,
+            blinded,
+            client_nonce,
+            client_keyshare,
+        }
+    }
+
+    pub fn encode(self) -> OpaqueLoginStartRequestEncoded {
+        OpaqueLoginStartRequestEncoded {
+            id: self.id,
+            blinded: hex::encode(Ristretto255::encode_element(&self.blinded)),
+            client_nonce: hex::encode(self.client_nonce),
+            client_keyshare: hex::encode(Ristretto255::encode_element(&self.client_keyshare)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpaqueLoginStartResponseEncoded {
+    pub evaluated: String,
+    pub masking_nonce: String,
+    pub masked_response: String,
+    pub server_nonce: String,
+    pub server_keyshare: String,
+    pub server_mac: String,
+    pub kdf: String,
+}
+
+pub struct OpaqueLoginStartResponse {
+    pub evaluated: RistrettoPoint,
+    pub masking_nonce: [u8; opaque::NONCE_LEN],
+    pub masked_response: [u8; opaque::MASKED_RESPONSE_LEN],
+    pub server_nonce: [u8; opaque::NONCE_LEN],
+    pub server_keyshare: RistrettoPoint,
+    pub server_mac: [u8; opaque::HASH_LEN],
+    pub kdf: PasswordKdf,
+}
+
+impl OpaqueLoginStartResponseEncoded {
+    pub fn decode(self) -> Result<OpaqueLoginStartResponse, DecodeError> {
+        Ok(OpaqueLoginStartResponse {
+            evaluated: decode_element::<Ristretto255>(&self.evaluated)?,
+            masking_nonce: decode_array("masking_nonce", &self.masking_nonce)?,
+            masked_response: decode_array("masked_response", &self.masked_response)?,
+            server_nonce: decode_array("server_nonce", &self.server_nonce)?,
+            server_keyshare: decode_element::<Ristretto255>(&self.server_keyshare)?,
+            server_mac: decode_array("server_mac", &self.server_mac)?,
+            kdf: self.kdf.parse()?,
+        })
+    }
+}
+
+impl OpaqueLoginStartResponse {
+    pub fn new(
+        evaluated: RistrettoPoint,
+        masking_nonce: [u8; opaque::NONCE_LEN],
+        masked_response: [u8; opaque::MASKED_RESPONSE_LEN],
+        server_nonce: [u8; opaque::NONCE_LEN],
+        server_keyshare: RistrettoPoint,
+        server_mac: [u8; opaque::HASH_LEN],
+        kdf: PasswordKdf,
+    ) -> Self {
+        Self {
+            evaluated,
+            masking_nonce,
+            masked_response,
+            server_nonce,
+            server_keyshare,
+            server_mac,
+            kdf,
+        }
+    }
+
+    pub fn encode(self) -> OpaqueLoginStartResponseEncoded {
+        OpaqueLoginStartResponseEncoded {
+            evaluated: hex::encode(Ristretto255::encode_element(&self.evaluated)),
+            masking_nonce: hex::encode(self.masking_nonce),
+            masked_response: hex::encode(self.masked_response),
+            server_nonce: hex::encode(self.server_nonce),
+            server_keyshare: hex::encode(Ristretto255::encode_element(&self.server_keyshare)),
+            server_mac: hex::encode(self.server_mac),
+            kdf: self.kdf.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpaqueLoginFinishRequestEncoded {
+    pub id: String,
+    pub client_mac: String,
+}
+
+pub struct OpaqueLoginFinishRequest {
+    pub id: String,
+    pub client_mac: [u8; opaque::HASH_LEN],
+}
+
+impl OpaqueLoginFinishRequestEncoded {
+    pub fn new(id: String, client_mac: String) -> Self {
+        Self { id, client_mac }
+    }
+
+    pub fn decode(self) -> Result<OpaqueLoginFinishRequest, DecodeError> {
+        Ok(OpaqueLoginFinishRequest {
+            client_mac: decode_array("client_mac", &self.client_mac)?,
+            id: decode_opaque_id(self.id)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrpSetupRequestEncoded {
+    pub id: String,
+    pub group: String,
+    pub salt: String,
+    pub verifier: String,
+}
+
+pub struct SrpSetupRequest {
+    pub id: String,
+    pub group: SrpGroup,
+    pub salt: Vec<u8>,
+    pub verifier: Zeroizing<Vec<u8>>,
+}
+
+impl SrpSetupRequestEncoded {
+    pub fn decode(mut self) -> Result<SrpSetupRequest, DecodeError> {
+        let verifier = decode_secret_hex(&self.verifier);
+        self.verifier.zeroize();
+        let group: SrpGroup = self.group.parse()?;
+        let verifier = verifier?;
+        if verifier.len() > group.element_len() {
+            return Err(DecodeError::InvalidLength("verifier".into()));
+        }
+        group.check_verifier(&verifier)?;
+        Ok(SrpSetupRequest {
+            id: self.id,
+            group,
+            salt: hex::decode(&self.salt)?,
+            verifier,
+        })
+    }
+}
+
+impl SrpSetupRequest {
+    pub fn new(id: String, group: SrpGroup, salt: Vec<u8>, verifier: Zeroizing<Vec<u8>>) -> Self {
+        Self {
+            id,
+            group,
+            salt,
+            verifier,
+        }
+    }
+
+    pub fn encode(self) -> SrpSetupRequestEncoded {
+        SrpSetupRequestEncoded {
+            id: self.id,
+            group: self.group.to_string(),
+            salt: hex::encode(&self.salt),
+            verifier: hex::encode(&*self.verifier),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrpExchangeRequestEncoded {
+    pub id: String,
+}
+
+impl SrpExchangeRequestEncoded {
+    pub fn new(id: String) -> Self {
+        Self { id }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrpExchangeResponseEncoded {
+    pub group: String,
+    pub salt: String,
+    pub b: String,
+}
+
+pub struct SrpExchangeResponse {
+    pub group: SrpGroup,
+    pub salt: Vec<u8>,
+    pub b: Vec<u8>,
+}
+
+impl SrpExchangeResponseEncoded {
+    pub fn decode(self) -> Result<SrpExchangeResponse, DecodeError> {
+        Ok(SrpExchangeResponse {
+            group: self.group.parse()?,
+            salt: hex::decode(&self.salt)?,
+            b: hex::decode(&self.b)?,
+        })
+    }
+}
+
+impl SrpExchangeResponse {
+    pub fn new(group: SrpGroup, salt: Vec<u8>, b: Vec<u8>) -> Self {
+        Self { group, salt, b }
+    }
+
+    pub fn encode(self) -> SrpExchangeResponseEncoded {
+        SrpExchangeResponseEncoded {
+            group: self.group.to_string(),
+            salt: hex::encode(self.salt),
+            b: hex::encode(self.b),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrpVerifyRequestEncoded {
+    pub id: String,
+    pub a: String,
+    pub m1: String,
+}
+
+pub struct SrpVerifyRequest {
+    pub id: String,
+    pub a: Vec<u8>,
+    pub m1: [u8; srp::PROOF_LEN],
+}
+
+impl SrpVerifyRequestEncoded {
+    pub fn decode(self) -> Result<SrpVerifyRequest, DecodeError> {
+        Ok(SrpVerifyRequest {
+            a: hex::decode(&self.a)?,
+            m1: decode_array("m1", &self.m1)?,
+            id: self.id,
+        })
+    }
+}
+
+impl SrpVerifyRequest {
+    pub fn new(id: String, a: Vec<u8>, m1: [u8; srp::PROOF_LEN]) -> Self {
+        Self { id, a, m1 }
+    }
+
+    pub fn encode(self) -> SrpVerifyRequestEncoded {
+        SrpVerifyRequestEncoded {
+            id: self.id,
+            a: hex::encode(self.a),
+            m1: hex::encode(self.m1),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrpVerifyResponseEncoded {
+    pub m2: String,
+}
+
+pub struct SrpVerifyResponse {
+    pub m2: [u8; srp::PROOF_LEN],
+}
+
+impl SrpVerifyResponseEncoded {
+    pub fn decode(self) -> Result<SrpVerifyResponse, DecodeError> {
+        Ok(SrpVerifyResponse {
+            m2: decode_array("m2", &self.m2)?,
+        })
+    }
+}
+
+impl SrpVerifyResponse {
+    pub fn new(m2: [u8; srp::PROOF_LEN]) -> Self {
+        Self { m2 }
+    }
+
+    pub fn encode(self) -> SrpVerifyResponseEncoded {
+        SrpVerifyResponseEncoded {
+            m2: hex::encode(self.m2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphersuite::{random_nonzero_scalar, suite_tests};
+    use proptest::{array::uniform, collection::vec, prelude::*};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use serde::de::DeserializeOwned;
+
+    fn identity_hex<S: Ciphersuite>() -> String {
+        hex::encode(S::encode_element(&S::Group::identity()))
+    }
+
+    fn shares_reject_identity<S: Ciphersuite>() {
+        let request = ExchangeRequestEncoded {
+            id: "client".into(),
+            u: identity_hex::<S>(),
+        };
+        assert!(matches!(
+            request.decode::<S>(),
+            Err(DecodeError::IdentityElement)
+        ));
+
+        let response = ExchangeResponseEncoded {
+            v: identity_hex::<S>(),
+            confirm_v: hex::encode(S::hash(b"")),
+        };
+        assert!(matches!(
+            response.decode::<S>(),
+            Err(DecodeError::IdentityElement)
+        ));
+    }
+
+    fn shares_accept_generator<S: Ciphersuite>() {
+        let request = ExchangeRequestEncoded {
+            id: "client".into(),
+            u: hex::encode(S::encode_element(&S::Group::generator())),
+        };
+        assert_eq!(request.decode::<S>().unwrap().u, S::Group::generator());
+    }
+
+    /// Sends an encoded message through JSON, as between client and server.
+    fn wire<T: Serialize + DeserializeOwned>(encoded: T) -> T {
+        serde_json::from_str(&serde_json::to_string(&encoded).unwrap()).unwrap()
+    }
+
+    /// Identities, with the empty string and non-ASCII text well represented.
+    fn ids() -> impl Strategy<Value = String> {
+        prop_oneof![Just(String::new()), "\\PC{0,8}", any::<String>()]
+    }
+
+    fn bytes() -> impl Strategy<Value = Vec<u8>> {
+        vec(any::<u8>(), 0..48)
+    }
+
+    fn elements<S: Ciphersuite>() -> impl Strategy<Value = S::Group> {
+        any::<u64>().prop_map(|seed| S::Group::random(ChaCha20Rng::seed_from_u64(seed)))
+    }
+
+    fn scalars<S: Ciphersuite>() -> impl Strategy<Value = SecretScalar<S>> {
+        any::<u64>().prop_map(|seed| {
+            SecretScalar::new(random_nonzero_scalar(&mut ChaCha20Rng::seed_from_u64(seed)))
+        })
+    }
+
+    fn outputs<S: Ciphersuite>() -> impl Strategy<Value = Output<S>> {
+        bytes().prop_map(|message| S::hash(&message))
+    }
+
+    fn kdfs() -> impl Strategy<Value = PasswordKdf> {
+        prop_oneof![
+            (any::<u32>(), any::<u32>(), any::<u32>())
+                .prop_map(|(m, t, p)| PasswordKdf::Argon2id { m, t, p }),
+            (any::<u8>(), any::<u32>(), any::<u32>())
+                .prop_map(|(log_n, r, p)| PasswordKdf::Scrypt { log_n, r, p }),
+            any::<u32>().prop_map(|rounds| PasswordKdf::Pbkdf2 { rounds }),
+        ]
+    }
+
+    fn srp_groups() -> impl Strategy<Value = SrpGroup> {
+        prop_oneof![
+            Just(SrpGroup::G1024),
+            Just(SrpGroup::G1536),
+            Just(SrpGroup::G2048),
+            Just(SrpGroup::G3072),
+            Just(SrpGroup::G4096),
+        ]
+    }
+
+    fn setup_request_round_trips<S: Ciphersuite>() {
+        proptest!(|(
+            id in ids(),
+            phi0 in scalars::<S>(),
+            c in elements::<S>(),
+            salt in uniform(any::<u8>()),
+            kdf in kdfs(),
+        )| {
+            let expected = *phi0.expose_secret();
+            let encoded = SetupRequest::<S>::new(id.clone(), phi0, c, salt, kdf).encode();
+            let request = wire(encoded).decode::<S>().unwrap();
+            prop_assert_eq!(request.id, id);
+            prop_assert_eq!(*request.phi0.expose_secret(), expected);
+            prop_assert_eq!(request.c, c);
+            prop_assert_eq!(request.salt, salt);
+            prop_assert_eq!(request.kdf, kdf);
+        });
+    }
+
+    fn exchange_messages_round_trip<S: Ciphersuite>() {
+        proptest!(|(id in ids(), u in elements::<S>(), confirm in outputs::<S>())| {
+            let request = ExchangeRequest::<S>::new(id.clone(), u).encode();
+            let request = wire(request).decode::<S>().unwrap();
+            prop_assert_eq!(&request.id, &id);
+            prop_assert_eq!(request.u, u);
+
+            let response = ExchangeResponse::<S>::new(u, confirm.clone()).encode();
+            let response = wire(response).decode::<S>().unwrap();
+            prop_assert_eq!(response.v, u);
+            prop_assert_eq!(&response.confirm_v, &confirm);
+
+            let request = VerifyRequestEncoded::new(id.clone(), hex::encode(&confirm));
+            let request = wire(request).decode::<S>().unwrap();
+            prop_assert_eq!(request.idc, id);
+            prop_assert_eq!(request.confirm_p, confirm);
+        });
+    }
+
+    suite_tests!(
+        shares_reject_identity,
+        shares_accept_generator,
+        setup_request_round_trips,
+        exchange_messages_round_trip,
+    );
+
+    proptest! {
+        #[test]
+        fn prepare_messages_round_trip(
+            id in ids(),
+            salt in uniform(any::<u8>()),
+            kdf in kdfs(),
+        ) {
+            prop_assert_eq!(&wire(PrepareRequestEncoded::new(id.clone())).id, &id);
+
+            let response = wire(PrepareResponse::new(salt, kdf).encode()).decode().unwrap();
+            prop_assert_eq!(response.salt, salt);
+            prop_assert_eq!(response.kdf, kdf);
+        }
+
+        #[test]
+        fn cpace_setup_request_round_trips(
+            id in ids(),
+            prs in uniform::<_, PRS_LEN>(any::<u8>()),
+            salt in uniform(any::<u8>()),
+            kdf in kdfs(),
+        ) {
+            let prs = Zeroizing::new(prs.to_vec());
+            let encoded = CpaceSetupRequest::new(id.clone(), prs.clone(), salt, kdf).encode();
+            let request = wire(encoded).decode().unwrap();
+            prop_assert_eq!(request.id, id);
+            prop_assert_eq!(request.prs, prs);
+            prop_assert_eq!(request.salt, salt);
+            prop_assert_eq!(request.kdf, kdf);
+        }
+
+        #[test]
+        fn cpace_messages_round_trip(
+            id in ids(),
+            sid in uniform(any::<u8>()),
+            y in elements::<Ristretto255>(),
+            ad in bytes(),
+            confirmation in uniform(any::<u8>()),
+        ) {
+            let request = CpaceExchangeRequest::new(id.clone(), sid, y, ad.clone()).encode();
+            let request = wire(request).decode().unwrap();
+            prop_assert_eq!(&request.id, &id);
+            prop_assert_eq!(request.sid, sid);
+            prop_assert_eq!(request.y, y);
+            prop_assert_eq!(&request.ad, &ad);
+
+            let response = CpaceExchangeResponse::new(y, ad.clone(), confirmation).encode();
+            let response = wire(response).decode().unwrap();
+            prop_assert_eq!(response.y, y);
+            prop_assert_eq!(response.ad, ad);
+            prop_assert_eq!(response.confirmation, confirmation);
+
+            let request = CpaceVerifyRequestEncoded::new(id.clone(), hex::encode(confirmation));
+            let request = wire(request).decode().unwrap();
+            prop_assert_eq!(request.idc, id);
+            prop_assert_eq!(request.confirmation, confirmation);
+        }
+
+        #[test]
+        fn opaque_registration_messages_round_trip(
+            id in ids(),
+            blinded in elements::<Ristretto255>(),
+            public_key in elements::<Ristretto255>(),
+            masking_key in uniform(any::<u8>()),
+            envelope in uniform(any::<u8>()),
+            kdf in kdfs(),
+        ) {
+            let request = OpaqueRegisterStartRequest::new(id.clone(), blinded).encode();
+            let request = wire(request).decode().unwrap();
+            prop_assert_eq!(&request.id, &id);
+            prop_assert_eq!(request.blinded, blinded);
+
+            let response = OpaqueRegisterStartResponse::new(blinded, public_key).encode();
+            let response = wire(response).decode().unwrap();
+            prop_assert_eq!(response.evaluated, blinded);
+            prop_assert_eq!(response.server_public_key, public_key);
+
+            let request =
+                OpaqueRegisterFinishRequest::new(id.clone(), public_key, masking_key, envelope, kdf)
+                    .encode();
+            let request = wire(request).decode().unwrap();
+            prop_assert_eq!(request.id, id);
+            prop_assert_eq!(request.client_public_key, public_key);
+            prop_assert_eq!(request.masking_key, masking_key);
+            prop_assert_eq!(request.envelope, envelope);
+            prop_assert_eq!(request.kdf, kdf);
+        }
+
+        #[test]
+        fn opaque_login_messages_round_trip(
+            id in ids(),
+            blinded in elements::<Ristretto255>(),
+            keyshare in elements::<Ristretto255>(),
+            nonce in uniform(any::<u8>()),
+            masked_response in uniform(any::<u8>()),
+            mac in uniform(any::<u8>()),
+            kdf in kdfs(),
+        ) {
+            let request = OpaqueLoginStartRequest::new(id.clone(), blinded, nonce, keyshare);
+            let request = wire(request.encode()).decode().unwrap();
+            prop_assert_eq!(&request.id, &id);
+            prop_assert_eq!(request.blinded, blinded);
+            prop_assert_eq!(request.client_nonce, nonce);
+            prop_assert_eq!(request.client_keyshare, keyshare);
+
+            let response = OpaqueLoginStartResponse::new(
+                blinded,
+                nonce,
+                masked_response,
+                nonce,
+                keyshare,
+                mac,
+                kdf,
+            );
+            let response = wire(response.encode()).decode().unwrap();
+            prop_assert_eq!(response.evaluated, blinded);
+            prop_assert_eq!(response.masking_nonce, nonce);
+            prop_assert_eq!(response.masked_response, masked_response);
+            prop_assert_eq!(response.server_nonce, nonce);
+            prop_assert_eq!(response.server_keyshare, keyshare);
+            prop_assert_eq!(response.server_mac, mac);
+            prop_assert_eq!(response.kdf, kdf);
+
+            let request = OpaqueLoginFinishRequestEncoded::new(id.clone(), hex::encode(mac));
+            let request = wire(request).decode().unwrap();
+            prop_assert_eq!(request.id, id);
+            prop_assert_eq!(request.client_mac, mac);
+        }
+
+        #[test]
+        fn srp_messages_round_trip(
+            id in ids(),
+            group in srp_groups(),
+            salt in bytes(),
+            verifier in vec(1..=u8::MAX, 1..=128),
+            a in bytes(),
+            proof in uniform(any::<u8>()),
+        ) {
+            let verifier = Zeroizing::new(verifier);
+            let request = SrpSetupRequest::new(id.clone(), group, salt.clone(), verifier.clone());
+            let request = wire(request.encode()).decode().unwrap();
+            prop_assert_eq!(&request.id, &id);
+            prop_assert_eq!(request.group, group);
+            prop_assert_eq!(&request.salt, &salt);
+            prop_assert_eq!(&request.verifier, &verifier);
+
+            prop_assert_eq!(&wire(SrpExchangeRequestEncoded::new(id.clone())).id, &id);
+
+            let response = SrpExchangeResponse::new(group, salt.clone(), a.clone()).encode();
+            let response = wire(response).decode().unwrap();
+            prop_assert_eq!(response.group, group);
+            prop_assert_eq!(response.salt, salt);
+            prop_assert_eq!(&response.b, &a);
+
+            let request = wire(SrpVerifyRequest::new(id.clone(), a.clone(), proof).encode());
+            let request = request.decode().unwrap();
+            prop_assert_eq!(request.id, id);
+            prop_assert_eq!(request.a, a);
+            prop_assert_eq!(request.m1, proof);
+
+            let response = wire(SrpVerifyResponse::new(proof).encode()).decode().unwrap();
+            prop_assert_eq!(response.m2, proof);
+        }
+    }
+
+    #[test]
+    fn cpace_request_checks_share_and_sid() {
+        let request = |sid: &str, y: &RistrettoPoint| CpaceExchangeRequestEncoded {
+            id: "client".into(),
+            sid: sid.into(),
+            y: hex::encode(Ristretto255::encode_element(y)),
+            ad: String::new(),
+        };
+        let sid = hex::encode([1; SID_LEN]);
+        let g = RistrettoPoint::generator();
+
+        assert!(request(&sid, &g).decode().is_ok());
+        assert!(matches!(
+            request(&sid, &RistrettoPoint::identity()).decode(),
+            Err(DecodeError::IdentityElement)
+        ));
+        assert!(matches!(
+            request("0101", &g).decode(),
+            Err(DecodeError::InvalidLength(_))
+        ));
+    }
+
+    #[test]
+    fn cpace_setup_checks_prs_length() {
+        let request = |prs: &[u8]| CpaceSetupRequestEncoded {
+            id: "client".into(),
+            prs: hex::encode(prs),
+            salt: hex::encode([0; SALT_LEN]),
+            kdf: PasswordKdf::default().to_string(),
+        };
+
+        assert!(request(&[1; PRS_LEN]).decode().is_ok());
+        for len in [0, 32, PRS_LEN + 1] {
+            assert!(matches!(
+                request(&vec![1; len]).decode(),
+                Err(DecodeError::InvalidLength(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn srp_setup_rejects_zero_verifier() {
+        let group = SrpGroup::G2048;
+        let request = |verifier: Vec<u8>| SrpSetupRequestEncoded {
+            id: "client".into(),
+            group: group.to_string(),
+            salt: hex::encode([0; 16]),
+            verifier: hex::encode(verifier),
+        };
+
+        assert!(request(vec![2]).decode().is_ok());
+        assert!(matches!(
+            request(vec![0; group.element_len()]).decode(),
+            Err(DecodeError::InvalidSrpGroup(SrpError::InvalidPublicValue))
+        ));
+        assert!(matches!(
+            request(vec![1; group.element_len() + 1]).decode(),
+            Err(DecodeError::InvalidLength(_))
+        ));
+    }
+
+    #[test]
+    fn secret_hex_decodes_like_hex() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for encoded in [hex::encode(&bytes), hex::encode_upper(&bytes)] {
+            assert_eq!(*decode_secret_hex(&encoded).unwrap(), bytes);
+        }
+        assert!(decode_secret_hex("").unwrap().is_empty());
+        for invalid in ["0", "0g", "g0", " 00", "0x00"] {
+            assert!(
+                matches!(
+                    decode_secret_hex(invalid),
+                    Err(DecodeError::InvalidSecretHex)
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn opaque_login_response_checks_lengths() {
+        let g = hex::encode(Ristretto255::encode_element(&RistrettoPoint::generator()));
+        let response = |masked_response: String| OpaqueLoginStartResponseEncoded {
+            evaluated: g.clone(),
+            masking_nonce: hex::encode([0; opaque::NONCE_LEN]),
+            masked_response,
+            server_nonce: hex::encode([0; opaque::NONCE_LEN]),
+            server_keyshare: g.clone(),
+            server_mac: hex::encode([0; opaque::HASH_LEN]),
+            kdf: PasswordKdf::default().to_string(),
+        };
+
+        assert!(
+            response(hex::encode([0; opaque::MASKED_RESPONSE_LEN]))
+                .decode()
+                .is_ok()
+        );
+        assert!(matches!(
+            response(hex::encode([0; opaque::ENVELOPE_LEN])).decode(),
+            Err(DecodeError::InvalidLength(_))
+        ));
+    }
+
+    #[test]
+    fn opaque_requests_reject_long_ids() {
+        let g = hex::encode(Ristretto255::encode_element(&RistrettoPoint::generator()));
+        let request = |id: String| OpaqueLoginStartRequestEncoded {
+            id,
+            blinded: g.clone(),
+            client_nonce: hex::encode([0; opaque::NONCE_LEN]),
+            client_keyshare: g.clone(),
+        };
+
+        assert!(
+            request("a".repeat(opaque::MAX_IDENTITY_LEN))
+                .decode()
+                .is_ok()
+        );
+        assert!(matches!(
+            request("a".repeat(opaque::MAX_IDENTITY_LEN + 1)).decode(),
+            Err(DecodeError::InvalidLength(_))
+        ));
+        assert!(matches!(
+            OpaqueLoginFinishRequestEncoded::new(
+                "é".repeat(opaque::MAX_IDENTITY_LEN / 2 + 1),
+                hex::encode([0; opaque::HASH_LEN]),
+            )
+            .decode(),
+            Err(DecodeError::InvalidLength(_))
+        ));
     }
 }
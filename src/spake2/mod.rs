@@ -0,0 +1,402 @@
+//! Balanced SPAKE2 following RFC 9382.
+//!
+//! Unlike SPAKE2+ there is no registration: both parties know the same
+//! low-entropy password, e.g. a pairing code, and derive the same scalar w
+//! from it. Party A blinds its share with M and party B with N, the same
+//! points as in SPAKE2+.
+//!
+//! In [`Role::Symmetric`] neither party needs to know which one is A. Both
+//! blind with M, and the transcript lists the two (identity, share) pairs
+//! ordered by the encoded share, so both compute the same TT. The party
+//! whose share sorts first takes the role of A for the key confirmation.
+
+use group::Group;
+use rand_core::CryptoRngCore;
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    ciphersuite::{Base, Ciphersuite, Output, Scalar, constant_time_eq, random_nonzero_scalar},
+    spake2plus::{
+        kdf::{KdfError, PasswordKdf},
+        rfc9383::{append_prefixed, secret_seed_len},
+        secret::{Password, SecretScalar},
+    },
+};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Spake2Error {
+    #[error("key confirmation failed")]
+    ConfirmationFailed,
+
+    #[error("peer share is the identity element")]
+    IdentityShare,
+
+    #[error("peer share makes K the identity element")]
+    IdentityKeyMaterial,
+
+    #[error("both parties sent the same share")]
+    ReflectedShare,
+}
+
+/// Which side of the exchange a party takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Blinds with M and sends the first confirmation.
+    A,
+    /// Blinds with N.
+    B,
+    /// Both parties blind with M, for when the roles cannot be agreed on.
+    Symmetric,
+}
+
+impl Role {
+    fn own_blind(self) -> Base {
+        match self {
+            Role::A | Role::Symmetric => Base::M,
+            Role::B => Base::N,
+        }
+    }
+
+    fn peer_blind(self) -> Base {
+        match self {
+            Role::A => Base::N,
+            Role::B | Role::Symmetric => Base::M,
+        }
+    }
+}
+
+/// Derives w from the shared password. Both parties must use the same
+/// `salt` and `kdf`, e.g. fixed by the pairing protocol.
+pub fn password_scalar<S: Ciphersuite>(
+    password: &Password,
+    salt: &[u8],
+    kdf: &PasswordKdf,
+) -> Result<SecretScalar<S>, KdfError> {
+    let mut output = Zeroizing::new(vec![0u8; secret_seed_len::<S>()]);
+    kdf.derive(password.expose_secret().as_bytes(), salt, &mut output)?;
+    Ok(SecretScalar::new(S::reduce_scalar(&output)))
+}
+
+/// A party's share, sent to the peer.
+pub struct Spake2Message<S: Ciphersuite> {
+    pub share: S::Group,
+}
+
+/// Entry point of a SPAKE2 exchange.
+pub struct Spake2Handshake;
+
+impl Spake2Handshake {
+    /// Blinds a fresh ephemeral scalar from `rng` with w into this party's
+    /// share. `aad` is bound into the confirmation keys and must match on
+    /// both sides.
+    pub fn start<S: Ciphersuite>(
+        rng: &mut impl CryptoRngCore,
+        role: Role,
+        w: SecretScalar<S>,
+        id: &str,
+        peer_id: &str,
+        aad: &[u8],
+    ) -> (Spake2AwaitingShare<S>, Spake2Message<S>) {
+        let x = random_nonzero_scalar::<Scalar<S>>(rng);
+        Self::start_with_scalar(role, w, SecretScalar::new(x), id, peer_id, aad)
+    }
+
+    fn start_with_scalar<S: Ciphersuite>(
+        role: Role,
+        w: SecretScalar<S>,
+        x: SecretScalar<S>,
+        id: &str,
+        peer_id: &str,
+        aad: &[u8],
+    ) -> (Spake2AwaitingShare<S>, Spake2Message<S>) {
+        let share = S::blind(x.expose_secret(), role.own_blind(), w.expose_secret());
+        let state = Spake2AwaitingShare {
+            role,
+            w,
+            x,
+            id: id.to_string(),
+            peer_id: peer_id.to_string(),
+            aad: aad.to_vec(),
+            share,
+        };
+        (state, Spake2Message { share })
+    }
+}
+
+/// A party that has sent its share and waits for the peer's.
+pub struct Spake2AwaitingShare<S: Ciphersuite> {
+    role: Role,
+    w: SecretScalar<S>,
+    x: SecretScalar<S>,
+    id: String,
+    peer_id: String,
+    aad: Vec<u8>,
+    share: S::Group,
+}
+
+impl<S: Ciphersuite> Spake2AwaitingShare<S> {
+    /// Computes K and the keys from the peer's share. Returns the state
+    /// waiting for the peer's confirmation, and this party's confirmation to
+    /// send to the peer.
+    pub fn finish(
+        self,
+        peer: Spake2Message<S>,
+    ) -> Result<(Spake2AwaitingConfirmation<S>, Output<S>), Spake2Error> {
+        if bool::from(peer.share.is_identity()) {
+            return Err(Spake2Error::IdentityShare);
+        }
+        // K = x·(Y - w·blind) = x·Y - (w·x)·blind
+        let blind = Zeroizing::new(-(*self.w.expose_secret() * self.x.expose_secret()));
+        let k = S::lincomb(
+            &peer.share,
+            self.x.expose_secret(),
+            &S::base(self.role.peer_blind()),
+            &blind,
+        );
+        if bool::from(k.is_identity()) {
+            return Err(Spake2Error::IdentityKeyMaterial);
+        }
+
+        let own = (self.id.as_str(), S::encode_element(&self.share));
+        let theirs = (self.peer_id.as_str(), S::encode_element(&peer.share));
+        let is_a = match self.role {
+            Role::A => true,
+            Role::B => false,
+            // A reflected share would let an attacker skip knowing w
+            Role::Symmetric if own.1 == theirs.1 => return Err(Spake2Error::ReflectedShare),
+            Role::Symmetric => own.1 < theirs.1,
+        };
+        let ((id_a, share_a), (id_b, share_b)) = if is_a { (own, theirs) } else { (theirs, own) };
+
+        let mut tt = Zeroizing::new(Vec::new());
+        append_prefixed(&mut tt, id_a.as_bytes());
+        append_prefixed(&mut tt, id_b.as_bytes());
+        append_prefixed(&mut tt, &share_a);
+        append_prefixed(&mut tt, &share_b);
+        append_prefixed(&mut tt, &S::encode_element(&k));
+        append_prefixed(
+            &mut tt,
+            &Zeroizing::new(S::encode_scalar(self.w.expose_secret())),
+        );
+
+        // Hash(TT) = Ke || Ka
+        let mut hash = S::hash(&tt);
+        let (ke, ka) = hash.split_at(hash.len() / 2);
+        let ke = Zeroizing::new(ke.to_vec());
+
+        let mut info = b"ConfirmationKeys".to_vec();
+        info.extend_from_slice(&self.aad);
+        let mut confirmation_keys = Zeroizing::new(vec![0u8; 2 * ka.len()]);
+        S::kdf(None, ka, &info, &mut confirmation_keys);
+        hash.as_mut_slice().zeroize();
+        let (kc_a, kc_b) = confirmation_keys.split_at(confirmation_keys.len() / 2);
+
+        let (own_key, peer_key) = if is_a { (kc_a, kc_b) } else { (kc_b, kc_a) };
+        let confirmation = S::mac(own_key, &tt);
+        let state = Spake2AwaitingConfirmation {
+            ke,
+            expected: S::mac(peer_key, &tt),
+        };
+        Ok((state, confirmation))
+    }
+}
+
+/// A party that has sent its confirmation and waits for the peer's.
+pub struct Spake2AwaitingConfirmation<S: Ciphersuite> {
+    ke: Zeroizing<Vec<u8>>,
+    expected: Output<S>,
+}
+
+impl<S: Ciphersuite> Spake2AwaitingConfirmation<S> {
+    /// Checks the peer's confirmation and releases the shared key Ke, which
+    /// is half a hash long.
+    pub fn finish(self, confirmation: &Output<S>) -> Result<Zeroizing<Vec<u8>>, Spake2Error> {
+        if !constant_time_eq(&self.expected, confirmation) {
+            return Err(Spake2Error::ConfirmationFailed);
+        }
+        Ok(self.ke)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphersuite::{P256, suite_tests};
+    use rand::rngs::OsRng;
+
+    const KDF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+    const SALT: &[u8] = b"pairing-salt";
+
+    fn w<S: Ciphersuite>(password: &str) -> SecretScalar<S> {
+        password_scalar::<S>(&Password::from(password), SALT, &KDF).unwrap()
+    }
+
+    /// Runs both sides to completion, returning each side's key or error.
+    #[allow(clippy::type_complexity)]
+    fn run<S: Ciphersuite>(
+        roles: (Role, Role),
+        passwords: (&str, &str),
+        aads: (&[u8], &[u8]),
+    ) -> (
+        Result<Zeroizing<Vec<u8>>, Spake2Error>,
+        Result<Zeroizing<Vec<u8>>, Spake2Error>,
+    ) {
+        let (first, first_message) = Spake2Handshake::start(
+            &mut OsRng,
+            roles.0,
+            w::<S>(passwords.0),
+            "alice",
+            "bob",
+            aads.0,
+        );
+        let (second, second_message) = Spake2Handshake::start(
+            &mut OsRng,
+            roles.1,
+            w::<S>(passwords.1),
+            "bob",
+            "alice",
+            aads.1,
+        );
+
+        let (first, first_confirmation) = first.finish(second_message).unwrap();
+        let (second, second_confirmation) = second.finish(first_message).unwrap();
+        (
+            first.finish(&second_confirmation),
+            second.finish(&first_confirmation),
+        )
+    }
+
+    fn asymmetric_roles_agree<S: Ciphersuite>() {
+        let (a, b) = run::<S>((Role::A, Role::B), ("123456", "123456"), (b"", b""));
+        assert_eq!(a.unwrap(), b.unwrap());
+    }
+
+    fn symmetric_mode_agrees<S: Ciphersuite>() {
+        let (a, b) = run::<S>(
+            (Role::Symmetric, Role::Symmetric),
+            ("123456", "123456"),
+            (b"pairing", b"pairing"),
+        );
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert_eq!(a, b);
+        assert_eq!(a.len(), <S::Hash as sha2::Digest>::output_size() / 2);
+    }
+
+    fn symmetric_mode_wrong_password_fails<S: Ciphersuite>() {
+        let (a, b) = run::<S>(
+            (Role::Symmetric, Role::Symmetric),
+            ("123456", "123457"),
+            (b"", b""),
+        );
+        assert_eq!(a, Err(Spake2Error::ConfirmationFailed));
+        assert_eq!(b, Err(Spake2Error::ConfirmationFailed));
+    }
+
+    fn symmetric_mode_aad_mismatch_fails<S: Ciphersuite>() {
+        let (a, b) = run::<S>(
+            (Role::Symmetric, Role::Symmetric),
+            ("123456", "123456"),
+            (b"v1", b"v2"),
+        );
+        assert_eq!(a, Err(Spake2Error::ConfirmationFailed));
+        assert_eq!(b, Err(Spake2Error::ConfirmationFailed));
+    }
+
+    fn mixed_modes_fail<S: Ciphersuite>() {
+        let (a, b) = run::<S>((Role::A, Role::Symmetric), ("123456", "123456"), (b"", b""));
+        assert!(a.is_err());
+        assert!(b.is_err());
+    }
+
+    fn rejects_degenerate_shares<S: Ciphersuite>() {
+        let start =
+            || Spake2Handshake::start(&mut OsRng, Role::Symmetric, w::<S>("123456"), "a", "b", b"");
+
+        let (state, _) = start();
+        let identity = Spake2Message {
+            share: S::Group::identity(),
+        };
+        assert!(matches!(
+            state.finish(identity),
+            Err(Spake2Error::IdentityShare)
+        ));
+
+        // w * M unblinds to the identity
+        let (state, _) = start();
+        let share = S::m() * w::<S>("123456").expose_secret();
+        assert!(matches!(
+            state.finish(Spake2Message { share }),
+            Err(Spake2Error::IdentityKeyMaterial)
+        ));
+
+        let (state, message) = start();
+        assert!(matches!(
+            state.finish(message),
+            Err(Spake2Error::ReflectedShare)
+        ));
+    }
+
+    suite_tests!(
+        asymmetric_roles_agree,
+        symmetric_mode_agrees,
+        symmetric_mode_wrong_password_fails,
+        symmetric_mode_aad_mismatch_fails,
+        mixed_modes_fail,
+        rejects_degenerate_shares,
+    );
+
+    fn scalar(hex: &str) -> SecretScalar<P256> {
+        SecretScalar::new(P256::decode_scalar(&hex::decode(hex).unwrap()).unwrap())
+    }
+
+    /// The first P256-SHA256-HKDF-HMAC test vector of RFC 9382, appendix B.
+    #[test]
+    fn rfc9382_test_vector() {
+        let w = "2ee57912099d31560b3a44b1184b9b4866e904c49d12ac5042c97dca461b1a5f";
+        let x = "43dd0fd7215bdcb482879fca3220c6a968e66d70b1356cac18bb26c84a78d729";
+        let y = "dcb60106f276b02606d8ef0a328c02e4b629f84f89786af5befb0bc75b6e66be";
+
+        let (a, a_message) = Spake2Handshake::start_with_scalar(
+            Role::A,
+            scalar(w),
+            scalar(x),
+            "server",
+            "client",
+            b"",
+        );
+        let (b, b_message) = Spake2Handshake::start_with_scalar(
+            Role::B,
+            scalar(w),
+            scalar(y),
+            "client",
+            "server",
+            b"",
+        );
+        assert_eq!(
+            hex::encode(P256::encode_element(&a_message.share)),
+            "04a56fa807caaa53a4d28dbb9853b9815c61a411118a6fe516a8798434751470f9\
+             010153ac33d0d5f2047ffdb1a3e42c9b4e6be662766e1eeb4116988ede5f912c"
+        );
+        assert_eq!(
+            hex::encode(P256::encode_element(&b_message.share)),
+            "0406557e482bd03097ad0cbaa5df82115460d951e3451962f1eaf4367a420676d0\
+             9857ccbc522686c83d1852abfa8ed6e4a1155cf8f1543ceca528afb591a1e0b7"
+        );
+
+        let (a, a_confirmation) = a.finish(b_message).unwrap();
+        let (b, b_confirmation) = b.finish(a_message).unwrap();
+        assert_eq!(
+            hex::encode(a_confirmation),
+            "58ad4aa88e0b60d5061eb6b5dd93e80d9c4f00d127c65b3b35b1b5281fee38f0"
+        );
+        assert_eq!(
+            hex::encode(b_confirmation),
+            "d3e2e547f1ae04f2dbdbf0fc4b79f8ecff2dff314b5d32fe9fcef2fb26dc459b"
+        );
+
+        let ke = "0e0672dc86f8e45565d338b0540abe69";
+        assert_eq!(hex::encode(a.finish(&b_confirmation).unwrap()), ke);
+        assert_eq!(hex::encode(b.finish(&a_confirmation).unwrap()), ke);
+    }
+}
@@ -0,0 +1,391 @@
+//! Typestate API for RFC 9383 handshakes.
+//!
+//! Each step consumes the state returned by the previous one, so a handshake
+//! cannot be finished twice and its ephemeral scalar cannot be reused. The
+//! shares travel in distinct message types, so u and v cannot be swapped.
+//!
+//! ```compile_fail
+//! # use rand::rngs::OsRng;
+//! # use rusty_pake::{ciphersuite::Ristretto255, spake2plus::{handshake::*, secret::*}};
+//! # fn f(
+//! #     phi0: SecretScalar<Ristretto255>,
+//! #     phi1: SecretScalar<Ristretto255>,
+//! #     response: ServerMessage<Ristretto255>,
+//! #     retry: ServerMessage<Ristretto255>,
+//! # ) {
+//! let (client, _) = ClientHandshake::start(&mut OsRng, b"", "client", "server", phi0, phi1);
+//! let keys = client.finish(response);
+//! // The state was consumed by the first call
+//! let again = client.finish(retry);
+//! # }
+//! ```
+//!
+//! ```compile_fail
+//! # use rand::rngs::OsRng;
+//! # use rusty_pake::{ciphersuite::Ristretto255, spake2plus::{handshake::*, secret::*}};
+//! # fn f(phi0: SecretScalar<Ristretto255>, phi1: SecretScalar<Ristretto255>) {
+//! let (client, message) = ClientHandshake::start(&mut OsRng, b"", "client", "server", phi0, phi1);
+//! // The client's own share u is not the server's v
+//! let keys = client.finish(message);
+//! # }
+//! ```
+
+use rand_core::CryptoRngCore;
+use thiserror::Error;
+
+use super::{
+    client_initial, client_initial_with_scalar,
+    rfc9383::{SessionKeys, client_compute_keys, server_compute_keys},
+    secret::SecretScalar,
+    server_initial, server_initial_with_scalar,
+};
+use crate::ciphersuite::{Ciphersuite, Output, constant_time_eq};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HandshakeError {
+    #[error("key confirmation failed")]
+    ConfirmationFailed,
+
+    #[error("peer share is the identity element")]
+    IdentityShare,
+
+    #[error("peer share makes w or d the identity element")]
+    IdentityKeyMaterial,
+}
+
+/// The client's share, sent to the server.
+pub struct ClientMessage<S: Ciphersuite> {
+    pub u: S::Group,
+}
+
+/// The server's share and key confirmation, sent to the client.
+pub struct ServerMessage<S: Ciphersuite> {
+    pub v: S::Group,
+    pub confirm_v: Output<S>,
+}
+
+/// Client side of a handshake.
+pub struct ClientHandshake;
+
+impl ClientHandshake {
+    /// Blinds a fresh ephemeral scalar from `rng` with phi0 into the client's share.
+    pub fn start<S: Ciphersuite>(
+        rng: &mut impl CryptoRngCore,
+        context: &[u8],
+        idc: &str,
+        ids: &str,
+        phi0: SecretScalar<S>,
+        phi1: SecretScalar<S>,
+    ) -> (ClientAwaitingResponse<S>, ClientMessage<S>) {
+        let (u, alpha) = client_initial::<S>(rng, &phi0);
+        Self::start_from_share(context, idc, ids, phi0, phi1, alpha, u)
+    }
+
+    /// Like [`start`](Self::start), but with the ephemeral `alpha` supplied
+    /// by the caller, e.g. to reproduce the RFC 9383 test vectors. `alpha`
+    /// must be uniformly random, nonzero and never reused.
+    pub fn start_with_scalar<S: Ciphersuite>(
+        context: &[u8],
+        idc: &str,
+        ids: &str,
+        phi0: SecretScalar<S>,
+        phi1: SecretScalar<S>,
+        alpha: SecretScalar<S>,
+    ) -> (ClientAwaitingResponse<S>, ClientMessage<S>) {
+        let (u, alpha) = client_initial_with_scalar::<S>(&phi0, alpha);
+        Self::start_from_share(context, idc, ids, phi0, phi1, alpha, u)
+    }
+
+    fn start_from_share<S: Ciphersuite>(
+        context: &[u8],
+        idc: &str,
+        ids: &str,
+        phi0: SecretScalar<S>,
+        phi1: SecretScalar<S>,
+        alpha: SecretScalar<S>,
+        u: S::Group,
+    ) -> (ClientAwaitingResponse<S>, ClientMessage<S>) {
+        let state = ClientAwaitingResponse {
+            context: context.to_vec(),
+            idc: idc.to_string(),
+            ids: ids.to_string(),
+            phi0,
+            phi1,
+            alpha,
+            u,
+        };
+        (state, ClientMessage { u })
+    }
+}
+
+/// A client that has sent its share and waits for the server's.
+pub struct ClientAwaitingResponse<S: Ciphersuite> {
+    context: Vec<u8>,
+    idc: String,
+    ids: String,
+    phi0: SecretScalar<S>,
+    phi1: SecretScalar<S>,
+    alpha: SecretScalar<S>,
+    u: S::Group,
+}
+
+impl<S: Ciphersuite> ClientAwaitingResponse<S> {
+    /// Derives the keys and checks the server's key confirmation, aborting if
+    /// the server's share is degenerate. On success `confirm_p` of the
+    /// returned keys is to be sent to the server.
+    pub fn finish(self, response: ServerMessage<S>) -> Result<SessionKeys<S>, HandshakeError> {
+        let keys = client_compute_keys::<S>(
+            &self.context,
+            &self.idc,
+            &self.ids,
+            &self.phi0,
+            &self.phi1,
+            self.alpha,
+            self.u,
+            response.v,
+        )?;
+        if !constant_time_eq(&keys.confirm_v, &response.confirm_v) {
+            return Err(HandshakeError::ConfirmationFailed);
+        }
+        Ok(keys)
+    }
+}
+
+/// Server side of a handshake.
+pub struct ServerHandshake;
+
+impl ServerHandshake {
+    /// Answers the client's share with the server's share and key
+    /// confirmation, using the registration `phi0` and `c`. Aborts if the
+    /// client's share is degenerate.
+    pub fn respond<S: Ciphersuite>(
+        rng: &mut impl CryptoRngCore,
+        context: &[u8],
+        idc: &str,
+        ids: &str,
+        phi0: &SecretScalar<S>,
+        c: S::Group,
+        message: ClientMessage<S>,
+    ) -> Result<(ServerAwaitingConfirmation<S>, ServerMessage<S>), HandshakeError> {
+        let (v, beta) = server_initial::<S>(rng, phi0);
+        Self::respond_from_share(context, idc, ids, phi0, c, beta, v, message)
+    }
+
+    /// Like [`respond`](Self::respond), but with the ephemeral `beta`
+    /// supplied by the caller, under the same requirements as `alpha`.
+    pub fn respond_with_scalar<S: Ciphersuite>(
+        context: &[u8],
+        idc: &str,
+        ids: &str,
+        phi0: &SecretScalar<S>,
+        c: S::Group,
+        beta: SecretScalar<S>,
+        message: ClientMessage<S>,
+    ) -> Result<(ServerAwaitingConfirmation<S>, ServerMessage<S>), HandshakeError> {
+        let (v, beta) = server_initial_with_scalar::<S>(phi0, beta);
+        Self::respond_from_share(context, idc, ids, phi0, c, beta, v, message)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn respond_from_share<S: Ciphersuite>(
+        context: &[u8],
+        idc: &str,
+        ids: &str,
+        phi0: &SecretScalar<S>,
+        c: S::Group,
+        beta: SecretScalar<S>,
+        v: S::Group,
+        message: ClientMessage<S>,
+    ) -> Result<(ServerAwaitingConfirmation<S>, ServerMessage<S>), HandshakeError> {
+        let keys = server_compute_keys::<S>(context, idc, ids, phi0, c, beta, message.u, v)?;
+        let response = ServerMessage {
+            v,
+            confirm_v: keys.confirm_v.clone(),
+        };
+        Ok((ServerAwaitingConfirmation { keys }, response))
+    }
+}
+
+/// A server that has answered and waits for the client's key confirmation.
+pub struct ServerAwaitingConfirmation<S: Ciphersuite> {
+    keys: SessionKeys<S>,
+}
+
+impl<S: Ciphersuite> ServerAwaitingConfirmation<S> {
+    /// Checks the client's key confirmation and releases the session keys.
+    pub fn finish(self, confirm_p: &Output<S>) -> Result<SessionKeys<S>, HandshakeError> {
+        if !constant_time_eq(&self.keys.confirm_p, confirm_p) {
+            return Err(HandshakeError::ConfirmationFailed);
+        }
+        Ok(self.keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ciphersuite::suite_tests,
+        spake2plus::{
+            client_cipher,
+            kdf::PasswordKdf,
+            rfc9383::{SALT_LEN, client_secret},
+            secret::Password,
+        },
+    };
+    use group::Group;
+    use rand::rngs::OsRng;
+
+    const CONTEXT: &[u8] = b"rusty-pake test";
+    const KDF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+    const SALT: [u8; SALT_LEN] = [7; SALT_LEN];
+
+    fn secrets<S: Ciphersuite>(password: &str) -> (SecretScalar<S>, SecretScalar<S>) {
+        client_secret::<S>(&Password::from(password), "client", "server", &SALT, &KDF).unwrap()
+    }
+
+    fn handshake_agrees<S: Ciphersuite>() {
+        let (phi0, phi1) = secrets::<S>("password123");
+        let c = client_cipher::<S>(&phi1);
+
+        let (client, message) =
+            ClientHandshake::start(&mut OsRng, CONTEXT, "client", "server", phi0, phi1);
+        let (phi0, _) = secrets::<S>("password123");
+        let (server, response) =
+            ServerHandshake::respond(&mut OsRng, CONTEXT, "client", "server", &phi0, c, message)
+                .unwrap();
+
+        let client_keys = client.finish(response).unwrap();
+        let server_keys = server.finish(&client_keys.confirm_p).unwrap();
+        assert_eq!(
+            client_keys.key.expose_secret(),
+            server_keys.key.expose_secret()
+        );
+    }
+
+    fn wrong_password_fails_confirmation<S: Ciphersuite>() {
+        let (phi0, phi1) = secrets::<S>("password123");
+        let c = client_cipher::<S>(&phi1);
+
+        let (wrong_phi0, wrong_phi1) = secrets::<S>("wrongpassword");
+        let (client, message) = ClientHandshake::start(
+            &mut OsRng, CONTEXT, "client", "server", wrong_phi0, wrong_phi1,
+        );
+        let (server, response) =
+            ServerHandshake::respond(&mut OsRng, CONTEXT, "client", "server", &phi0, c, message)
+                .unwrap();
+        let confirm_v = response.confirm_v.clone();
+
+        assert!(matches!(
+            client.finish(response),
+            Err(HandshakeError::ConfirmationFailed)
+        ));
+        assert!(matches!(
+            server.finish(&confirm_v),
+            Err(HandshakeError::ConfirmationFailed)
+        ));
+    }
+
+    fn server_rejects_confirmation_off_in_any_byte<S: Ciphersuite>() {
+        let (phi0, phi1) = secrets::<S>("password123");
+        let c = client_cipher::<S>(&phi1);
+
+        for i in 0..Output::<S>::default().len() {
+            let (client, message) = ClientHandshake::start(
+                &mut OsRng,
+                CONTEXT,
+                "client",
+                "server",
+                SecretScalar::new(*phi0.expose_secret()),
+                SecretScalar::new(*phi1.expose_secret()),
+            );
+            let (server, response) = ServerHandshake::respond(
+                &mut OsRng, CONTEXT, "client", "server", &phi0, c, message,
+            )
+            .unwrap();
+            let mut confirm_p = client.finish(response).unwrap().confirm_p.clone();
+            confirm_p[i] ^= 1;
+
+            assert!(
+                matches!(
+                    server.finish(&confirm_p),
+                    Err(HandshakeError::ConfirmationFailed)
+                ),
+                "byte {}",
+                i
+            );
+        }
+    }
+
+    fn server_rejects_degenerate_shares<S: Ciphersuite>() {
+        let (phi0, phi1) = secrets::<S>("password123");
+        let c = client_cipher::<S>(&phi1);
+        let respond = |u| {
+            ServerHandshake::respond(
+                &mut OsRng,
+                CONTEXT,
+                "client",
+                "server",
+                &phi0,
+                c,
+                ClientMessage { u },
+            )
+            .map(|_| ())
+        };
+
+        assert_eq!(
+            respond(S::Group::identity()),
+            Err(HandshakeError::IdentityShare)
+        );
+        // A share of phi0 * M unblinds to the identity
+        assert_eq!(
+            respond(S::m() * phi0.expose_secret()),
+            Err(HandshakeError::IdentityKeyMaterial)
+        );
+        // So does any share against an identity c
+        let u = S::Group::generator();
+        let result = ServerHandshake::respond::<S>(
+            &mut OsRng,
+            CONTEXT,
+            "client",
+            "server",
+            &phi0,
+            S::Group::identity(),
+            ClientMessage { u },
+        );
+        assert!(matches!(result, Err(HandshakeError::IdentityKeyMaterial)));
+    }
+
+    fn client_rejects_degenerate_shares<S: Ciphersuite>() {
+        let finish = |v| {
+            let (phi0, phi1) = secrets::<S>("password123");
+            let (client, _) =
+                ClientHandshake::start(&mut OsRng, CONTEXT, "client", "server", phi0, phi1);
+            client
+                .finish(ServerMessage {
+                    v,
+                    confirm_v: Output::<S>::default(),
+                })
+                .map(|_| ())
+        };
+
+        assert_eq!(
+            finish(S::Group::identity()),
+            Err(HandshakeError::IdentityShare)
+        );
+        // A share of phi0 * N unblinds to the identity
+        let (phi0, _) = secrets::<S>("password123");
+        assert_eq!(
+            finish(S::n() * phi0.expose_secret()),
+            Err(HandshakeError::IdentityKeyMaterial)
+        );
+    }
+
+    suite_tests!(
+        handshake_agrees,
+        wrong_password_fails_confirmation,
+        server_rejects_confirmation_off_in_any_byte,
+        server_rejects_degenerate_shares,
+        client_rejects_degenerate_shares,
+    );
+}
@@ -0,0 +1,325 @@
+//! Password stretching for the password-to-scalar step.
+//!
+//! The parameters are stored with each registration in a compact text form,
+//! e.g. `argon2id$m=19456,t=2,p=1`, so the cost for new registrations can be
+//! raised without invalidating existing ones.
+
+use std::{fmt, str::FromStr};
+
+use sha2::Sha256;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum KdfError {
+    #[error("unknown password KDF: {0}")]
+    UnknownAlgorithm(String),
+
+    #[error("invalid password KDF parameters: {0}")]
+    InvalidParams(String),
+}
+
+/// A password-based key derivation function together with its cost
+/// parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordKdf {
+    /// Argon2id with memory cost `m` in KiB, `t` iterations and `p` lanes.
+    Argon2id { m: u32, t: u32, p: u32 },
+    /// scrypt with cost `2^log_n`, block size `r` and parallelism `p`.
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    /// PBKDF2-HMAC-SHA256, for platforms where neither of the above is
+    /// available.
+    Pbkdf2 { rounds: u32 },
+}
+
+impl Default for PasswordKdf {
+    /// Argon2id with the OWASP recommended minimum parameters.
+    fn default() -> Self {
+        PasswordKdf::Argon2id {
+            m: 19 * 1024,
+            t: 2,
+            p: 1,
+        }
+    }
+}
+
+impl PasswordKdf {
+    /// Whether the parameters are at least as costly as one of the OWASP
+    /// recommended configurations, which servers require of new
+    /// registrations:
+    /// - Argon2id with `p >= 1` and 46 MiB for one iteration, 19 MiB for two,
+    ///   12 MiB for three, 9 MiB for four or 7 MiB for five
+    /// - scrypt with `r >= 8` and `2^17` for one lane, `2^16` for two, `2^15`
+    ///   for three, `2^14` for five or `2^13` for ten
+    /// - PBKDF2-HMAC-SHA256 with 600,000 rounds
+    pub fn meets_minimum(&self) -> bool {
+        match *self {
+            PasswordKdf::Argon2id { m, t, p } => {
+                const FLOORS: [(u32, u32); 5] =
+                    [(1, 47104), (2, 19456), (3, 12288), (4, 9216), (5, 7168)];
+                p >= 1
+                    && FLOORS
+                        .iter()
+                        .any(|&(min_t, min_m)| t >= min_t && m >= min_m)
+            }
+            PasswordKdf::Scrypt { log_n, r, p } => {
+                const FLOORS: [(u8, u32); 5] = [(17, 1), (16, 2), (15, 3), (14, 5), (13, 10)];
+                r >= 8
+                    && FLOORS
+                        .iter()
+                        .any(|&(min_log_n, min_p)| log_n >= min_log_n && p >= min_p)
+            }
+            PasswordKdf::Pbkdf2 { rounds } => rounds >= 600_000,
+        }
+    }
+
+    /// Whether the parameters are within what a client is willing to run for
+    /// a server, so that a malicious one cannot make it allocate or compute
+    /// without bound:
+    /// - Argon2id with at most 1 GiB, 16 iterations and 16 lanes
+    /// - scrypt with at most 1 GiB (`128 * r * 2^log_n` bytes), `2^24` and
+    ///   16 lanes
+    /// - PBKDF2-HMAC-SHA256 with at most 10,000,000 rounds
+    pub fn within_maximum(&self) -> bool {
+        const MAX_MEMORY: u64 = 1 << 30;
+        match *self {
+            PasswordKdf::Argon2id { m, t, p } => {
+                u64::from(m) * 1024 <= MAX_MEMORY && t <= 16 && p <= 16
+            }
+            PasswordKdf::Scrypt { log_n, r, p } => {
+                log_n <= 24 && (128 * u64::from(r)) << log_n <= MAX_MEMORY && p <= 16
+            }
+            PasswordKdf::Pbkdf2 { rounds } => rounds <= 10_000_000,
+        }
+    }
+
+    /// Fills `output` with key material derived from `password` and `salt`.
+    pub fn derive(&self, password: &[u8], salt: &[u8], output: &mut [u8]) -> Result<(), KdfError> {
+        match *self {
+            PasswordKdf::Argon2id { m, t, p } => {
+                let params = argon2::Params::new(m, t, p, Some(output.len()))
+                    .map_err(|e| KdfError::InvalidParams(e.to_string()))?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(password, salt, output)
+                    .map_err(|e| KdfError::InvalidParams(e.to_string()))
+            }
+            PasswordKdf::Scrypt { log_n, r, p } => {
+                // The length is only used for PHC strings, the output length is what counts
+                let params = scrypt::Params::new(log_n, r, p, scrypt::Params::RECOMMENDED_LEN)
+                    .map_err(|e| KdfError::InvalidParams(e.to_string()))?;
+                scrypt::scrypt(password, salt, &params, output)
+                    .map_err(|e| KdfError::InvalidParams(e.to_string()))
+            }
+            PasswordKdf::Pbkdf2 { rounds } => {
+                if rounds == 0 {
+                    return Err(KdfError::InvalidParams("rounds must be positive".into()));
+                }
+                pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, rounds, output);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for PasswordKdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordKdf::Argon2id { m, t, p } => write!(f, "argon2id$m={m},t={t},p={p}"),
+            PasswordKdf::Scrypt { log_n, r, p } => write!(f, "scrypt$ln={log_n},r={r},p={p}"),
+            PasswordKdf::Pbkdf2 { rounds } => write!(f, "pbkdf2-sha256$i={rounds}"),
+        }
+    }
+}
+
+impl FromStr for PasswordKdf {
+    type Err = KdfError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, params) = s
+            .split_once('$')
+            .ok_or_else(|| KdfError::InvalidParams(s.into()))?;
+
+        // Parameters must appear exactly in the order given by `names`
+        let values = |names: &[&str]| -> Result<Vec<u64>, KdfError> {
+            let pairs: Vec<_> = params.split(',').collect();
+            if pairs.len() != names.len() {
+                return Err(KdfError::InvalidParams(params.into()));
+            }
+            names
+                .iter()
+                .zip(pairs)
+                .map(|(name, pair)| match pair.split_once('=') {
+                    Some((key, value)) if key == *name => value
+                        .parse()
+                        .map_err(|_| KdfError::InvalidParams(pair.into())),
+                    _ => Err(KdfError::InvalidParams(pair.into())),
+                })
+                .collect()
+        };
+        let narrow = |value: u64| -> Result<u32, KdfError> {
+            value
+                .try_into()
+                .map_err(|_| KdfError::InvalidParams(value.to_string()))
+        };
+
+        match algorithm {
+            "argon2id" => {
+                let v = values(&["m", "t", "p"])?;
+                Ok(PasswordKdf::Argon2id {
+                    m: narrow(v[0])?,
+                    t: narrow(v[1])?,
+                    p: narrow(v[2])?,
+                })
+            }
+            "scrypt" => {
+                let v = values(&["ln", "r", "p"])?;
+                Ok(PasswordKdf::Scrypt {
+                    log_n: v[0]
+                        .try_into()
+                        .map_err(|_| KdfError::InvalidParams(v[0].to_string()))?,
+                    r: narrow(v[1])?,
+                    p: narrow(v[2])?,
+                })
+            }
+            "pbkdf2-sha256" => {
+                let v = values(&["i"])?;
+                Ok(PasswordKdf::Pbkdf2 {
+                    rounds: narrow(v[0])?,
+                })
+            }
+            _ => Err(KdfError::UnknownAlgorithm(algorithm.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHEAP: [PasswordKdf; 3] = [
+        PasswordKdf::Argon2id { m: 64, t: 1, p: 1 },
+        PasswordKdf::Scrypt {
+            log_n: 4,
+            r: 8,
+            p: 1,
+        },
+        PasswordKdf::Pbkdf2 { rounds: 10 },
+    ];
+
+    #[test]
+    fn encoding_round_trip() {
+        for kdf in CHEAP.into_iter().chain([PasswordKdf::default()]) {
+            assert_eq!(kdf.to_string().parse::<PasswordKdf>(), Ok(kdf));
+        }
+        assert_eq!(
+            PasswordKdf::default().to_string(),
+            "argon2id$m=19456,t=2,p=1"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_encodings() {
+        assert_eq!(
+            "bcrypt$c=12".parse::<PasswordKdf>(),
+            Err(KdfError::UnknownAlgorithm("bcrypt".into()))
+        );
+        for s in [
+            "argon2id",
+            "argon2id$m=64,t=1",
+            "argon2id$t=1,m=64,p=1",
+            "argon2id$m=64,t=1,p=x",
+            "scrypt$ln=300,r=8,p=1",
+            "pbkdf2-sha256$i=99999999999",
+        ] {
+            assert!(s.parse::<PasswordKdf>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn minimum_follows_owasp() {
+        assert!(PasswordKdf::default().meets_minimum());
+        for kdf in CHEAP {
+            assert!(!kdf.meets_minimum(), "{kdf}");
+        }
+        for s in [
+            "argon2id$m=47104,t=1,p=1",
+            "argon2id$m=7168,t=5,p=1",
+            "scrypt$ln=17,r=8,p=1",
+            "scrypt$ln=13,r=8,p=10",
+            "pbkdf2-sha256$i=600000",
+        ] {
+            assert!(s.parse::<PasswordKdf>().unwrap().meets_minimum(), "{s}");
+        }
+        for s in [
+            "argon2id$m=19455,t=2,p=1",
+            "argon2id$m=19456,t=1,p=1",
+            "argon2id$m=19456,t=2,p=0",
+            "argon2id$m=8,t=1,p=1",
+            "scrypt$ln=16,r=8,p=1",
+            "scrypt$ln=17,r=4,p=1",
+            "pbkdf2-sha256$i=599999",
+            "pbkdf2-sha256$i=1",
+        ] {
+            assert!(!s.parse::<PasswordKdf>().unwrap().meets_minimum(), "{s}");
+        }
+    }
+
+    #[test]
+    fn maximum_bounds_cost() {
+        assert!(PasswordKdf::default().within_maximum());
+        for kdf in CHEAP {
+            assert!(kdf.within_maximum(), "{kdf}");
+        }
+        for s in [
+            "argon2id$m=1048576,t=16,p=16",
+            "scrypt$ln=20,r=8,p=16",
+            "pbkdf2-sha256$i=10000000",
+        ] {
+            assert!(s.parse::<PasswordKdf>().unwrap().within_maximum(), "{s}");
+        }
+        for s in [
+            "argon2id$m=4294967295,t=2,p=1",
+            "argon2id$m=19456,t=17,p=1",
+            "argon2id$m=19456,t=2,p=17",
+            "scrypt$ln=63,r=8,p=1",
+            "scrypt$ln=21,r=8,p=1",
+            "scrypt$ln=17,r=4294967295,p=1",
+            "scrypt$ln=17,r=8,p=17",
+            "pbkdf2-sha256$i=10000001",
+        ] {
+            assert!(!s.parse::<PasswordKdf>().unwrap().within_maximum(), "{s}");
+        }
+    }
+
+    #[test]
+    fn derive_depends_on_algorithm_and_salt() {
+        let mut outputs = Vec::new();
+        for kdf in CHEAP {
+            for salt in [b"salt-one", b"salt-two"] {
+                let mut out = [0u8; 80];
+                kdf.derive(b"password123", salt, &mut out).unwrap();
+                outputs.push(out);
+            }
+        }
+        for (i, a) in outputs.iter().enumerate() {
+            for b in &outputs[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn derive_rejects_invalid_params() {
+        let mut out = [0u8; 80];
+        for kdf in [
+            PasswordKdf::Argon2id { m: 1, t: 1, p: 1 },
+            PasswordKdf::Scrypt {
+                log_n: 4,
+                r: 0,
+                p: 1,
+            },
+            PasswordKdf::Pbkdf2 { rounds: 0 },
+        ] {
+            assert!(kdf.derive(b"password123", b"saltsalt", &mut out).is_err());
+        }
+    }
+}
@@ -1,180 +1,512 @@
-use curve25519_dalek::{RistrettoPoint, Scalar};
-use group::Group;
-use rand::rngs::OsRng;
-use sha2::{Digest, Sha512};
+pub mod handshake;
+pub mod kdf;
+pub mod rfc9383;
+pub mod secret;
 
-fn a_point() -> RistrettoPoint {
-    RistrettoPoint::hash_from_bytes::<Sha512>(b"A")
-}
+use rand_core::CryptoRngCore;
+use sha2::Digest;
+use zeroize::Zeroizing;
 
-fn b_point() -> RistrettoPoint {
-    RistrettoPoint::hash_from_bytes::<Sha512>(b"B")
-}
+use crate::ciphersuite::{Base, Ciphersuite, random_nonzero_scalar};
+use handshake::HandshakeError;
+use rfc9383::{check_derived, check_share};
+use secret::{SecretScalar, SessionKey};
 
-fn h_prime(
-    phi0: Scalar,
+/// Hashes the exchange into the session key. The application `context` and
+/// both identities are length-prefixed so they cannot run into the fields
+/// after them.
+#[allow(clippy::too_many_arguments)]
+fn h_prime<S: Ciphersuite>(
+    context: &[u8],
+    phi0: &SecretScalar<S>,
     idc: &str,
     ids: &str,
-    u: RistrettoPoint,
-    v: RistrettoPoint,
-    w: RistrettoPoint,
-    d: RistrettoPoint,
-) -> [u8; 32] {
-    let mut hasher = Sha512::new();
-    hasher.update(phi0.as_bytes());
-    hasher.update(idc.as_bytes());
-    hasher.update(ids.as_bytes());
-    hasher.update(u.compress().as_bytes());
-    hasher.update(v.compress().as_bytes());
-    hasher.update(w.compress().as_bytes());
-    hasher.update(d.compress().as_bytes());
-    let hash = hasher.finalize();
-    let mut bytes = [0u8; 32];
-    bytes.copy_from_slice(&hash[..32]);
-    bytes
+    u: S::Group,
+    v: S::Group,
+    w: S::Group,
+    d: S::Group,
+) -> SessionKey<S> {
+    let mut hasher = S::Hash::new();
+    for field in [context, idc.as_bytes(), ids.as_bytes()] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field);
+    }
+    hasher.update(Zeroizing::new(S::encode_scalar(phi0.expose_secret())));
+    hasher.update(S::encode_element(&u));
+    hasher.update(S::encode_element(&v));
+    hasher.update(S::encode_element(&w));
+    hasher.update(S::encode_element(&d));
+    SessionKey::new(hasher.finalize())
 }
 
-fn h(m: &[u8]) -> (Scalar, Scalar) {
-    let hash = Sha512::digest(m);
-    let mut left = [0u8; 32];
-    let mut right = [0u8; 32];
-    left.copy_from_slice(&hash[..32]);
-    right.copy_from_slice(&hash[32..]);
-    (
-        Scalar::from_bytes_mod_order(left),
-        Scalar::from_bytes_mod_order(right),
-    )
+pub fn client_cipher<S: Ciphersuite>(phi1: &SecretScalar<S>) -> S::Group {
+    S::mul_base(Base::G, phi1.expose_secret())
 }
 
-pub fn client_secret(password: &str, idc: &str, ids: &str) -> (Scalar, Scalar) {
-    let mut hasher = Sha512::new();
-    hasher.update(password.as_bytes());
-    hasher.update(idc.as_bytes());
-    hasher.update(ids.as_bytes());
-    h(&hasher.finalize())
+pub fn client_initial<S: Ciphersuite>(
+    rng: &mut impl CryptoRngCore,
+    phi0: &SecretScalar<S>,
+) -> (S::Group, SecretScalar<S>) {
+    let alpha = SecretScalar::<S>::new(random_nonzero_scalar(rng));
+    client_initial_with_scalar(phi0, alpha)
 }
 
-pub fn client_cipher(phi1: Scalar) -> RistrettoPoint {
-    RistrettoPoint::generator() * phi1
+/// Like [`client_initial`], but with the ephemeral `alpha` supplied by the
+/// caller, e.g. to reproduce test vectors. `alpha` must be uniformly random,
+/// nonzero and never reused.
+pub fn client_initial_with_scalar<S: Ciphersuite>(
+    phi0: &SecretScalar<S>,
+    alpha: SecretScalar<S>,
+) -> (S::Group, SecretScalar<S>) {
+    let u = S::blind(alpha.expose_secret(), Base::M, phi0.expose_secret());
+    (u, alpha)
 }
 
-pub fn client_initial(phi0: Scalar) -> (RistrettoPoint, Scalar) {
-    let alpha = Scalar::random(&mut OsRng);
-    if alpha == Scalar::ZERO {
-        panic!("alpha should not be zero!");
-    }
-    let a = a_point();
-    let g = RistrettoPoint::generator();
-    let u = g * alpha + a * phi0;
-    (u, alpha)
+pub fn server_initial<S: Ciphersuite>(
+    rng: &mut impl CryptoRngCore,
+    phi0: &SecretScalar<S>,
+) -> (S::Group, SecretScalar<S>) {
+    let beta = SecretScalar::<S>::new(random_nonzero_scalar(rng));
+    server_initial_with_scalar(phi0, beta)
 }
 
-pub fn server_initial(phi0: Scalar) -> (RistrettoPoint, Scalar) {
-    let beta = Scalar::random(&mut OsRng);
-    if beta == Scalar::ZERO {
-        panic!("beta should not be zero!");
-    }
-    let b = b_point();
-    let g = RistrettoPoint::generator();
-    let v = g * beta + b * phi0;
+/// Like [`server_initial`], but with the ephemeral `beta` supplied by the
+/// caller. The same requirements as for `alpha` apply.
+pub fn server_initial_with_scalar<S: Ciphersuite>(
+    phi0: &SecretScalar<S>,
+    beta: SecretScalar<S>,
+) -> (S::Group, SecretScalar<S>) {
+    let v = S::blind(beta.expose_secret(), Base::N, phi0.expose_secret());
     (v, beta)
 }
 
-pub fn client_compute_key(
+/// The client's w = alpha·(v - phi0·N) and d = phi1·(v - phi0·N).
+pub(crate) fn client_key_material<S: Ciphersuite>(
+    phi0: &SecretScalar<S>,
+    phi1: &SecretScalar<S>,
+    alpha: &SecretScalar<S>,
+    v: S::Group,
+) -> (S::Group, S::Group) {
+    let unblinded = v - S::mul_base(Base::N, phi0.expose_secret());
+    (
+        unblinded * alpha.expose_secret(),
+        unblinded * phi1.expose_secret(),
+    )
+}
+
+/// The server's w = beta·(u - phi0·M), computed as beta·u - (phi0·beta)·M in
+/// one multiscalar multiplication, and d = beta·c.
+pub(crate) fn server_key_material<S: Ciphersuite>(
+    phi0: &SecretScalar<S>,
+    c: S::Group,
+    beta: &SecretScalar<S>,
+    u: S::Group,
+) -> (S::Group, S::Group) {
+    let blind = Zeroizing::new(-(*phi0.expose_secret() * beta.expose_secret()));
+    (
+        S::lincomb(&u, beta.expose_secret(), &S::m(), &blind),
+        c * beta.expose_secret(),
+    )
+}
+
+/// Computes the client's session key. `context` binds the key to the
+/// application and may be empty. Fails like
+/// [`ClientAwaitingResponse::finish`](handshake::ClientAwaitingResponse::finish)
+/// if `v` is the identity or unblinds to it.
+#[allow(clippy::too_many_arguments)]
+pub fn client_compute_key<S: Ciphersuite>(
+    context: &[u8],
     idc: &str,
     ids: &str,
-    phi0: Scalar,
-    phi1: Scalar,
-    alpha: Scalar,
-    u: RistrettoPoint,
-    v: RistrettoPoint,
-) -> [u8; 32] {
-    let b = b_point();
-    let w = (v - b * phi0) * alpha;
-    let d = (v - b * phi0) * phi1;
-    h_prime(phi0, idc, ids, u, v, w, d)
+    phi0: &SecretScalar<S>,
+    phi1: &SecretScalar<S>,
+    alpha: &SecretScalar<S>,
+    u: S::Group,
+    v: S::Group,
+) -> Result<SessionKey<S>, HandshakeError> {
+    check_share::<S>(v)?;
+    let (w, d) = client_key_material::<S>(phi0, phi1, alpha, v);
+    check_derived::<S>(w, d)?;
+    Ok(h_prime::<S>(context, phi0, idc, ids, u, v, w, d))
 }
 
-pub fn server_compute_key(
+/// Computes the server's session key. `context` binds the key to the
+/// application and may be empty. Fails like
+/// [`ServerHandshake::respond`](handshake::ServerHandshake::respond) if `u` is
+/// the identity or unblinds to it, or if `c` is the identity.
+#[allow(clippy::too_many_arguments)]
+pub fn server_compute_key<S: Ciphersuite>(
+    context: &[u8],
     idc: &str,
     ids: &str,
-    phi0: Scalar,
-    c: RistrettoPoint,
-    beta: Scalar,
-    u: RistrettoPoint,
-    v: RistrettoPoint,
-) -> [u8; 32] {
-    let a = a_point();
-    let w = (u - a * phi0) * beta;
-    let d = c * beta;
-    h_prime(phi0, idc, ids, u, v, w, d)
+    phi0: &SecretScalar<S>,
+    c: S::Group,
+    beta: &SecretScalar<S>,
+    u: S::Group,
+    v: S::Group,
+) -> Result<SessionKey<S>, HandshakeError> {
+    check_share::<S>(u)?;
+    let (w, d) = server_key_material::<S>(phi0, c, beta, u);
+    check_derived::<S>(w, d)?;
+    Ok(h_prime::<S>(context, phi0, idc, ids, u, v, w, d))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[test]
-    fn distinct_a_b_g() {
-        assert!(!bool::from(a_point().is_identity()));
-        assert!(!bool::from(b_point().is_identity()));
-        assert_ne!(a_point(), b_point());
-        assert_ne!(a_point(), RistrettoPoint::generator());
-        assert_ne!(b_point(), RistrettoPoint::generator());
+    use crate::ciphersuite::{Scalar, suite_tests};
+    use group::{Group, ff::Field};
+    use handshake::{ClientHandshake, ServerHandshake, ServerMessage};
+    use kdf::PasswordKdf;
+    use proptest::{collection::vec, prelude::*};
+    use rand::{SeedableRng, rngs::OsRng};
+    use rand_chacha::ChaCha20Rng;
+    use rfc9383::SALT_LEN;
+    use secret::Password;
+
+    const KDF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+    const SALT: [u8; SALT_LEN] = [7; SALT_LEN];
+
+    fn client_secret<S: Ciphersuite>(
+        password: &Password,
+        idc: &str,
+        ids: &str,
+    ) -> (SecretScalar<S>, SecretScalar<S>) {
+        rfc9383::client_secret::<S>(password, idc, ids, &SALT, &KDF).unwrap()
+    }
+
+    fn distinct_a_b_g<S: Ciphersuite>() {
+        assert!(!bool::from(S::m().is_identity()));
+        assert!(!bool::from(S::n().is_identity()));
+        assert_ne!(S::m(), S::n());
+        assert_ne!(S::m(), S::Group::generator());
+        assert_ne!(S::n(), S::Group::generator());
     }
 
-    #[test]
-    fn correct_password_same_key() {
+    fn correct_password_same_key<S: Ciphersuite>() {
         let idc = "client";
         let ids = "server";
-        let password = "password123";
+        let password = Password::from("password123");
 
         // Initial setup
-        let (phi0, phi1) = client_secret(password, idc, ids);
-        let c = client_cipher(phi1);
+        let (phi0, phi1) = client_secret::<S>(&password, idc, ids);
+        let c = client_cipher::<S>(&phi1);
 
         // Step 1: Client computes u
-        let (u, alpha) = client_initial(phi0);
+        let (u, alpha) = client_initial::<S>(&mut OsRng, &phi0);
 
         // Step 2: Server computes v
-        let (v, beta) = server_initial(phi0);
+        let (v, beta) = server_initial::<S>(&mut OsRng, &phi0);
 
         // Step 3: Client computes session key
         // Uses v from server
-        let k_client = client_compute_key(idc, ids, phi0, phi1, alpha, u, v);
+        let k_client = client_compute_key::<S>(b"", idc, ids, &phi0, &phi1, &alpha, u, v).unwrap();
 
         // Step 4: Server computes session key
         // Uses u from client and c from setup_2
-        let k_server = server_compute_key(idc, ids, phi0, c, beta, u, v);
+        let k_server = server_compute_key::<S>(b"", idc, ids, &phi0, c, &beta, u, v).unwrap();
 
-        assert_eq!(k_client, k_server);
+        assert_eq!(k_client.expose_secret(), k_server.expose_secret());
     }
 
-    #[test]
-    fn wrong_password_different_key() {
+    fn wrong_password_different_key<S: Ciphersuite>() {
         let idc = "client";
         let ids = "server";
-        let password = "password123";
-        let wrong_password = "wrongpassword";
+        let password = Password::from("password123");
+        let wrong_password = Password::from("wrongpassword");
 
         // Initial setup
-        let (phi0, phi1) = client_secret(password, idc, ids);
-        let c = client_cipher(phi1);
+        let (phi0, phi1) = client_secret::<S>(&password, idc, ids);
+        let c = client_cipher::<S>(&phi1);
 
         // Wrang setup
-        let (phi0_wrong, phi1_wrong) = client_secret(wrong_password, idc, ids);
+        let (phi0_wrong, phi1_wrong) = client_secret::<S>(&wrong_password, idc, ids);
 
         // Step 1: Client computes u with wrong phi0
-        let (u_wrong, alpha) = client_initial(phi0_wrong);
+        let (u_wrong, alpha) = client_initial::<S>(&mut OsRng, &phi0_wrong);
 
         // Step 2: Server computes v with correct saved password
-        let (v, beta) = server_initial(phi0);
+        let (v, beta) = server_initial::<S>(&mut OsRng, &phi0);
 
         // Step 3: Client computes session key with wrong phi0 and phi1
-        let k_client = client_compute_key(idc, ids, phi0_wrong, phi1_wrong, alpha, u_wrong, v);
+        let k_client =
+            client_compute_key::<S>(b"", idc, ids, &phi0_wrong, &phi1_wrong, &alpha, u_wrong, v)
+                .unwrap();
 
         // Step 4: Server computes session key with correct saved phi0 and c
-        let k_server = server_compute_key(idc, ids, phi0, c, beta, u_wrong, v);
+        let k_server = server_compute_key::<S>(b"", idc, ids, &phi0, c, &beta, u_wrong, v).unwrap();
+
+        assert_ne!(k_client.expose_secret(), k_server.expose_secret());
+    }
+
+    fn context_changes_key<S: Ciphersuite>() {
+        let idc = "client";
+        let ids = "server";
+        let password = Password::from("password123");
+
+        let (phi0, phi1) = client_secret::<S>(&password, idc, ids);
+        let c = client_cipher::<S>(&phi1);
+        let (u, alpha) = client_initial::<S>(&mut OsRng, &phi0);
+        let (v, beta) = server_initial::<S>(&mut OsRng, &phi0);
+
+        let k_client =
+            client_compute_key::<S>(b"service-a", idc, ids, &phi0, &phi1, &alpha, u, v).unwrap();
+        let k_server =
+            server_compute_key::<S>(b"service-a", idc, ids, &phi0, c, &beta, u, v).unwrap();
+        let k_other =
+            server_compute_key::<S>(b"service-b", idc, ids, &phi0, c, &beta, u, v).unwrap();
+
+        assert_eq!(k_client.expose_secret(), k_server.expose_secret());
+        assert_ne!(k_server.expose_secret(), k_other.expose_secret());
+    }
+
+    fn compute_key_rejects_degenerate_shares<S: Ciphersuite>() {
+        let (idc, ids) = ("client", "server");
+        let (phi0, phi1) = client_secret::<S>(&Password::from("password123"), idc, ids);
+        let c = client_cipher::<S>(&phi1);
+        let (u, alpha) = client_initial::<S>(&mut OsRng, &phi0);
+        let (v, beta) = server_initial::<S>(&mut OsRng, &phi0);
+        let client = |v| client_compute_key::<S>(b"", idc, ids, &phi0, &phi1, &alpha, u, v);
+        let server = |c, u| server_compute_key::<S>(b"", idc, ids, &phi0, c, &beta, u, v);
 
-        assert_ne!(k_client, k_server);
+        let identity = S::Group::identity();
+        assert_eq!(client(identity).err(), Some(HandshakeError::IdentityShare));
+        assert_eq!(
+            server(c, identity).err(),
+            Some(HandshakeError::IdentityShare)
+        );
+        // Shares of phi0 * N and phi0 * M unblind to the identity
+        assert_eq!(
+            client(S::n() * phi0.expose_secret()).err(),
+            Some(HandshakeError::IdentityKeyMaterial)
+        );
+        assert_eq!(
+            server(c, S::m() * phi0.expose_secret()).err(),
+            Some(HandshakeError::IdentityKeyMaterial)
+        );
+        assert_eq!(
+            server(identity, u).err(),
+            Some(HandshakeError::IdentityKeyMaterial)
+        );
     }
+
+    /// The inputs of an exchange, and a seed for its ephemeral scalars.
+    #[derive(Debug, Clone)]
+    struct Exchange {
+        password: String,
+        idc: String,
+        ids: String,
+        context: Vec<u8>,
+        seed: u64,
+    }
+
+    /// A server whose password or identities differ from the client's, or a
+    /// share changed in transit by adding a multiple of the generator.
+    #[derive(Debug, Clone)]
+    enum Mismatch {
+        Password(String),
+        Idc(String),
+        Ids(String),
+        U(u64),
+        V(u64),
+    }
+
+    /// Identities and passwords, with the empty string and non-ASCII text
+    /// well represented.
+    fn text() -> impl Strategy<Value = String> {
+        prop_oneof![Just(String::new()), "\\PC{0,8}", any::<String>()]
+    }
+
+    fn exchanges() -> impl Strategy<Value = Exchange> {
+        (
+            text(),
+            text(),
+            text(),
+            vec(any::<u8>(), 0..32),
+            any::<u64>(),
+        )
+            .prop_map(|(password, idc, ids, context, seed)| Exchange {
+                password,
+                idc,
+                ids,
+                context,
+                seed,
+            })
+    }
+
+    /// Exchanges with a mismatch that changes them.
+    fn mismatched_exchanges() -> impl Strategy<Value = (Exchange, Mismatch)> {
+        let mismatches = prop_oneof![
+            text().prop_map(Mismatch::Password),
+            text().prop_map(Mismatch::Idc),
+            text().prop_map(Mismatch::Ids),
+            (1..u64::MAX).prop_map(Mismatch::U),
+            (1..u64::MAX).prop_map(Mismatch::V),
+        ];
+        (exchanges(), mismatches).prop_filter("mismatch must change an input", |(e, m)| match m {
+            Mismatch::Password(password) => *password != e.password,
+            Mismatch::Idc(idc) => *idc != e.idc,
+            Mismatch::Ids(ids) => *ids != e.ids,
+            Mismatch::U(_) | Mismatch::V(_) => true,
+        })
+    }
+
+    /// The messages of an exchange as the client and the server see them.
+    struct Transcript<S: Ciphersuite> {
+        client: Exchange,
+        server: Exchange,
+        u_offset: Scalar<S>,
+        v_offset: Scalar<S>,
+    }
+
+    impl<S: Ciphersuite> Transcript<S> {
+        fn new(exchange: &Exchange, mismatch: Option<&Mismatch>) -> Self {
+            let mut server = exchange.clone();
+            let (mut u_offset, mut v_offset) = (Scalar::<S>::ZERO, Scalar::<S>::ZERO);
+            match mismatch.cloned() {
+                None => (),
+                Some(Mismatch::Password(password)) => server.password = password,
+                Some(Mismatch::Idc(idc)) => server.idc = idc,
+                Some(Mismatch::Ids(ids)) => server.ids = ids,
+                Some(Mismatch::U(offset)) => u_offset = Scalar::<S>::from(offset),
+                Some(Mismatch::V(offset)) => v_offset = Scalar::<S>::from(offset),
+            }
+            Self {
+                client: exchange.clone(),
+                server,
+                u_offset,
+                v_offset,
+            }
+        }
+
+        fn client_secrets(&self) -> (SecretScalar<S>, SecretScalar<S>) {
+            let password = Password::new(self.client.password.clone());
+            client_secret::<S>(&password, &self.client.idc, &self.client.ids)
+        }
+
+        /// The secrets the client registered with the server. The server's
+        /// identities only enter the exchange, so that the property shows
+        /// they are bound into the keys.
+        fn server_secrets(&self) -> (SecretScalar<S>, SecretScalar<S>) {
+            let password = Password::new(self.server.password.clone());
+            client_secret::<S>(&password, &self.client.idc, &self.client.ids)
+        }
+
+        /// The keys of [`client_compute_key`] and [`server_compute_key`].
+        fn keys(&self) -> (SessionKey<S>, SessionKey<S>) {
+            let mut rng = ChaCha20Rng::seed_from_u64(self.client.seed);
+            let (phi0, phi1) = self.client_secrets();
+            let (server_phi0, server_phi1) = self.server_secrets();
+            let c = client_cipher::<S>(&server_phi1);
+
+            let (u, alpha) = client_initial::<S>(&mut rng, &phi0);
+            let (v, beta) = server_initial::<S>(&mut rng, &server_phi0);
+            let (server_u, client_v) = (
+                u + S::Group::generator() * self.u_offset,
+                v + S::Group::generator() * self.v_offset,
+            );
+
+            let (client, server) = (&self.client, &self.server);
+            let k_client = client_compute_key::<S>(
+                &client.context,
+                &client.idc,
+                &client.ids,
+                &phi0,
+                &phi1,
+                &alpha,
+                u,
+                client_v,
+            )
+            .unwrap();
+            let k_server = server_compute_key::<S>(
+                &server.context,
+                &server.idc,
+                &server.ids,
+                &server_phi0,
+                c,
+                &beta,
+                server_u,
+                v,
+            )
+            .unwrap();
+            (k_client, k_server)
+        }
+
+        /// Runs the RFC 9383 handshake and returns both sides' shared keys,
+        /// or `None` if either side rejected the other's confirmation.
+        fn handshake(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+            let mut rng = ChaCha20Rng::seed_from_u64(self.client.seed);
+            let (phi0, phi1) = self.client_secrets();
+            let (server_phi0, server_phi1) = self.server_secrets();
+            let c = client_cipher::<S>(&server_phi1);
+            let (client, server) = (&self.client, &self.server);
+
+            let (client_handshake, mut message) = ClientHandshake::start(
+                &mut rng,
+                &client.context,
+                &client.idc,
+                &client.ids,
+                phi0,
+                phi1,
+            );
+            message.u += S::Group::generator() * self.u_offset;
+            let (server_handshake, response) = ServerHandshake::respond(
+                &mut rng,
+                &server.context,
+                &server.idc,
+                &server.ids,
+                &server_phi0,
+                c,
+                message,
+            )
+            .ok()?;
+            let response = ServerMessage {
+                v: response.v + S::Group::generator() * self.v_offset,
+                confirm_v: response.confirm_v,
+            };
+
+            let client_keys = client_handshake.finish(response).ok()?;
+            let server_keys = server_handshake.finish(&client_keys.confirm_p).ok()?;
+            Some((
+                client_keys.key.expose_secret().to_vec(),
+                server_keys.key.expose_secret().to_vec(),
+            ))
+        }
+    }
+
+    /// Every exchange computes a dozen multiplications, which are slow in
+    /// unoptimized builds.
+    fn config() -> ProptestConfig {
+        ProptestConfig::with_cases(32)
+    }
+
+    fn matching_inputs_agree<S: Ciphersuite>() {
+        proptest!(config(), |(exchange in exchanges())| {
+            let transcript = Transcript::<S>::new(&exchange, None);
+            let (k_client, k_server) = transcript.keys();
+            prop_assert_eq!(k_client.expose_secret(), k_server.expose_secret());
+
+            let (key_client, key_server) = transcript.handshake().expect("handshake succeeds");
+            prop_assert_eq!(key_client, key_server);
+        });
+    }
+
+    fn any_mismatch_disagrees<S: Ciphersuite>() {
+        proptest!(config(), |((exchange, mismatch) in mismatched_exchanges())| {
+            let transcript = Transcript::<S>::new(&exchange, Some(&mismatch));
+            let (k_client, k_server) = transcript.keys();
+            prop_assert_ne!(k_client.expose_secret(), k_server.expose_secret());
+            prop_assert!(transcript.handshake().is_none());
+        });
+    }
+
+    suite_tests!(
+        distinct_a_b_g,
+        correct_password_same_key,
+        wrong_password_different_key,
+        context_changes_key,
+        compute_key_rejects_degenerate_shares,
+        matching_inputs_agree,
+        any_mismatch_disagrees,
+    );
 }
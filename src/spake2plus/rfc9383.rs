@@ -0,0 +1,506 @@
+//! SPAKE2+ following RFC 9383.
+//!
+//! The group arithmetic is the same as in the parent module, but the password
+//! derivation, the transcript TT, the key schedule and the key confirmation
+//! messages follow the RFC. In RFC terms the client is the Prover and the
+//! server the Verifier; `phi0`/`phi1` are w0/w1, `c` is L, `u`/`v` are
+//! shareP/shareV, `w`/`d` are Z/V and `a_point`/`b_point` are M/N.
+//!
+//! The group, hash, KDF and MAC are given by the [`Ciphersuite`]; all
+//! supported groups have prime order, so the cofactor h is 1.
+
+use group::{Group, ff::PrimeField};
+use rand_core::CryptoRngCore;
+use sha2::Digest;
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{
+    client_key_material,
+    handshake::HandshakeError,
+    kdf::{KdfError, PasswordKdf},
+    secret::{Password, SecretScalar, SessionKey},
+    server_key_material,
+};
+use crate::ciphersuite::{Ciphersuite, Output, Scalar};
+
+/// Length of the per-user salt for the password stretching.
+pub const SALT_LEN: usize = 16;
+
+/// Length of w0s and w1s: the byte length of the group order plus 64 bits, so
+/// that the reduction modulo the order is unbiased.
+pub(crate) fn secret_seed_len<S: Ciphersuite>() -> usize {
+    (Scalar::<S>::NUM_BITS as usize).div_ceil(8) + 8
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExportError {
+    #[error("cannot export {0} bytes, at most 255 hash lengths are supported")]
+    InvalidLength(usize),
+}
+
+/// Keys derived from a completed exchange.
+pub struct SessionKeys<S: Ciphersuite> {
+    /// K_shared, the key handed to the application.
+    pub key: SessionKey<S>,
+    /// confirmP, sent by the client to prove it derived the same keys.
+    pub confirm_p: Output<S>,
+    /// confirmV, sent by the server to prove it derived the same keys.
+    pub confirm_v: Output<S>,
+    /// Secret for `export_keying_material`, independent of `key`.
+    exporter_secret: SessionKey<S>,
+}
+
+impl<S: Ciphersuite> SessionKeys<S> {
+    /// Derives `len` bytes of keying material for the purpose named by
+    /// `label`, in the style of the TLS exporter.
+    ///
+    /// The output is HKDF over a secret bound to the whole transcript TT,
+    /// with the length-prefixed `label` and `context` as info. Different
+    /// labels or contexts give independent keys, and both sides of the
+    /// exchange derive the same ones.
+    pub fn export_keying_material(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        len: usize,
+    ) -> Result<Zeroizing<Vec<u8>>, ExportError> {
+        if len > 255 * <S::Hash as Digest>::output_size() {
+            return Err(ExportError::InvalidLength(len));
+        }
+        let mut info = Vec::new();
+        append_prefixed(&mut info, label);
+        append_prefixed(&mut info, context);
+
+        let mut output = Zeroizing::new(vec![0u8; len]);
+        S::kdf(
+            None,
+            self.exporter_secret.expose_secret(),
+            &info,
+            &mut output,
+        );
+        Ok(output)
+    }
+}
+
+/// Appends `data` to `buf` prefixed with its length as an 8-byte
+/// little-endian integer, as done for every field of TT.
+pub(crate) fn append_prefixed(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    buf.extend_from_slice(data);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn transcript<S: Ciphersuite>(
+    context: &[u8],
+    idc: &str,
+    ids: &str,
+    u: S::Group,
+    v: S::Group,
+    w: S::Group,
+    d: S::Group,
+    phi0: &SecretScalar<S>,
+) -> Zeroizing<Vec<u8>> {
+    let mut tt = Zeroizing::new(Vec::new());
+    append_prefixed(&mut tt, context);
+    append_prefixed(&mut tt, idc.as_bytes());
+    append_prefixed(&mut tt, ids.as_bytes());
+    append_prefixed(&mut tt, &S::encode_element(&S::m()));
+    append_prefixed(&mut tt, &S::encode_element(&S::n()));
+    append_prefixed(&mut tt, &S::encode_element(&u));
+    append_prefixed(&mut tt, &S::encode_element(&v));
+    append_prefixed(&mut tt, &S::encode_element(&w));
+    append_prefixed(&mut tt, &S::encode_element(&d));
+    append_prefixed(
+        &mut tt,
+        &Zeroizing::new(S::encode_scalar(phi0.expose_secret())),
+    );
+    tt
+}
+
+fn key_schedule<S: Ciphersuite>(tt: &[u8], u: S::Group, v: S::Group) -> SessionKeys<S> {
+    let mut k_main = S::hash(tt);
+
+    let mut confirmation_keys = Zeroizing::new(vec![0u8; 2 * k_main.len()]);
+    S::kdf(None, &k_main, b"ConfirmationKeys", &mut confirmation_keys);
+    let (k_confirm_p, k_confirm_v) = confirmation_keys.split_at(k_main.len());
+
+    let mut key = Output::<S>::default();
+    S::kdf(None, &k_main, b"SharedKey", &mut key);
+    let mut exporter_secret = Output::<S>::default();
+    S::kdf(None, &k_main, b"ExporterSecret", &mut exporter_secret);
+    k_main.as_mut_slice().zeroize();
+
+    SessionKeys {
+        key: SessionKey::new(key),
+        confirm_p: S::mac(k_confirm_p, &S::encode_element(&v)),
+        confirm_v: S::mac(k_confirm_v, &S::encode_element(&u)),
+        exporter_secret: SessionKey::new(exporter_secret),
+    }
+}
+
+/// Aborts if the peer's share is the identity. The groups have prime order,
+/// so there are no other low-order elements to check for.
+pub(super) fn check_share<S: Ciphersuite>(share: S::Group) -> Result<(), HandshakeError> {
+    if bool::from(share.is_identity()) {
+        return Err(HandshakeError::IdentityShare);
+    }
+    Ok(())
+}
+
+/// Aborts if w or d is the identity, which happens when the peer's share
+/// cancels the blinding exactly, or when c is the identity.
+pub(super) fn check_derived<S: Ciphersuite>(
+    w: S::Group,
+    d: S::Group,
+) -> Result<(), HandshakeError> {
+    if bool::from(w.is_identity() | d.is_identity()) {
+        return Err(HandshakeError::IdentityKeyMaterial);
+    }
+    Ok(())
+}
+
+/// Generates a random salt for a new registration.
+pub fn generate_salt(rng: &mut impl CryptoRngCore) -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Derives (phi0, phi1) from the password and both identities.
+///
+/// The length-prefixed encoding of the password and identities is stretched
+/// with `kdf` and the user's `salt` into w0s || w1s, which are then reduced
+/// modulo the group order.
+pub fn client_secret<S: Ciphersuite>(
+    password: &Password,
+    idc: &str,
+    ids: &str,
+    salt: &[u8; SALT_LEN],
+    kdf: &PasswordKdf,
+) -> Result<(SecretScalar<S>, SecretScalar<S>), KdfError> {
+    let mut input = Zeroizing::new(Vec::new());
+    append_prefixed(&mut input, password.expose_secret().as_bytes());
+    append_prefixed(&mut input, idc.as_bytes());
+    append_prefixed(&mut input, ids.as_bytes());
+
+    let seed_len = secret_seed_len::<S>();
+    let mut output = Zeroizing::new(vec![0u8; 2 * seed_len]);
+    kdf.derive(&input, salt, &mut output)?;
+    let (w0s, w1s) = output.split_at(seed_len);
+    Ok((
+        SecretScalar::new(S::reduce_scalar(w0s)),
+        SecretScalar::new(S::reduce_scalar(w1s)),
+    ))
+}
+
+/// Derives the client's keys, consuming the ephemeral `alpha`. Use
+/// [`ClientHandshake`](super::handshake::ClientHandshake) outside this module.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn client_compute_keys<S: Ciphersuite>(
+    context: &[u8],
+    idc: &str,
+    ids: &str,
+    phi0: &SecretScalar<S>,
+    phi1: &SecretScalar<S>,
+    alpha: SecretScalar<S>,
+    u: S::Group,
+    v: S::Group,
+) -> Result<SessionKeys<S>, HandshakeError> {
+    check_share::<S>(v)?;
+    let (w, d) = client_key_material::<S>(phi0, phi1, &alpha, v);
+    check_derived::<S>(w, d)?;
+    Ok(key_schedule(
+        &transcript::<S>(context, idc, ids, u, v, w, d, phi0),
+        u,
+        v,
+    ))
+}
+
+/// Derives the server's keys, consuming the ephemeral `beta`. Use
+/// [`ServerHandshake`](super::handshake::ServerHandshake) outside this module.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn server_compute_keys<S: Ciphersuite>(
+    context: &[u8],
+    idc: &str,
+    ids: &str,
+    phi0: &SecretScalar<S>,
+    c: S::Group,
+    beta: SecretScalar<S>,
+    u: S::Group,
+    v: S::Group,
+) -> Result<SessionKeys<S>, HandshakeError> {
+    check_share::<S>(u)?;
+    let (w, d) = server_key_material::<S>(phi0, c, &beta, u);
+    check_derived::<S>(w, d)?;
+    Ok(key_schedule(
+        &transcript::<S>(context, idc, ids, u, v, w, d, phi0),
+        u,
+        v,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ciphersuite::{P256, Ristretto255, suite_tests},
+        spake2plus::{
+            client_cipher, client_initial, client_initial_with_scalar,
+            handshake::{ClientHandshake, ServerHandshake},
+            server_initial, server_initial_with_scalar,
+        },
+    };
+    use rand::rngs::OsRng;
+    use std::collections::HashMap;
+
+    const CONTEXT: &[u8] = b"rusty-pake test";
+    const KDF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+    const SALT: [u8; SALT_LEN] = *b"0123456789abcdef";
+
+    fn exchange<S: Ciphersuite>(
+        password: &str,
+        registered_password: &str,
+        client_context: &[u8],
+        server_context: &[u8],
+    ) -> (SessionKeys<S>, SessionKeys<S>) {
+        let idc = "client";
+        let ids = "server";
+
+        let registered_password = Password::from(registered_password);
+        let (phi0, phi1) = client_secret::<S>(&registered_password, idc, ids, &SALT, &KDF).unwrap();
+        let c = client_cipher::<S>(&phi1);
+
+        let password = Password::from(password);
+        let (phi0_c, phi1_c) = client_secret::<S>(&password, idc, ids, &SALT, &KDF).unwrap();
+        let (u, alpha) = client_initial::<S>(&mut OsRng, &phi0_c);
+        let (v, beta) = server_initial::<S>(&mut OsRng, &phi0);
+
+        let client =
+            client_compute_keys(client_context, idc, ids, &phi0_c, &phi1_c, alpha, u, v).unwrap();
+        let server = server_compute_keys(server_context, idc, ids, &phi0, c, beta, u, v).unwrap();
+        (client, server)
+    }
+
+    fn exposed_secret<S: Ciphersuite>(
+        idc: &str,
+        ids: &str,
+        salt: &[u8; SALT_LEN],
+        kdf: &PasswordKdf,
+    ) -> (Scalar<S>, Scalar<S>) {
+        let password = Password::from("password123");
+        let (phi0, phi1) = client_secret::<S>(&password, idc, ids, salt, kdf).unwrap();
+        (*phi0.expose_secret(), *phi1.expose_secret())
+    }
+
+    #[test]
+    fn append_prefixed_uses_8_byte_little_endian_length() {
+        let mut buf = Vec::new();
+        append_prefixed(&mut buf, b"abc");
+        append_prefixed(&mut buf, b"");
+        assert_eq!(buf, b"\x03\0\0\0\0\0\0\0abc\0\0\0\0\0\0\0\0");
+    }
+
+    #[test]
+    fn secret_seed_len_adds_64_bits() {
+        assert_eq!(secret_seed_len::<Ristretto255>(), 40);
+        assert_eq!(secret_seed_len::<P256>(), 40);
+    }
+
+    /// A test vector in the `name = value` format of RFC 9383, appendix C.
+    struct Vector(HashMap<&'static str, &'static str>);
+
+    impl Vector {
+        fn parse(text: &'static str) -> Self {
+            let fields = text
+                .lines()
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.split_once(" = ").expect("line is `name = value`"))
+                .collect();
+            Vector(fields)
+        }
+
+        fn text(&self, name: &str) -> &'static str {
+            self.0[name].trim_matches('\'')
+        }
+
+        fn bytes(&self, name: &str) -> Vec<u8> {
+            hex::decode(self.0[name].trim_start_matches("0x")).unwrap()
+        }
+
+        fn scalar<S: Ciphersuite>(&self, name: &str) -> SecretScalar<S> {
+            SecretScalar::new(S::decode_scalar(&self.bytes(name)).unwrap())
+        }
+
+        fn element<S: Ciphersuite>(&self, name: &str) -> S::Group {
+            S::decode_element(&self.bytes(name)).unwrap()
+        }
+    }
+
+    fn check_test_vector<S: Ciphersuite>(text: &'static str) {
+        let vector = Vector::parse(text);
+        let context = vector.text("Context").as_bytes();
+        let idc = vector.text("idProver");
+        let ids = vector.text("idVerifier");
+        let w0 = || vector.scalar::<S>("w0");
+        let w1 = || vector.scalar::<S>("w1");
+        let l = client_cipher::<S>(&w1());
+        assert_eq!(S::encode_element(&l), vector.bytes("L"));
+
+        let (share_p, x) = client_initial_with_scalar(&w0(), vector.scalar("x"));
+        assert_eq!(S::encode_element(&share_p), vector.bytes("shareP"));
+        let (share_v, y) = server_initial_with_scalar(&w0(), vector.scalar("y"));
+        assert_eq!(S::encode_element(&share_v), vector.bytes("shareV"));
+
+        // Z and V as computed by the Verifier; the Prover's values enter TT
+        // below and must match for K_main to agree.
+        let z = (share_p - S::m() * w0().expose_secret()) * y.expose_secret();
+        let v = l * y.expose_secret();
+        assert_eq!(z, vector.element::<S>("Z"));
+        assert_eq!(v, vector.element::<S>("V"));
+        let prover_z = (share_v - S::n() * w0().expose_secret()) * x.expose_secret();
+        let prover_v = (share_v - S::n() * w0().expose_secret()) * w1().expose_secret();
+        assert_eq!((prover_z, prover_v), (z, v));
+
+        let tt = transcript::<S>(context, idc, ids, share_p, share_v, z, v, &w0());
+        assert_eq!(*tt, vector.bytes("TT"));
+        let k_main = S::hash(&tt);
+        assert_eq!(k_main.as_slice(), vector.bytes("K_main"));
+        let mut confirmation_keys = vec![0u8; 2 * k_main.len()];
+        S::kdf(None, &k_main, b"ConfirmationKeys", &mut confirmation_keys);
+        let (k_confirm_p, k_confirm_v) = confirmation_keys.split_at(k_main.len());
+        assert_eq!(k_confirm_p, vector.bytes("K_confirmP"));
+        assert_eq!(k_confirm_v, vector.bytes("K_confirmV"));
+
+        let client =
+            client_compute_keys::<S>(context, idc, ids, &w0(), &w1(), x, share_p, share_v).unwrap();
+        let server =
+            server_compute_keys::<S>(context, idc, ids, &w0(), l, y, share_p, share_v).unwrap();
+        for keys in [&client, &server] {
+            assert_eq!(keys.confirm_p.as_slice(), vector.bytes("confirmP"));
+            assert_eq!(keys.confirm_v.as_slice(), vector.bytes("confirmV"));
+            assert_eq!(keys.key.expose_secret(), vector.bytes("K_shared"));
+        }
+
+        // The same values through the handshake API, as they go on the wire
+        let (client, message) =
+            ClientHandshake::start_with_scalar(context, idc, ids, w0(), w1(), vector.scalar("x"));
+        assert_eq!(S::encode_element(&message.u), vector.bytes("shareP"));
+        let (server, response) = ServerHandshake::respond_with_scalar(
+            context,
+            idc,
+            ids,
+            &w0(),
+            l,
+            vector.scalar("y"),
+            message,
+        )
+        .unwrap();
+        assert_eq!(S::encode_element(&response.v), vector.bytes("shareV"));
+        assert_eq!(response.confirm_v.as_slice(), vector.bytes("confirmV"));
+        let client = client.finish(response).unwrap();
+        let server = server.finish(&client.confirm_p).unwrap();
+        assert_eq!(server.key.expose_secret(), vector.bytes("K_shared"));
+    }
+
+    #[test]
+    fn rfc9383_p256_sha256_test_vector() {
+        check_test_vector::<P256>(include_str!("vectors/p256-sha256.txt"));
+    }
+
+    fn correct_password_same_keys<S: Ciphersuite>() {
+        let (client, server) = exchange::<S>("password123", "password123", CONTEXT, CONTEXT);
+        assert_eq!(client.key.expose_secret(), server.key.expose_secret());
+        assert_eq!(client.confirm_p, server.confirm_p);
+        assert_eq!(client.confirm_v, server.confirm_v);
+        assert_ne!(client.confirm_p, client.confirm_v);
+    }
+
+    fn wrong_password_confirmation_fails<S: Ciphersuite>() {
+        let (client, server) = exchange::<S>("wrongpassword", "password123", CONTEXT, CONTEXT);
+        assert_ne!(client.key.expose_secret(), server.key.expose_secret());
+        assert_ne!(client.confirm_p, server.confirm_p);
+        assert_ne!(client.confirm_v, server.confirm_v);
+    }
+
+    fn different_context_confirmation_fails<S: Ciphersuite>() {
+        let (client, server) = exchange::<S>("password123", "password123", CONTEXT, b"other");
+        assert_ne!(client.key.expose_secret(), server.key.expose_secret());
+        assert_ne!(client.confirm_v, server.confirm_v);
+    }
+
+    fn identities_are_length_prefixed<S: Ciphersuite>() {
+        // Without length prefixes ("ab", "c") and ("a", "bc") would collide.
+        assert_ne!(
+            exposed_secret::<S>("ab", "c", &SALT, &KDF),
+            exposed_secret::<S>("a", "bc", &SALT, &KDF)
+        );
+    }
+
+    fn kdf_parameters_change_secrets<S: Ciphersuite>() {
+        let cheaper = PasswordKdf::Argon2id { m: 32, t: 1, p: 1 };
+        assert_ne!(
+            exposed_secret::<S>("client", "server", &SALT, &KDF),
+            exposed_secret::<S>("client", "server", &SALT, &cheaper)
+        );
+    }
+
+    fn salt_changes_secrets<S: Ciphersuite>() {
+        let salt = generate_salt(&mut OsRng);
+        assert_ne!(salt, generate_salt(&mut OsRng));
+        assert_ne!(
+            exposed_secret::<S>("client", "server", &SALT, &KDF),
+            exposed_secret::<S>("client", "server", &salt, &KDF)
+        );
+    }
+
+    fn exported_keys_agree_and_are_independent<S: Ciphersuite>() {
+        let (client, server) = exchange::<S>("password123", "password123", CONTEXT, CONTEXT);
+        let export = |keys: &SessionKeys<S>, label: &[u8], context: &[u8], len| {
+            keys.export_keying_material(label, context, len).unwrap()
+        };
+
+        let encryption = export(&client, b"encryption", b"", 32);
+        assert_eq!(encryption, export(&server, b"encryption", b"", 32));
+        assert_ne!(encryption, export(&client, b"mac", b"", 32));
+        assert_ne!(encryption, export(&client, b"encryption", b"v2", 32));
+        assert_ne!(encryption.as_slice(), client.key.expose_secret());
+        // The label and context are length-prefixed
+        assert_ne!(
+            export(&client, b"ab", b"c", 32),
+            export(&client, b"a", b"bc", 32)
+        );
+        assert_eq!(export(&client, b"token", b"", 100).len(), 100);
+    }
+
+    fn exported_keys_differ_between_sessions<S: Ciphersuite>() {
+        let (first, _) = exchange::<S>("password123", "password123", CONTEXT, CONTEXT);
+        let (second, _) = exchange::<S>("password123", "password123", CONTEXT, CONTEXT);
+        assert_ne!(
+            first.export_keying_material(b"encryption", b"", 32),
+            second.export_keying_material(b"encryption", b"", 32)
+        );
+    }
+
+    fn export_rejects_overlong_output<S: Ciphersuite>() {
+        let (client, _) = exchange::<S>("password123", "password123", CONTEXT, CONTEXT);
+        let max = 255 * <S::Hash as Digest>::output_size();
+        assert!(client.export_keying_material(b"label", b"", max).is_ok());
+        assert_eq!(
+            client.export_keying_material(b"label", b"", max + 1),
+            Err(ExportError::InvalidLength(max + 1))
+        );
+    }
+
+    suite_tests!(
+        exported_keys_agree_and_are_independent,
+        exported_keys_differ_between_sessions,
+        export_rejects_overlong_output,
+        correct_password_same_keys,
+        wrong_password_confirmation_fails,
+        different_context_confirmation_fails,
+        identities_are_length_prefixed,
+        kdf_parameters_change_secrets,
+        salt_changes_secrets,
+    );
+}
@@ -0,0 +1,154 @@
+//! Wrappers for secret values.
+//!
+//! The wrapped values are zeroized when dropped, or earlier through
+//! `Zeroize`, and are redacted in `Debug` output, so they cannot end up in
+//! logs by accident. The raw value is only available through `expose_secret`.
+
+use std::fmt;
+
+use zeroize::Zeroize;
+
+use crate::ciphersuite::{Ciphersuite, Output, Scalar};
+
+/// A user's password.
+pub struct Password(String);
+
+impl Password {
+    pub fn new(password: String) -> Self {
+        Self(password)
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Password {
+    fn from(password: &str) -> Self {
+        Self(password.to_string())
+    }
+}
+
+impl Zeroize for Password {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for Password {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Password([REDACTED])")
+    }
+}
+
+/// A secret scalar such as phi0, phi1 or an ephemeral alpha or beta.
+pub struct SecretScalar<S: Ciphersuite>(Scalar<S>);
+
+impl<S: Ciphersuite> SecretScalar<S> {
+    pub fn new(scalar: Scalar<S>) -> Self {
+        Self(scalar)
+    }
+
+    pub fn expose_secret(&self) -> &Scalar<S> {
+        &self.0
+    }
+}
+
+impl<S: Ciphersuite> Zeroize for SecretScalar<S> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<S: Ciphersuite> Drop for SecretScalar<S> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<S: Ciphersuite> fmt::Debug for SecretScalar<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretScalar([REDACTED])")
+    }
+}
+
+/// A session key derived from an exchange.
+pub struct SessionKey<S: Ciphersuite>(Output<S>);
+
+impl<S: Ciphersuite> SessionKey<S> {
+    pub fn new(key: Output<S>) -> Self {
+        Self(key)
+    }
+
+    pub fn expose_secret(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<S: Ciphersuite> Zeroize for SessionKey<S> {
+    fn zeroize(&mut self) {
+        self.0.as_mut_slice().zeroize();
+    }
+}
+
+impl<S: Ciphersuite> Drop for SessionKey<S> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<S: Ciphersuite> fmt::Debug for SessionKey<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SessionKey([REDACTED])")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphersuite::suite_tests;
+    use group::ff::Field;
+
+    #[test]
+    fn password_debug_is_redacted() {
+        let password = Password::from("hunter2");
+        assert_eq!(format!("{:?}", password), "Password([REDACTED])");
+        assert_eq!(password.expose_secret(), "hunter2");
+    }
+
+    fn secrets_debug_is_redacted<S: Ciphersuite>() {
+        let scalar = SecretScalar::<S>::new(Scalar::<S>::ONE);
+        assert_eq!(format!("{:?}", scalar), "SecretScalar([REDACTED])");
+        assert_eq!(*scalar.expose_secret(), Scalar::<S>::ONE);
+
+        let key = SessionKey::<S>::new(S::hash(b"key"));
+        assert_eq!(format!("{:?}", key), "SessionKey([REDACTED])");
+        assert_eq!(key.expose_secret(), S::hash(b"key").as_slice());
+    }
+
+    #[test]
+    fn zeroize_clears_password() {
+        // Drop calls zeroize, check that it clears the wrapped value
+        let mut password = Password::from("hunter2");
+        password.zeroize();
+        assert_eq!(password.expose_secret(), "");
+    }
+
+    fn zeroize_clears_secrets<S: Ciphersuite>() {
+        let mut scalar = SecretScalar::<S>::new(Scalar::<S>::ONE);
+        scalar.zeroize();
+        assert_eq!(*scalar.expose_secret(), Scalar::<S>::ZERO);
+
+        let mut key = SessionKey::<S>::new(S::hash(b"key"));
+        key.zeroize();
+        assert!(key.expose_secret().iter().all(|&b| b == 0));
+    }
+
+    suite_tests!(secrets_debug_is_redacted, zeroize_clears_secrets);
+}
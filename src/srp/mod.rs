@@ -0,0 +1,625 @@
+//! SRP-6a over the RFC 5054 groups, for legacy clients that cannot move to
+//! SPAKE2+ yet.
+//!
+//! The hash is SHA-1, as RFC 5054 requires, and x = H(s | H(I | ":" | P))
+//! involves no password stretching. The verifier is therefore far cheaper to
+//! attack than a SPAKE2+ registration, and new clients should not use it.
+//!
+//! RFC 5054 stops at the premaster secret S. Key confirmation follows SRP-6a
+//! as implemented by most libraries: K = H(S),
+//! M1 = H(H(N) xor H(g) | H(I) | s | A | B | K) and M2 = H(A | M1 | K), with
+//! N and g unpadded and A, B and S padded to the length of N.
+
+mod groups;
+
+use std::{fmt, str::FromStr, sync::LazyLock};
+
+use crypto_bigint::{
+    Encoding, NonZero, U4096,
+    modular::runtime_mod::{DynResidue, DynResidueParams},
+};
+use rand_core::CryptoRngCore;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::{ciphersuite::constant_time_eq, spake2plus::secret::Password};
+
+/// Large enough for the largest group; smaller moduli fit in the same type.
+type Int = U4096;
+const LIMBS: usize = Int::LIMBS;
+
+/// Length of the salts generated by [`generate_salt`].
+pub const SALT_LEN: usize = 16;
+
+/// Length of the key confirmation proofs M1 and M2, that of SHA-1.
+pub const PROOF_LEN: usize = 20;
+
+/// Length of the ephemeral private exponents a and b.
+const PRIVATE_LEN: usize = 32;
+
+/// Bit lengths of the exponents, so exponentiation does not run over all
+/// 4096 bits. They are fixed bounds rather than the actual lengths, which
+/// would leak through timing.
+const PRIVATE_BITS: usize = PRIVATE_LEN * 8;
+const HASH_BITS: usize = PROOF_LEN * 8;
+/// a + u * x is below 2^(PRIVATE_BITS) + 2^(2 * HASH_BITS).
+const CLIENT_EXPONENT_BITS: usize = 2 * HASH_BITS + 1;
+
+const GROUPS: [SrpGroup; 5] = [
+    SrpGroup::G1024,
+    SrpGroup::G1536,
+    SrpGroup::G2048,
+    SrpGroup::G3072,
+    SrpGroup::G4096,
+];
+
+/// The Montgomery parameters of each group, in the order of [`GROUPS`].
+static PARAMS: LazyLock<[DynResidueParams<LIMBS>; 5]> =
+    LazyLock::new(|| GROUPS.map(|group| DynResidueParams::new(&group.n())));
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SrpError {
+    #[error("unknown SRP group: {0}")]
+    UnknownGroup(String),
+
+    #[error("public value is longer than the group modulus")]
+    InvalidLength,
+
+    #[error("public value is zero modulo N")]
+    InvalidPublicValue,
+
+    #[error("scrambling parameter u is zero")]
+    ZeroScramble,
+
+    #[error("key confirmation failed")]
+    ConfirmationFailed,
+}
+
+/// One of the groups of RFC 5054 appendix A, by the size of its modulus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrpGroup {
+    G1024,
+    G1536,
+    G2048,
+    G3072,
+    G4096,
+}
+
+impl Default for SrpGroup {
+    /// The smallest group RFC 5054 does not discourage for new deployments.
+    fn default() -> Self {
+        SrpGroup::G2048
+    }
+}
+
+impl SrpGroup {
+    /// Size of the modulus in bits.
+    pub fn bits(self) -> usize {
+        match self {
+            SrpGroup::G1024 => 1024,
+            SrpGroup::G1536 => 1536,
+            SrpGroup::G2048 => 2048,
+            SrpGroup::G3072 => 3072,
+            SrpGroup::G4096 => 4096,
+        }
+    }
+
+    /// Length of the padded encoding of group elements.
+    pub fn element_len(self) -> usize {
+        self.bits() / 8
+    }
+
+    fn n(self) -> Int {
+        let hex = match self {
+            SrpGroup::G1024 => groups::N_1024,
+            SrpGroup::G1536 => groups::N_1536,
+            SrpGroup::G2048 => groups::N_2048,
+            SrpGroup::G3072 => groups::N_3072,
+            SrpGroup::G4096 => groups::N_4096,
+        };
+        let bytes = hex::decode(hex).expect("group moduli are valid hex");
+        int_from_be(&bytes).expect("group moduli fit in 4096 bits")
+    }
+
+    fn g(self) -> Int {
+        match self {
+            SrpGroup::G1024 | SrpGroup::G1536 | SrpGroup::G2048 => Int::from_u64(2),
+            SrpGroup::G3072 | SrpGroup::G4096 => Int::from_u64(5),
+        }
+    }
+
+    fn params(self) -> DynResidueParams<LIMBS> {
+        let index = GROUPS.iter().position(|&group| group == self);
+        PARAMS[index.expect("every group is listed")]
+    }
+
+    /// Reduces `x` modulo N into Montgomery form.
+    fn residue(self, x: &Int) -> DynResidue<LIMBS> {
+        let params = self.params();
+        let n = NonZero::new(*params.modulus()).expect("group moduli are non-zero");
+        DynResidue::new(&x.rem(&n), params)
+    }
+
+    /// PAD(x): `x` as big-endian bytes, left-padded to the length of N.
+    fn pad(self, x: &Int) -> Vec<u8> {
+        x.to_be_bytes()[Int::BYTES - self.element_len()..].to_vec()
+    }
+
+    /// Checks a verifier received at registration, so that it is not only
+    /// rejected by [`SrpServer::start`] once the client tries to log in.
+    pub fn check_verifier(self, verifier: &[u8]) -> Result<(), SrpError> {
+        self.decode_public(verifier).map(|_| ())
+    }
+
+    /// Decodes a public value, rejecting values that are zero modulo N.
+    fn decode_public(self, bytes: &[u8]) -> Result<DynResidue<LIMBS>, SrpError> {
+        if bytes.len() > self.element_len() {
+            return Err(SrpError::InvalidLength);
+        }
+        let value = self.residue(&int_from_be(bytes).ok_or(SrpError::InvalidLength)?);
+        if value.retrieve() == Int::ZERO {
+            return Err(SrpError::InvalidPublicValue);
+        }
+        Ok(value)
+    }
+
+    /// The multiplier k = H(N | PAD(g)).
+    fn k(self) -> Int {
+        hash_int(&[&self.pad(&self.n()), &self.pad(&self.g())])
+    }
+}
+
+impl fmt::Display for SrpGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rfc5054-{}", self.bits())
+    }
+}
+
+impl FromStr for SrpGroup {
+    type Err = SrpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rfc5054-1024" => Ok(SrpGroup::G1024),
+            "rfc5054-1536" => Ok(SrpGroup::G1536),
+            "rfc5054-2048" => Ok(SrpGroup::G2048),
+            "rfc5054-3072" => Ok(SrpGroup::G3072),
+            "rfc5054-4096" => Ok(SrpGroup::G4096),
+            _ => Err(SrpError::UnknownGroup(s.to_string())),
+        }
+    }
+}
+
+/// Parses big-endian bytes, left-padding them to the width of [`Int`].
+fn int_from_be(bytes: &[u8]) -> Option<Int> {
+    let mut padded = Zeroizing::new([0u8; Int::BYTES]);
+    let offset = Int::BYTES.checked_sub(bytes.len())?;
+    padded[offset..].copy_from_slice(bytes);
+    Some(Int::from_be_slice(&*padded))
+}
+
+/// The big-endian bytes of `x` without leading zeros.
+fn unpadded(x: &Int) -> Vec<u8> {
+    let bytes = x.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    bytes[start..].to_vec()
+}
+
+/// SHA-1 of the concatenation of `parts`.
+fn sha1(parts: &[&[u8]]) -> [u8; PROOF_LEN] {
+    let mut hasher = Sha1::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+fn hash_int(parts: &[&[u8]]) -> Int {
+    int_from_be(&sha1(parts)).expect("SHA-1 output fits in 4096 bits")
+}
+
+/// x = H(s | H(I | ":" | P)).
+fn compute_x(identity: &str, password: &Password, salt: &[u8]) -> Zeroizing<Int> {
+    let inner = Zeroizing::new(sha1(&[
+        identity.as_bytes(),
+        b":",
+        password.expose_secret().as_bytes(),
+    ]));
+    Zeroizing::new(hash_int(&[salt, &*inner]))
+}
+
+/// Generates a random salt for a new verifier.
+pub fn generate_salt(rng: &mut impl CryptoRngCore) -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Computes the verifier v = g^x, padded to the length of N, which the
+/// server stores instead of the password.
+pub fn compute_verifier(
+    group: SrpGroup,
+    identity: &str,
+    password: &Password,
+    salt: &[u8],
+) -> Zeroizing<Vec<u8>> {
+    let x = compute_x(identity, password, salt);
+    let v = group
+        .residue(&group.g())
+        .pow_bounded_exp(&*x, HASH_BITS)
+        .retrieve();
+    Zeroizing::new(group.pad(&v))
+}
+
+/// Draws a nonzero private exponent from `rng`, resampling on zero.
+fn random_private(rng: &mut impl CryptoRngCore) -> Zeroizing<Int> {
+    let mut bytes = Zeroizing::new([0u8; PRIVATE_LEN]);
+    loop {
+        rng.fill_bytes(&mut *bytes);
+        let private =
+            Zeroizing::new(int_from_be(&*bytes).expect("private exponents fit in 4096 bits"));
+        if *private != Int::ZERO {
+            return private;
+        }
+    }
+}
+
+/// u = H(PAD(A) | PAD(B)), rejecting zero.
+fn scramble(group: SrpGroup, a: &Int, b: &Int) -> Result<Int, SrpError> {
+    let u = hash_int(&[&group.pad(a), &group.pad(b)]);
+    if u == Int::ZERO {
+        return Err(SrpError::ZeroScramble);
+    }
+    Ok(u)
+}
+
+/// K = H(PAD(S)), and the proofs M1 and M2.
+fn proofs(
+    group: SrpGroup,
+    identity: &str,
+    salt: &[u8],
+    a: &Int,
+    b: &Int,
+    premaster: &Int,
+) -> (Zeroizing<Vec<u8>>, [u8; PROOF_LEN], [u8; PROOF_LEN]) {
+    let key = Zeroizing::new(sha1(&[&group.pad(premaster)]).to_vec());
+    let mut group_hash = sha1(&[&unpadded(&group.n())]);
+    for (byte, g) in group_hash.iter_mut().zip(sha1(&[&unpadded(&group.g())])) {
+        *byte ^= g;
+    }
+    let (a, b) = (group.pad(a), group.pad(b));
+    let m1 = sha1(&[
+        &group_hash,
+        &sha1(&[identity.as_bytes()]),
+        salt,
+        &a,
+        &b,
+        &key,
+    ]);
+    let m2 = sha1(&[&a, &m1, &key]);
+    (key, m1, m2)
+}
+
+/// Server side of an SRP-6a exchange.
+pub struct SrpServer;
+
+impl SrpServer {
+    /// Picks the ephemeral exponent b from `rng` and computes B = k * v + g^b
+    /// from the stored verifier. B is sent to the client along with the salt.
+    pub fn start(
+        rng: &mut impl CryptoRngCore,
+        group: SrpGroup,
+        verifier: &[u8],
+    ) -> Result<(SrpServerAwaitingClient, Vec<u8>), SrpError> {
+        Self::start_with_private(group, verifier, random_private(rng))
+    }
+
+    fn start_with_private(
+        group: SrpGroup,
+        verifier: &[u8],
+        b: Zeroizing<Int>,
+    ) -> Result<(SrpServerAwaitingClient, Vec<u8>), SrpError> {
+        let v = group.decode_public(verifier)?;
+        let g = group.residue(&group.g());
+        let public =
+            (group.residue(&group.k()) * v + g.pow_bounded_exp(&*b, PRIVATE_BITS)).retrieve();
+        let state = SrpServerAwaitingClient {
+            group,
+            private: b,
+            public,
+            verifier: Zeroizing::new(v.retrieve()),
+        };
+        Ok((state, group.pad(&public)))
+    }
+}
+
+/// A server that has sent B and waits for the client's A and M1.
+pub struct SrpServerAwaitingClient {
+    group: SrpGroup,
+    private: Zeroizing<Int>,
+    public: Int,
+    verifier: Zeroizing<Int>,
+}
+
+impl SrpServerAwaitingClient {
+    /// Computes S = (A * v^u)^b and checks the client's proof. Returns the
+    /// session key K and the proof M2 to send back. Aborts if A is zero
+    /// modulo N or u is zero.
+    pub fn finish(
+        self,
+        identity: &str,
+        salt: &[u8],
+        message: &SrpClientMessage,
+    ) -> Result<(Zeroizing<Vec<u8>>, [u8; PROOF_LEN]), SrpError> {
+        let group = self.group;
+        let a = group.decode_public(&message.a)?;
+        let u = scramble(group, &a.retrieve(), &self.public)?;
+
+        let v = group.residue(&self.verifier);
+        let premaster = Zeroizing::new(
+            (a * v.pow_bounded_exp(&u, HASH_BITS))
+                .pow_bounded_exp(&*self.private, PRIVATE_BITS)
+                .retrieve(),
+        );
+        let (key, m1, m2) = proofs(
+            group,
+            identity,
+            salt,
+            &a.retrieve(),
+            &self.public,
+            &premaster,
+        );
+        if !constant_time_eq(&m1, &message.m1) {
+            return Err(SrpError::ConfirmationFailed);
+        }
+        Ok((key, m2))
+    }
+}
+
+/// The client's public value A and proof M1, sent to the server.
+pub struct SrpClientMessage {
+    pub a: Vec<u8>,
+    pub m1: [u8; PROOF_LEN],
+}
+
+/// Client side of an SRP-6a exchange.
+pub struct SrpClient;
+
+impl SrpClient {
+    /// Answers the server's B with A = g^a and the proof M1, computing
+    /// S = (B - k * g^x)^(a + u * x). Aborts if B is zero modulo N or u is
+    /// zero. The ephemeral exponent a is drawn from `rng`.
+    pub fn respond(
+        rng: &mut impl CryptoRngCore,
+        group: SrpGroup,
+        identity: &str,
+        password: &Password,
+        salt: &[u8],
+        b: &[u8],
+    ) -> Result<(SrpClientAwaitingProof, SrpClientMessage), SrpError> {
+        Self::respond_with_private(group, identity, password, salt, b, random_private(rng))
+    }
+
+    fn respond_with_private(
+        group: SrpGroup,
+        identity: &str,
+        password: &Password,
+        salt: &[u8],
+        b: &[u8],
+        a: Zeroizing<Int>,
+    ) -> Result<(SrpClientAwaitingProof, SrpClientMessage), SrpError> {
+        let b = group.decode_public(b)?.retrieve();
+        let g = group.residue(&group.g());
+        let public = g.pow_bounded_exp(&*a, PRIVATE_BITS).retrieve();
+        let u = scramble(group, &public, &b)?;
+        let x = compute_x(identity, password, salt);
+
+        let base =
+            group.residue(&b) - group.residue(&group.k()) * g.pow_bounded_exp(&*x, HASH_BITS);
+        let exponent = Zeroizing::new(a.wrapping_add(&u.wrapping_mul(&*x)));
+        let premaster = Zeroizing::new(
+            base.pow_bounded_exp(&*exponent, CLIENT_EXPONENT_BITS)
+                .retrieve(),
+        );
+
+        let (key, m1, m2) = proofs(group, identity, salt, &public, &b, &premaster);
+        let state = SrpClientAwaitingProof {
+            key,
+            expected_m2: m2,
+        };
+        let message = SrpClientMessage {
+            a: group.pad(&public),
+            m1,
+        };
+        Ok((state, message))
+    }
+}
+
+/// A client that has sent A and M1 and waits for the server's M2.
+pub struct SrpClientAwaitingProof {
+    key: Zeroizing<Vec<u8>>,
+    expected_m2: [u8; PROOF_LEN],
+}
+
+impl SrpClientAwaitingProof {
+    /// Checks the server's proof and releases the session key K.
+    pub fn finish(self, m2: &[u8; PROOF_LEN]) -> Result<Zeroizing<Vec<u8>>, SrpError> {
+        if !constant_time_eq(&self.expected_m2, m2) {
+            return Err(SrpError::ConfirmationFailed);
+        }
+        Ok(self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    type Key = Zeroizing<Vec<u8>>;
+
+    fn int(hex: &str) -> Int {
+        int_from_be(&hex::decode(hex).unwrap()).unwrap()
+    }
+
+    fn private(hex: &str) -> Zeroizing<Int> {
+        Zeroizing::new(int(hex))
+    }
+
+    /// Runs an exchange, returning the client's and the server's key.
+    fn exchange(group: SrpGroup, password: &str, verifier: &[u8]) -> Result<(Key, Key), SrpError> {
+        let salt = [1; SALT_LEN];
+        let (server, b) = SrpServer::start(&mut OsRng, group, verifier)?;
+        let (client, message) = SrpClient::respond(
+            &mut OsRng,
+            group,
+            "alice",
+            &Password::from(password),
+            &salt,
+            &b,
+        )?;
+        let (server_key, m2) = server.finish("alice", &salt, &message)?;
+        Ok((client.finish(&m2)?, server_key))
+    }
+
+    // RFC 5054 appendix B
+    #[test]
+    fn rfc5054_test_vector() {
+        let group = SrpGroup::G1024;
+        let identity = "alice";
+        let password = Password::from("password123");
+        let salt = hex::decode("beb25379d1a8581eb5a727673a2441ee").unwrap();
+
+        assert_eq!(group.k(), int("7556aa045aef2cdd07abaf0f665c3e818913186f"));
+        assert_eq!(
+            *compute_x(identity, &password, &salt),
+            int("94b7555aabe9127cc58ccf4993db6cf84d16c124")
+        );
+
+        let v = compute_verifier(group, identity, &password, &salt);
+        assert_eq!(
+            hex::encode(&*v),
+            "7e273de8696ffc4f4e337d05b4b375beb0dde1569e8fa00a9886d8129bada1f1\
+             822223ca1a605b530e379ba4729fdc59f105b4787e5186f5c671085a1447b52a\
+             48cf1970b4fb6f8400bbf4cebfbb168152e08ab5ea53d15c1aff87b2b9da6e04\
+             e058ad51cc72bfc9033b564e26480d78e955a5e29e7ab245db2be315e2099afb"
+        );
+
+        let a = private("60975527035cf2ad1989806f0407210bc81edc04e2762a56afd529ddda2d4393");
+        let b = private("e487cb59d31ac550471e81f00f6928e01dda08e974a004f49e61f5d105284d20");
+        let (server, big_b) = SrpServer::start_with_private(group, &v, b).unwrap();
+        assert_eq!(
+            hex::encode(&big_b),
+            "bd0c61512c692c0cb6d041fa01bb152d4916a1e77af46ae105393011baf38964\
+             dc46a0670dd125b95a981652236f99d9b681cbf87837ec996c6da04453728610\
+             d0c6ddb58b318885d7d82c7f8deb75ce7bd4fbaa37089e6f9c6059f388838e7a\
+             00030b331eb76840910440b1b27aaeaeeb4012b7d7665238a8e3fb004b117b58"
+        );
+
+        let (client, message) =
+            SrpClient::respond_with_private(group, identity, &password, &salt, &big_b, a).unwrap();
+        assert_eq!(
+            hex::encode(&message.a),
+            "61d5e490f6f1b79547b0704c436f523dd0e560f0c64115bb72557ec44352e890\
+             3211c04692272d8b2d1a5358a2cf1b6e0bfcf99f921530ec8e39356179eae45e\
+             42ba92aeaced825171e1e8b9af6d9c03e1327f44be087ef06530e69f66615261\
+             eef54073ca11cf5858f0edfdfe15efeab349ef5d76988a3672fac47b0769447b"
+        );
+        assert_eq!(
+            scramble(
+                group,
+                &int(&hex::encode(&message.a)),
+                &int(&hex::encode(&big_b))
+            )
+            .unwrap(),
+            int("ce38b9593487da98554ed47d70a7ae5f462ef019")
+        );
+
+        // Both sides derive K = H(S) from the premaster secret of the RFC
+        let premaster = hex::decode(
+            "b0dc82babcf30674ae450c0287745e7990a3381f63b387aaf271a10d233861e3\
+             59b48220f7c4693c9ae12b0a6f67809f0876e2d013800d6c41bb59b6d5979b5c\
+             00a172b4a2a5903a0bdcaf8a709585eb2afafa8f3499b200210dcc1f10eb3394\
+             3cd67fc88a2f39a4be5bec4ec0a3212dc346d7e474b29ede8a469ffeca686e5a",
+        )
+        .unwrap();
+        let (server_key, m2) = server.finish(identity, &salt, &message).unwrap();
+        let client_key = client.finish(&m2).unwrap();
+        assert_eq!(*client_key, sha1(&[&premaster]));
+        assert_eq!(client_key, server_key);
+    }
+
+    #[test]
+    fn all_groups_agree() {
+        for group in GROUPS {
+            let verifier = compute_verifier(
+                group,
+                "alice",
+                &Password::from("password123"),
+                &[1; SALT_LEN],
+            );
+            let (client_key, server_key) = exchange(group, "password123", &verifier).unwrap();
+            assert_eq!(client_key, server_key, "{group}");
+        }
+    }
+
+    #[test]
+    fn wrong_password_fails_confirmation() {
+        let group = SrpGroup::G1024;
+        let verifier = compute_verifier(
+            group,
+            "alice",
+            &Password::from("password123"),
+            &[1; SALT_LEN],
+        );
+        assert_eq!(
+            exchange(group, "wrongpassword", &verifier).err(),
+            Some(SrpError::ConfirmationFailed)
+        );
+    }
+
+    #[test]
+    fn rejects_public_values_zero_mod_n() {
+        let group = SrpGroup::G1024;
+        let password = Password::from("password123");
+        let salt = [1; SALT_LEN];
+        let verifier = compute_verifier(group, "alice", &password, &salt);
+
+        let finish = |a: Vec<u8>| {
+            let (server, _) = SrpServer::start(&mut OsRng, group, &verifier).unwrap();
+            let message = SrpClientMessage {
+                a,
+                m1: [0; PROOF_LEN],
+            };
+            server.finish("alice", &salt, &message).map(|_| ())
+        };
+        assert_eq!(finish(vec![0]), Err(SrpError::InvalidPublicValue));
+        assert_eq!(
+            finish(group.pad(&group.n())),
+            Err(SrpError::InvalidPublicValue)
+        );
+        assert_eq!(finish(vec![1; 129]), Err(SrpError::InvalidLength));
+
+        let respond = |b: &[u8]| {
+            SrpClient::respond(&mut OsRng, group, "alice", &password, &salt, b).map(|_| ())
+        };
+        assert_eq!(respond(&[0; 128]), Err(SrpError::InvalidPublicValue));
+        assert_eq!(
+            respond(&group.pad(&group.n())),
+            Err(SrpError::InvalidPublicValue)
+        );
+    }
+
+    #[test]
+    fn group_names_round_trip() {
+        for group in GROUPS {
+            assert_eq!(group.to_string().parse::<SrpGroup>(), Ok(group));
+            assert_eq!(group.pad(&group.n()).len(), group.element_len());
+        }
+        assert!(matches!(
+            "rfc5054-8192".parse::<SrpGroup>(),
+            Err(SrpError::UnknownGroup(_))
+        ));
+    }
+}
@@ -1,11 +1,43 @@
 use std::sync::Once;
 
-use rusty_pake::{client, server};
+use rand::{SeedableRng, rngs::OsRng};
+use rand_chacha::ChaCha20Rng;
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, P256, Ristretto255},
+    client,
+    opaque::{ClientRegistration, RegistrationResponse},
+    server::{self, Randomness},
+    shared::{
+        CONTEXT, OpaqueRegisterFinishRequest, OpaqueRegisterStartRequest,
+        OpaqueRegisterStartResponseEncoded, PrepareRequestEncoded, PrepareResponseEncoded,
+        SetupRequest,
+    },
+    spake2plus::{self, kdf::PasswordKdf, rfc9383, secret::Password},
+    srp::SrpGroup,
+};
 
 static INIT: Once = Once::new();
 
 async fn setup_server(port: u32, id: &str) {
+    setup_server_with_suite::<Ristretto255>(port, id).await
+}
+
+async fn setup_server_with_suite<S: Ciphersuite>(port: u32, id: &str) {
+    setup_server_with_context::<S>(port, id, CONTEXT).await
+}
+
+async fn setup_server_with_context<S: Ciphersuite>(port: u32, id: &str, context: &[u8]) {
+    setup_server_with_randomness::<S>(port, id, context, Randomness::Os).await
+}
+
+async fn setup_server_with_randomness<S: Ciphersuite>(
+    port: u32,
+    id: &str,
+    context: &[u8],
+    randomness: Randomness,
+) {
     let id = id.to_string();
+    let context = context.to_vec();
 
     INIT.call_once(|| {
         tracing_subscriber::fmt()
@@ -16,7 +48,7 @@ async fn setup_server(port: u32, id: &str) {
     });
 
     tokio::spawn(async move {
-        server::run(port, &id).await;
+        server::run::<S>(port, &id, &context, randomness).await;
     });
 
     let client = reqwest::Client::new();
@@ -48,19 +80,24 @@ async fn test_successful_exchange() {
     let ip = "http://localhost:3001";
     let server_id = "id";
     let client_id = "Alice";
-    let password = "ilovebob123";
+    let password = &Password::from("ilovebob123");
 
     setup_server(3001, server_id).await;
 
-    client::perform_setup(ip, server_id, client_id, password)
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, client_id, password)
         .await
         .unwrap();
 
-    let key = client::perform_exchange(ip, server_id, client_id, password)
+    let exchange = client::perform_exchange::<Ristretto255>(
+        &mut OsRng, ip, server_id, client_id, password, CONTEXT,
+    )
+    .await
+    .unwrap();
+
+    let success = client::perform_verify(ip, client_id, &exchange.confirmation)
         .await
         .unwrap();
-
-    client::perform_verify(ip, client_id, &key).await.unwrap();
+    assert!(success);
 }
 
 #[tokio::test]
@@ -68,21 +105,56 @@ async fn test_wrong_password_exchange() {
     let ip = "http://localhost:3002";
     let server_id = "id";
     let client_id = "Bob";
-    let password = "alice1234";
+    let password = &Password::from("alice1234");
 
     setup_server(3002, server_id).await;
 
-    client::perform_setup(ip, server_id, client_id, password)
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, client_id, password)
         .await
         .unwrap();
 
-    let wrong_password = "alice1234oops";
-    let key = client::perform_exchange(ip, server_id, client_id, wrong_password)
+    // The client rejects the server's confirmation
+    let wrong_password = &Password::from("alice1234oops");
+    let exchange = client::perform_exchange::<Ristretto255>(
+        &mut OsRng,
+        ip,
+        server_id,
+        client_id,
+        wrong_password,
+        CONTEXT,
+    )
+    .await;
+    assert!(exchange.is_err());
+}
+
+#[tokio::test]
+async fn test_wrong_confirmation_verify() {
+    let ip = "http://localhost:3005";
+    let server_id = "id";
+    let client_id = "Mallory";
+    let password = &Password::from("password");
+
+    setup_server(3005, server_id).await;
+
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, client_id, password)
         .await
         .unwrap();
 
-    let success = client::perform_verify(ip, client_id, &key).await.unwrap();
-    assert_eq!(success, false)
+    let exchange = client::perform_exchange::<Ristretto255>(
+        &mut OsRng, ip, server_id, client_id, password, CONTEXT,
+    )
+    .await
+    .unwrap();
+
+    // Sending the session key instead of the confirmation is rejected
+    let success = client::perform_verify(
+        ip,
+        client_id,
+        &hex::encode(exchange.keys.key.expose_secret()),
+    )
+    .await
+    .unwrap();
+    assert!(!success)
 }
 
 #[tokio::test]
@@ -90,31 +162,42 @@ async fn test_multiple_exchanges() {
     let ip = "http://localhost:3003";
     let server_id = "popular-server";
     let client_id = "Bob";
-    let password = "alice1234";
+    let password = &Password::from("alice1234");
 
     setup_server(3003, server_id).await;
 
-    client::perform_setup(ip, server_id, client_id, password)
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, client_id, password)
         .await
         .unwrap();
 
     // Exchange 1
-    let key1 = client::perform_exchange(ip, server_id, client_id, password)
+    let exchange1 = client::perform_exchange::<Ristretto255>(
+        &mut OsRng, ip, server_id, client_id, password, CONTEXT,
+    )
+    .await
+    .unwrap();
+
+    let success1 = client::perform_verify(ip, client_id, &exchange1.confirmation)
         .await
         .unwrap();
 
-    let success1 = client::perform_verify(ip, client_id, &key1).await.unwrap();
-
     // Exchange 2
-    let key2 = client::perform_exchange(ip, server_id, client_id, password)
+    let exchange2 = client::perform_exchange::<Ristretto255>(
+        &mut OsRng, ip, server_id, client_id, password, CONTEXT,
+    )
+    .await
+    .unwrap();
+
+    let success2 = client::perform_verify(ip, client_id, &exchange2.confirmation)
         .await
         .unwrap();
 
-    let success2 = client::perform_verify(ip, client_id, &key2).await.unwrap();
-
     assert!(success1);
     assert!(success2);
-    assert_ne!(key1, key2);
+    assert_ne!(
+        exchange1.keys.key.expose_secret(),
+        exchange2.keys.key.expose_secret()
+    );
 }
 
 #[tokio::test]
@@ -135,14 +218,21 @@ async fn test_multiple_clients() {
         .into_iter()
         .map(|(id, password)| {
             tokio::spawn(async move {
+                let password = &Password::from(password);
                 let server_id = client::get_server_id(ip).await.unwrap();
-                client::perform_setup(ip, &server_id, id, password)
+                client::perform_setup::<Ristretto255>(&mut OsRng, ip, &server_id, id, password)
                     .await
                     .unwrap();
-                let key = client::perform_exchange(ip, &server_id, &id, &password)
-                    .await
-                    .unwrap();
-                assert_eq!(true, client::perform_verify(ip, &id, &key).await.unwrap());
+                let exchange = client::perform_exchange::<Ristretto255>(
+                    &mut OsRng, ip, &server_id, id, password, CONTEXT,
+                )
+                .await
+                .unwrap();
+                assert!(
+                    client::perform_verify(ip, id, &exchange.confirmation)
+                        .await
+                        .unwrap()
+                );
             })
         })
         .collect();
@@ -151,3 +241,400 @@ async fn test_multiple_clients() {
         assert!(handle.await.is_ok())
     }
 }
+
+#[tokio::test]
+async fn test_p256_exchange() {
+    let ip = "http://localhost:3006";
+    let server_id = "id";
+    let client_id = "Alice";
+    let password = &Password::from("ilovebob123");
+
+    setup_server_with_suite::<P256>(3006, server_id).await;
+
+    let suite = client::get_server_suite(ip).await.unwrap();
+    assert_eq!(suite, P256::NAME);
+
+    client::perform_setup::<P256>(&mut OsRng, ip, server_id, client_id, password)
+        .await
+        .unwrap();
+
+    let exchange =
+        client::perform_exchange::<P256>(&mut OsRng, ip, server_id, client_id, password, CONTEXT)
+            .await
+            .unwrap();
+
+    let success = client::perform_verify(ip, client_id, &exchange.confirmation)
+        .await
+        .unwrap();
+    assert!(success);
+}
+
+#[tokio::test]
+async fn test_mismatched_suite_setup() {
+    let ip = "http://localhost:3007";
+    let server_id = "id";
+
+    setup_server_with_suite::<P256>(3007, server_id).await;
+
+    // Ristretto255 encodings are rejected by a P-256 server
+    let result = client::perform_setup::<Ristretto255>(
+        &mut OsRng,
+        ip,
+        server_id,
+        "Alice",
+        &Password::from("ilovebob123"),
+    )
+    .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_mismatched_context_exchange() {
+    let ip = "http://localhost:3008";
+    let server_id = "id";
+    let client_id = "Alice";
+    let password = &Password::from("ilovebob123");
+
+    setup_server_with_context::<Ristretto255>(3008, server_id, b"deployment-a").await;
+
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, client_id, password)
+        .await
+        .unwrap();
+
+    // The server's key confirmation does not match under another context
+    let exchange = client::perform_exchange::<Ristretto255>(
+        &mut OsRng,
+        ip,
+        server_id,
+        client_id,
+        password,
+        b"deployment-b",
+    )
+    .await;
+    assert!(exchange.is_err());
+
+    let exchange = client::perform_exchange::<Ristretto255>(
+        &mut OsRng,
+        ip,
+        server_id,
+        client_id,
+        password,
+        b"deployment-a",
+    )
+    .await
+    .unwrap();
+    let success = client::perform_verify(ip, client_id, &exchange.confirmation)
+        .await
+        .unwrap();
+    assert!(success);
+}
+
+#[tokio::test]
+async fn test_setup_rejects_weak_kdf() {
+    let ip = "http://localhost:3014";
+    let server_id = "id";
+    let client_id = "Alice";
+    let password = &Password::from("ilovebob123");
+
+    setup_server(3014, server_id).await;
+
+    // A registration that is cheap to crack offline is refused
+    let salt = rfc9383::generate_salt(&mut OsRng);
+    let kdf = PasswordKdf::Pbkdf2 { rounds: 1 };
+    let (phi0, phi1) =
+        rfc9383::client_secret::<Ristretto255>(password, client_id, server_id, &salt, &kdf)
+            .unwrap();
+    let c = spake2plus::client_cipher::<Ristretto255>(&phi1);
+    let request = SetupRequest::<Ristretto255>::new(client_id.into(), phi0, c, salt, kdf);
+    let response = reqwest::Client::new()
+        .post(format!("{}/setup", ip))
+        .json(&request.encode())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // and leaves the id free for one that is not
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, client_id, password)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_cpace_exchange() {
+    let ip = "http://localhost:3009";
+    let server_id = "id";
+    let client_id = "Alice";
+    let password = &Password::from("ilovebob123");
+
+    setup_server(3009, server_id).await;
+
+    client::perform_cpace_setup(&mut OsRng, ip, server_id, client_id, password)
+        .await
+        .unwrap();
+
+    let exchange =
+        client::perform_cpace_exchange(&mut OsRng, ip, server_id, client_id, password, CONTEXT)
+            .await
+            .unwrap();
+    let success = client::perform_cpace_verify(ip, client_id, &exchange.confirmation)
+        .await
+        .unwrap();
+    assert!(success);
+
+    // A handshake is only verified once
+    let success = client::perform_cpace_verify(ip, client_id, &exchange.confirmation)
+        .await
+        .unwrap();
+    assert!(!success);
+
+    let wrong_password = &Password::from("ilovebob1234");
+    let exchange = client::perform_cpace_exchange(
+        &mut OsRng,
+        ip,
+        server_id,
+        client_id,
+        wrong_password,
+        CONTEXT,
+    )
+    .await;
+    assert!(exchange.is_err());
+
+    // A SPAKE2+ registration cannot be used for CPace, nor the other way
+    // around
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, "Bob", password)
+        .await
+        .unwrap();
+    let exchange =
+        client::perform_cpace_exchange(&mut OsRng, ip, server_id, "Bob", password, CONTEXT).await;
+    assert!(exchange.is_err());
+    let exchange = client::perform_exchange::<Ristretto255>(
+        &mut OsRng, ip, server_id, client_id, password, CONTEXT,
+    )
+    .await;
+    assert!(exchange.is_err());
+}
+
+#[tokio::test]
+async fn test_opaque_registration_and_login() {
+    let ip = "http://localhost:3010";
+    let server_id = "id";
+    let client_id = "Alice";
+    let password = &Password::from("ilovebob123");
+
+    setup_server(3010, server_id).await;
+
+    let export_key =
+        client::perform_opaque_registration(&mut OsRng, ip, server_id, client_id, password)
+            .await
+            .unwrap();
+
+    // Registering the same client twice is rejected
+    let result =
+        client::perform_opaque_registration(&mut OsRng, ip, server_id, client_id, password).await;
+    assert!(result.is_err());
+
+    let keys =
+        client::perform_opaque_login(&mut OsRng, ip, server_id, client_id, password, CONTEXT)
+            .await
+            .unwrap();
+    assert_eq!(keys.export_key, export_key);
+
+    let wrong_password = &Password::from("ilovebob1234");
+    let result = client::perform_opaque_login(
+        &mut OsRng,
+        ip,
+        server_id,
+        client_id,
+        wrong_password,
+        CONTEXT,
+    )
+    .await;
+    assert!(result.is_err());
+
+    // Unknown clients fail the same way as a wrong password
+    let result =
+        client::perform_opaque_login(&mut OsRng, ip, server_id, "Bob", password, CONTEXT).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_opaque_login_uses_the_registered_kdf() {
+    let ip = "http://localhost:3016";
+    let server_id = "id";
+    let client_id = "Alice";
+    let password = &Password::from("ilovebob123");
+
+    setup_server(3016, server_id).await;
+
+    // Registers with `kdf` instead of the default, returning the server's
+    // answer to the record and the export key
+    let register = |kdf: PasswordKdf| async move {
+        let client = reqwest::Client::new();
+        let (registration, request) = ClientRegistration::start(&mut OsRng, password);
+        let request = OpaqueRegisterStartRequest::new(client_id.into(), request.blinded);
+        let response: OpaqueRegisterStartResponseEncoded = client
+            .post(format!("{}/opaque/register/start", ip))
+            .json(&request.encode())
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let response = response.decode().unwrap();
+        let (record, export_key) = registration
+            .finish(
+                &mut OsRng,
+                RegistrationResponse {
+                    evaluated: response.evaluated,
+                    server_public_key: response.server_public_key,
+                },
+                &kdf,
+                client_id,
+                server_id,
+            )
+            .unwrap();
+        let request = OpaqueRegisterFinishRequest::new(
+            client_id.into(),
+            record.client_public_key,
+            record.masking_key,
+            record.envelope,
+            kdf,
+        );
+        let response = client
+            .post(format!("{}/opaque/register/finish", ip))
+            .json(&request.encode())
+            .send()
+            .await
+            .unwrap();
+        (response.status(), export_key)
+    };
+
+    // A record that is cheap to crack offline is refused
+    let (status, _) = register(PasswordKdf::Pbkdf2 { rounds: 1 }).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+    // and the login is handed the KDF of the one that is stored
+    let (status, export_key) = register(PasswordKdf::Pbkdf2 { rounds: 600_000 }).await;
+    assert!(status.is_success());
+    let keys =
+        client::perform_opaque_login(&mut OsRng, ip, server_id, client_id, password, CONTEXT)
+            .await
+            .unwrap();
+    assert_eq!(keys.export_key, export_key);
+}
+
+#[tokio::test]
+async fn test_srp_exchange() {
+    let ip = "http://localhost:3011";
+    let server_id = "id";
+    let password = &Password::from("ilovebob123");
+
+    setup_server(3011, server_id).await;
+
+    client::perform_srp_setup(&mut OsRng, ip, "Alice", password, SrpGroup::G2048)
+        .await
+        .unwrap();
+    client::perform_srp_exchange(&mut OsRng, ip, "Alice", password)
+        .await
+        .unwrap();
+
+    let wrong_password = &Password::from("ilovebob1234");
+    let result = client::perform_srp_exchange(&mut OsRng, ip, "Alice", wrong_password).await;
+    assert!(result.is_err());
+
+    // Legacy and SPAKE2+ clients share the session store, but not their routes
+    client::perform_setup::<Ristretto255>(&mut OsRng, ip, server_id, "Bob", password)
+        .await
+        .unwrap();
+    let result = client::perform_srp_exchange(&mut OsRng, ip, "Bob", password).await;
+    assert!(result.is_err());
+    let result = client::perform_exchange::<Ristretto255>(
+        &mut OsRng, ip, server_id, "Alice", password, CONTEXT,
+    )
+    .await;
+    assert!(result.is_err());
+    let result = client::perform_srp_setup(&mut OsRng, ip, "Bob", password, SrpGroup::G2048).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_unknown_clients_fail_at_confirmation() {
+    let ip = "http://localhost:3015";
+    let server_id = "id";
+    let password = &Password::from("ilovebob123");
+
+    setup_server(3015, server_id).await;
+
+    // An unknown id gets a salt and KDF like a registered one, the same
+    // every time
+    let prepare = || async {
+        let response = reqwest::Client::new()
+            .post(format!("{}/prepare", ip))
+            .json(&PrepareRequestEncoded::new("Mallory".into()))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        let response: PrepareResponseEncoded = response.json().await.unwrap();
+        response.decode().unwrap()
+    };
+    let (first, second) = (prepare().await, prepare().await);
+    assert_eq!(first.salt, second.salt);
+    assert_eq!(first.kdf, PasswordKdf::default());
+
+    // and the exchanges only fail at confirmation
+    let exchange = client::perform_exchange::<Ristretto255>(
+        &mut OsRng, ip, server_id, "Mallory", password, CONTEXT,
+    )
+    .await;
+    assert!(exchange.err().unwrap().to_string().contains("handshake"));
+    let exchange =
+        client::perform_cpace_exchange(&mut OsRng, ip, server_id, "Mallory", password, CONTEXT)
+            .await;
+    assert!(exchange.err().unwrap().to_string().contains("handshake"));
+    let exchange = client::perform_srp_exchange(&mut OsRng, ip, "Mallory", password).await;
+    assert!(exchange.unwrap_err().to_string().contains("401"));
+}
+
+#[tokio::test]
+async fn test_seeded_randomness_is_reproducible() {
+    let server_id = "id";
+    let password = &Password::from("ilovebob123");
+
+    // Two servers with the same seed, each with a client with the same seed,
+    // go through the same exchange
+    let mut confirmations = Vec::new();
+    for port in [3012, 3013] {
+        let ip = &format!("http://localhost:{}", port);
+        setup_server_with_randomness::<Ristretto255>(
+            port,
+            server_id,
+            CONTEXT,
+            Randomness::Seeded(42),
+        )
+        .await;
+
+        let rng = &mut ChaCha20Rng::seed_from_u64(7);
+        client::perform_setup::<Ristretto255>(rng, ip, server_id, "Alice", password)
+            .await
+            .unwrap();
+        let exchange = client::perform_exchange::<Ristretto255>(
+            rng, ip, server_id, "Alice", password, CONTEXT,
+        )
+        .await
+        .unwrap();
+        confirmations.push(exchange.confirmation);
+    }
+    assert_eq!(confirmations[0], confirmations[1]);
+
+    // A fresh client seed on the same server gives a different exchange
+    let ip = "http://localhost:3013";
+    let rng = &mut ChaCha20Rng::seed_from_u64(8);
+    let exchange =
+        client::perform_exchange::<Ristretto255>(rng, ip, server_id, "Alice", password, CONTEXT)
+            .await
+            .unwrap();
+    assert_ne!(exchange.confirmation, confirmations[1]);
+}
@@ -0,0 +1,402 @@
+//! Model-based test of the server's session lifecycle. Random sequences of
+//! SPAKE2+, SRP and CPace setups, prepares, exchanges and verifications by
+//! several clients are sent to an in-process router, and every response is
+//! checked against a reference model of what the session store should hold.
+//!
+//! Every operation is allowed in every state, so the model needs no
+//! preconditions and proptest shrinks a failing sequence by dropping
+//! operations from it.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header::CONTENT_TYPE},
+};
+use group::Group;
+use proptest::{collection::vec, prelude::*};
+use rand::rngs::OsRng;
+use rusty_pake::{
+    ciphersuite::{Ciphersuite, Ristretto255},
+    cpace,
+    server::{self, Randomness},
+    shared::{
+        CONTEXT, CpaceSetupRequest, ExchangeRequestEncoded, ExchangeResponseEncoded,
+        PrepareRequestEncoded, SetupRequest, SrpSetupRequest, VerifyRequestEncoded,
+    },
+    spake2plus::{
+        client_cipher,
+        handshake::{ClientHandshake, ServerMessage},
+        kdf::PasswordKdf,
+        rfc9383::{SALT_LEN, client_secret},
+        secret::{Password, SecretScalar},
+    },
+    srp::{self, SrpGroup},
+};
+use serde::Serialize;
+use tower::ServiceExt;
+
+type S = Ristretto255;
+
+const SERVER_ID: &str = "server";
+const CLIENTS: [&str; 3] = ["alice", "bob", "Zoë"];
+const PASSWORDS: [&str; 2] = ["correct horse battery staple", "hunter2"];
+/// A cheap KDF for the client secrets. The server never rederives them, so
+/// the KDF it is told about at setup need not match.
+const KDF: PasswordKdf = PasswordKdf::Argon2id { m: 64, t: 1, p: 1 };
+const SALT: [u8; SALT_LEN] = [0; SALT_LEN];
+
+/// Which confirmation a client sends to `/verify`.
+#[derive(Debug, Clone, Copy)]
+enum Confirmation {
+    /// From the client's latest accepted exchange, or a wrong one if the
+    /// client rejected it.
+    Latest,
+    /// From the exchange before the latest, which the latest replaced.
+    Stale,
+    /// Not hex at all.
+    Malformed,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Setup {
+        client: usize,
+        password: usize,
+    },
+    SrpSetup {
+        client: usize,
+        password: usize,
+    },
+    CpaceSetup {
+        client: usize,
+        password: usize,
+    },
+    Prepare {
+        client: usize,
+    },
+    Exchange {
+        client: usize,
+        password: usize,
+    },
+    /// An exchange whose share is the identity element.
+    IdentityExchange {
+        client: usize,
+    },
+    Verify {
+        client: usize,
+        confirmation: Confirmation,
+    },
+}
+
+fn ops() -> impl Strategy<Value = Op> {
+    let client = 0..CLIENTS.len();
+    let password = 0..PASSWORDS.len();
+    let confirmation = prop_oneof![
+        2 => Just(Confirmation::Latest),
+        1 => Just(Confirmation::Stale),
+        1 => Just(Confirmation::Malformed),
+    ];
+    prop_oneof![
+        2 => (client.clone(), password.clone())
+            .prop_map(|(client, password)| Op::Setup { client, password }),
+        1 => (client.clone(), password.clone())
+            .prop_map(|(client, password)| Op::SrpSetup { client, password }),
+        1 => (client.clone(), password.clone())
+            .prop_map(|(client, password)| Op::CpaceSetup { client, password }),
+        1 => client.clone().prop_map(|client| Op::Prepare { client }),
+        3 => (client.clone(), password)
+            .prop_map(|(client, password)| Op::Exchange { client, password }),
+        1 => client.clone().prop_map(|client| Op::IdentityExchange { client }),
+        3 => (client, confirmation)
+            .prop_map(|(client, confirmation)| Op::Verify { client, confirmation }),
+    ]
+}
+
+/// The observable result of an operation: the response status and, for
+/// exchanges the server answered, whether the client accepted the server's
+/// confirmation.
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    status: StatusCode,
+    accepted: Option<bool>,
+}
+
+impl From<StatusCode> for Outcome {
+    fn from(status: StatusCode) -> Self {
+        Self {
+            status,
+            accepted: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Registration {
+    Spake2Plus { password: usize },
+    Srp,
+    Cpace,
+}
+
+#[derive(Debug)]
+struct ModelSession {
+    registration: Registration,
+    /// The pending handshake, if any, and whether the client that started
+    /// it used the registered password.
+    pending: Option<bool>,
+}
+
+/// What the server's session store should hold.
+#[derive(Debug, Default)]
+struct Model {
+    sessions: HashMap<usize, ModelSession>,
+    /// Clients with a handshake pending against a fake registration, from
+    /// an exchange before they registered with SPAKE2+, if ever.
+    fake_pending: HashSet<usize>,
+}
+
+impl Model {
+    /// Applies `op` and returns the outcome the server must produce.
+    fn apply(&mut self, op: Op) -> Outcome {
+        match op {
+            Op::Setup { client, password } => {
+                self.register(client, Registration::Spake2Plus { password })
+            }
+            Op::SrpSetup { client, .. } => self.register(client, Registration::Srp),
+            Op::CpaceSetup { client, .. } => self.register(client, Registration::Cpace),
+            // ids without a salt get a fake one
+            Op::Prepare { .. } => StatusCode::OK.into(),
+            Op::Exchange { client, password } => match self.sessions.get_mut(&client) {
+                Some(ModelSession {
+                    registration:
+                        Registration::Spake2Plus {
+                            password: registered,
+                        },
+                    pending,
+                }) => {
+                    let accepted = password == *registered;
+                    *pending = Some(accepted);
+                    Outcome {
+                        status: StatusCode::OK,
+                        accepted: Some(accepted),
+                    }
+                }
+                // other ids are answered from a fake registration, which
+                // the client rejects
+                _ => {
+                    self.fake_pending.insert(client);
+                    Outcome {
+                        status: StatusCode::OK,
+                        accepted: Some(false),
+                    }
+                }
+            },
+            // the share is rejected after the registration is looked up,
+            // and leaves any pending handshake in place
+            Op::IdentityExchange { .. } => StatusCode::BAD_REQUEST.into(),
+            Op::Verify {
+                client,
+                confirmation,
+            } => {
+                // any attempt discards the pending handshake, the one in the
+                // session first
+                let pending = self
+                    .sessions
+                    .get_mut(&client)
+                    .and_then(|session| session.pending.take())
+                    .or_else(|| self.fake_pending.remove(&client).then_some(false));
+                match (pending, confirmation) {
+                    (None, _) | (Some(_), Confirmation::Malformed) => StatusCode::BAD_REQUEST,
+                    (Some(true), Confirmation::Latest) => StatusCode::OK,
+                    (Some(_), _) => StatusCode::UNAUTHORIZED,
+                }
+                .into()
+            }
+        }
+    }
+
+    fn register(&mut self, client: usize, registration: Registration) -> Outcome {
+        if self.sessions.contains_key(&client) {
+            return StatusCode::BAD_REQUEST.into();
+        }
+        self.sessions.insert(
+            client,
+            ModelSession {
+                registration,
+                pending: None,
+            },
+        );
+        StatusCode::OK.into()
+    }
+}
+
+/// The confirmations a client keeps from its exchanges.
+#[derive(Default)]
+struct ClientState {
+    latest: Option<String>,
+    stale: Option<String>,
+}
+
+/// The router under test and its clients.
+struct System {
+    app: Router,
+    clients: [ClientState; CLIENTS.len()],
+}
+
+impl System {
+    fn new() -> Self {
+        Self {
+            app: server::router::<S>(SERVER_ID, CONTEXT, Randomness::Os),
+            clients: Default::default(),
+        }
+    }
+
+    /// Posts `body` as JSON to `path` and returns the response status and
+    /// body.
+    async fn post(&self, path: &str, body: &impl Serialize) -> (StatusCode, Vec<u8>) {
+        let request = Request::post(path)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap();
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    async fn apply(&mut self, op: Op) -> Outcome {
+        match op {
+            Op::Setup { client, password } => {
+                let (phi0, phi1) = secrets(client, password);
+                let request = SetupRequest::<S>::new(
+                    CLIENTS[client].into(),
+                    phi0,
+                    client_cipher::<S>(&phi1),
+                    [password as u8; 16],
+                    PasswordKdf::default(),
+                );
+                self.post("/setup", &request.encode()).await.0.into()
+            }
+            Op::SrpSetup { client, password } => {
+                let salt = srp::generate_salt(&mut OsRng).to_vec();
+                let password = Password::new(PASSWORDS[password].into());
+                let verifier =
+                    srp::compute_verifier(SrpGroup::G1024, CLIENTS[client], &password, &salt);
+                let request =
+                    SrpSetupRequest::new(CLIENTS[client].into(), SrpGroup::G1024, salt, verifier);
+                self.post("/srp/setup", &request.encode()).await.0.into()
+            }
+            Op::CpaceSetup { client, password } => {
+                let salt = [password as u8; 16];
+                let kdf = PasswordKdf::default();
+                let password = Password::new(PASSWORDS[password].into());
+                let prs = cpace::password_related_string(
+                    &password,
+                    CLIENTS[client],
+                    SERVER_ID,
+                    &salt,
+                    &kdf,
+                )
+                .unwrap();
+                let request = CpaceSetupRequest::new(CLIENTS[client].into(), prs, salt, kdf);
+                self.post("/cpace/setup", &request.encode()).await.0.into()
+            }
+            Op::Prepare { client } => {
+                let request = PrepareRequestEncoded::new(CLIENTS[client].into());
+                self.post("/prepare", &request).await.0.into()
+            }
+            Op::Exchange { client, password } => {
+                let (phi0, phi1) = secrets(client, password);
+                let (handshake, message) = ClientHandshake::start(
+                    &mut OsRng,
+                    CONTEXT,
+                    CLIENTS[client],
+                    SERVER_ID,
+                    phi0,
+                    phi1,
+                );
+                let request = ExchangeRequestEncoded {
+                    id: CLIENTS[client].into(),
+                    u: hex::encode(S::encode_element(&message.u)),
+                };
+                let (status, body) = self.post("/exchange", &request).await;
+                if status != StatusCode::OK {
+                    return status.into();
+                }
+
+                let response = serde_json::from_slice::<ExchangeResponseEncoded>(&body)
+                    .unwrap()
+                    .decode::<S>()
+                    .unwrap();
+                let keys = handshake.finish(ServerMessage {
+                    v: response.v,
+                    confirm_v: response.confirm_v,
+                });
+                let accepted = keys.is_ok();
+                // a client that rejected the server still has to send
+                // something for the model to verify against
+                let confirm_p = match keys {
+                    Ok(keys) => hex::encode(keys.confirm_p),
+                    Err(_) => wrong_confirmation(),
+                };
+                let state = &mut self.clients[client];
+                state.stale = state.latest.replace(confirm_p);
+                Outcome {
+                    status,
+                    accepted: Some(accepted),
+                }
+            }
+            Op::IdentityExchange { client } => {
+                let request = ExchangeRequestEncoded {
+                    id: CLIENTS[client].into(),
+                    u: hex::encode(S::encode_element(&<S as Ciphersuite>::Group::identity())),
+                };
+                self.post("/exchange", &request).await.0.into()
+            }
+            Op::Verify {
+                client,
+                confirmation,
+            } => {
+                let state = &self.clients[client];
+                let confirm_p = match confirmation {
+                    Confirmation::Latest => state.latest.clone(),
+                    Confirmation::Stale => state.stale.clone(),
+                    Confirmation::Malformed => Some("not hex".into()),
+                }
+                .unwrap_or_else(wrong_confirmation);
+                let request = VerifyRequestEncoded::new(CLIENTS[client].into(), confirm_p);
+                self.post("/verify", &request).await.0.into()
+            }
+        }
+    }
+}
+
+fn secrets(client: usize, password: usize) -> (SecretScalar<S>, SecretScalar<S>) {
+    let password = Password::new(PASSWORDS[password].into());
+    client_secret::<S>(&password, CLIENTS[client], SERVER_ID, &SALT, &KDF).unwrap()
+}
+
+/// A well-formed confirmation that matches no handshake.
+fn wrong_confirmation() -> String {
+    hex::encode(S::hash(b"wrong confirmation"))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn sessions_follow_the_model(ops in vec(ops(), 1..40)) {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let mut model = Model::default();
+            let mut system = System::new();
+            for (step, &op) in ops.iter().enumerate() {
+                let expected = model.apply(op);
+                let actual = system.apply(op).await;
+                prop_assert_eq!(actual, expected, "step {}: {:?}", step, op);
+            }
+            Ok(())
+        })?;
+    }
+}